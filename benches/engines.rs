@@ -0,0 +1,81 @@
+//! Compares the bytecode VM with the tree-walking evaluator.
+//!
+//! Run with `cargo bench`; each program is timed once per engine.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use scheme::{Engine, Interpreter};
+
+const FIB: &str = "
+(define (fib n)
+  (if (< n 2)
+      n
+      (+ (fib (- n 1)) (fib (- n 2)))))";
+
+const TAK: &str = "
+(define (tak x y z)
+  (if (< y x)
+      (tak (tak (- x 1) y z)
+           (tak (- y 1) z x)
+           (tak (- z 1) x y))
+      z))";
+
+const NQUEENS: &str = "
+(define (nqueens n)
+  (define (one-to n)
+    (let loop ((i n) (l '()))
+      (if (= i 0) l (loop (- i 1) (cons i l)))))
+  (define (append-to x y)
+    (if (eq? x '()) y (cons (car x) (append-to (cdr x) y))))
+  (define (ok? row dist placed)
+    (if (eq? placed '())
+        #t
+        (and (if (= (car placed) (+ row dist)) #f #t)
+             (if (= (car placed) (- row dist)) #f #t)
+             (ok? row (+ dist 1) (cdr placed)))))
+  (define (try x y z)
+    (if (eq? x '())
+        (if (eq? y '()) 1 0)
+        (+ (if (ok? (car x) 1 z)
+               (try (append-to (cdr x) y) '() (cons (car x) z))
+               0)
+           (try (cdr x) (cons (car x) y) z))))
+  (try (one-to n) '() '()))";
+
+const STRINGS: &str = r#"
+(define (build n)
+  (let loop ((i 0) (s ""))
+    (if (= i n)
+        s
+        (loop (+ i 1) (string-append s (number->string i) ",")))))"#;
+
+fn interpreter(engine: Engine, program: &str) -> Interpreter {
+    let interp = Interpreter::with_engine(engine);
+    interp.eval_str(program).unwrap();
+    interp
+}
+
+fn engines(c: &mut Criterion) {
+    let programs = [
+        ("fib", FIB, "(fib 20)"),
+        ("tak", TAK, "(tak 18 12 6)"),
+        ("nqueens", NQUEENS, "(nqueens 7)"),
+        ("string building", STRINGS, "(build 1000)"),
+    ];
+    for (name, program, call) in programs.iter() {
+        let mut group = c.benchmark_group(*name);
+        group.sample_size(20);
+        for (label, engine) in [
+            ("bytecode", Engine::Bytecode),
+            ("tree-walking", Engine::TreeWalking),
+        ] {
+            let interp = interpreter(engine, program);
+            group.bench_function(BenchmarkId::from_parameter(label), |b| {
+                b.iter(|| interp.eval_str(call).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, engines);
+criterion_main!(benches);
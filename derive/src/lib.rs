@@ -0,0 +1,244 @@
+//! `#[derive(IntoLisp, FromLisp)]` for the `scheme` crate.
+//!
+//! - A struct with named fields maps to an association list keyed by its
+//!   field names, `((x . 1) (y . 2))`.
+//! - A struct with one unnamed field maps to that field's value, and one with
+//!   several maps to a list of them.
+//! - An enum maps a unit variant to a symbol naming it, `red`, and any other
+//!   variant to a list headed by that symbol: `(circle 1.0)` for a tuple
+//!   variant and `(rect (w . 1) (h . 2))` for one with named fields.
+//!
+//! Names are written the Scheme way: `y_pos` becomes `y-pos` and `BigBox`
+//! becomes `big-box`.
+//!
+//! Fields convert with the field type's own `IntoLisp` and `FromLisp`, so an
+//! `Option` field that is `None` is stored as `#f`, and a field of type
+//! `Option<bool>` reads `Some(false)` back as `None`. A value of the wrong
+//! shape, including an association list missing a field, fails with a
+//! `TypeMismatch` naming the type.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Ident, LitStr};
+
+#[proc_macro_derive(IntoLisp)]
+pub fn derive_into_lisp(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, value) = into_fields(quote!(#name), &data.fields, None);
+            quote!(let #pattern = self; #value)
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let tag = scheme_name(&variant_name(ident));
+                let (pattern, value) =
+                    into_fields(quote!(#name::#ident), &variant.fields, Some(tag));
+                quote!(#pattern => #value)
+            });
+            quote!(match self { #(#arms,)* })
+        }
+        Data::Union(_) => return unsupported(name),
+    };
+    let generics = add_bound(&input.generics, quote!(::scheme::IntoLisp));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote!(
+        impl #impl_generics ::scheme::IntoLisp for #name #ty_generics #where_clause {
+            fn into_lisp(self) -> ::scheme::LispVal {
+                #body
+            }
+        }
+    )
+    .into()
+}
+
+#[proc_macro_derive(FromLisp)]
+pub fn derive_from_lisp(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let expected = LitStr::new(&name.to_string(), Span::call_site());
+    let mismatch = quote!(::scheme::LispError::TypeMismatch(#expected, value.clone()));
+    let body = match &input.data {
+        Data::Struct(data) => from_fields(quote!(#name), &data.fields, false, &mismatch),
+        Data::Enum(data) => {
+            let units = data
+                .variants
+                .iter()
+                .filter(|variant| matches!(variant.fields, Fields::Unit))
+                .map(|variant| {
+                    let ident = &variant.ident;
+                    let tag = scheme_name(&variant_name(ident));
+                    quote!(if *name == #tag { return Ok(#name::#ident); })
+                });
+            let tagged = data
+                .variants
+                .iter()
+                .filter(|variant| !matches!(variant.fields, Fields::Unit))
+                .map(|variant| {
+                    let ident = &variant.ident;
+                    let tag = scheme_name(&variant_name(ident));
+                    let build =
+                        from_fields(quote!(#name::#ident), &variant.fields, true, &mismatch);
+                    quote!(if tag == #tag { return #build; })
+                });
+            quote!(
+                match value {
+                    ::scheme::LispVal::Atom(name) => { #(#units)* }
+                    ::scheme::LispVal::Pair(pair) => {
+                        if let ::scheme::LispVal::Atom(tag) = pair.car() {
+                            let rest = pair.cdr();
+                            #(#tagged)*
+                        }
+                    }
+                    _ => (),
+                }
+                Err(#mismatch)
+            )
+        }
+        Data::Union(_) => return unsupported(name),
+    };
+    let generics = add_bound(&input.generics, quote!(::scheme::FromLisp));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote!(
+        impl #impl_generics ::scheme::FromLisp for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn from_lisp(value: &::scheme::LispVal) -> Result<Self, ::scheme::LispError> {
+                #body
+            }
+        }
+    )
+    .into()
+}
+
+/// A pattern binding every field of a struct or variant, and the expression
+/// building its Scheme value. `tag` names the variant of an enum.
+fn into_fields(
+    path: TokenStream,
+    fields: &Fields,
+    tag: Option<LitStr>,
+) -> (TokenStream, TokenStream) {
+    let tag = tag.map(|tag| quote!(::scheme::LispVal::symbol(#tag)));
+    match fields {
+        Fields::Named(fields) => {
+            let idents: Vec<_> = fields
+                .named
+                .iter()
+                .map(|f| f.ident.clone().unwrap())
+                .collect();
+            let keys = idents.iter().map(|ident| scheme_name(&ident.to_string()));
+            let entries = quote!(#(
+                ::scheme::LispVal::cons(
+                    ::scheme::LispVal::symbol(#keys),
+                    ::scheme::IntoLisp::into_lisp(#idents),
+                )
+            ),*);
+            let items = match tag {
+                Some(tag) => quote!(vec![#tag, #entries]),
+                None => quote!(vec![#entries]),
+            };
+            (
+                quote!(#path { #(#idents),* }),
+                quote!(::scheme::LispVal::list(#items)),
+            )
+        }
+        Fields::Unnamed(fields) => {
+            let idents: Vec<_> = (0..fields.unnamed.len())
+                .map(|i| format_ident!("field{}", i))
+                .collect();
+            let value = match (&tag, idents.as_slice()) {
+                (None, [only]) => quote!(::scheme::IntoLisp::into_lisp(#only)),
+                (Some(tag), _) => quote!(::scheme::LispVal::list(vec![
+                    #tag, #(::scheme::IntoLisp::into_lisp(#idents)),*
+                ])),
+                (None, _) => quote!(::scheme::LispVal::list(vec![
+                    #(::scheme::IntoLisp::into_lisp(#idents)),*
+                ])),
+            };
+            (quote!(#path(#(#idents),*)), value)
+        }
+        Fields::Unit => (
+            quote!(#path),
+            tag.unwrap_or_else(|| quote!(::scheme::LispVal::Nil)),
+        ),
+    }
+}
+
+/// An expression converting `value` into the struct or variant at `path`.
+/// A variant is built from `rest`, the list following its tag.
+fn from_fields(
+    path: TokenStream,
+    fields: &Fields,
+    variant: bool,
+    mismatch: &TokenStream,
+) -> TokenStream {
+    let input = if variant {
+        quote!(&rest)
+    } else {
+        quote!(value)
+    };
+    match fields {
+        Fields::Named(fields) => {
+            let idents = fields.named.iter().map(|f| f.ident.clone().unwrap());
+            let keys = fields
+                .named
+                .iter()
+                .map(|f| scheme_name(&f.ident.as_ref().unwrap().to_string()));
+            quote!(Ok(#path {
+                #(#idents: ::scheme::FromLisp::from_lisp(
+                    &::scheme::assoc_field(#input, #keys).ok_or_else(|| #mismatch)?
+                )?,)*
+            }))
+        }
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 && !variant => {
+            quote!(Ok(#path(::scheme::FromLisp::from_lisp(#input)?)))
+        }
+        Fields::Unnamed(fields) => {
+            let len = fields.unnamed.len();
+            let indices = 0..len;
+            quote!(match #input.list_to_vec() {
+                Some(items) if items.len() == #len => Ok(#path(
+                    #(::scheme::FromLisp::from_lisp(&items[#indices])?),*
+                )),
+                _ => Err(#mismatch),
+            })
+        }
+        Fields::Unit => quote!(match #input {
+            ::scheme::LispVal::Nil => Ok(#path),
+            _ => Err(#mismatch),
+        }),
+    }
+}
+
+fn add_bound(generics: &Generics, bound: TokenStream) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+/// `BigBox` as `big_box`, ready for `scheme_name`.
+fn variant_name(ident: &Ident) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+    name
+}
+
+fn scheme_name(name: &str) -> LitStr {
+    LitStr::new(&name.replace('_', "-"), Span::call_site())
+}
+
+fn unsupported(name: &Ident) -> proc_macro::TokenStream {
+    syn::Error::new_spanned(name, "unions cannot be converted to Scheme values")
+        .to_compile_error()
+        .into()
+}
@@ -0,0 +1,438 @@
+use crate::error::LispError;
+use crate::parser::LispVal;
+use crate::symbol::Symbol;
+use std::rc::Rc;
+
+/// A sequence of expressions, such as a procedure body or the operator and
+/// operands of a call. Shared so that the evaluator can keep its place in one
+/// without copying it.
+pub type Body = Rc<[Rc<Node>]>;
+
+/// Procedures implemented by the evaluator itself, because they need access
+/// to the continuation. The prelude wraps each in an ordinary procedure.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlOp {
+    CallCC,
+    DynamicWind,
+    Apply,
+    WithHandler,
+    Raise,
+    RaiseContinuable,
+}
+
+const CONTROL_OPS: &[(&str, ControlOp)] = &[
+    ("%call/cc", ControlOp::CallCC),
+    ("%dynamic-wind", ControlOp::DynamicWind),
+    ("%apply", ControlOp::Apply),
+    ("%with-exception-handler", ControlOp::WithHandler),
+    ("%raise", ControlOp::Raise),
+    ("%raise-continuable", ControlOp::RaiseContinuable),
+];
+
+pub struct Proto {
+    pub params: Vec<Symbol>,
+    pub vararg: Option<Symbol>,
+    pub body: Body,
+}
+
+/// One clause of a `case`; `data` is `None` for the `else` clause.
+pub struct CaseClause {
+    pub data: Option<Vec<LispVal>>,
+    pub body: Body,
+}
+
+/// An expression after its syntax has been checked, with derived forms such
+/// as `let` and `cond` rewritten in terms of simpler ones.
+pub enum Node {
+    Const(LispVal),
+    Var(Symbol),
+    If(Rc<Node>, Rc<Node>, Option<Rc<Node>>),
+    Define(Symbol, Rc<Node>),
+    Set(Symbol, Rc<Node>),
+    Lambda(Rc<Proto>),
+    // A procedure bound to `name` in a scope of its own, for named `let`.
+    NamedLambda(Symbol, Rc<Proto>),
+    Sequence(Body),
+    Apply(Body),
+    Control(ControlOp, Body),
+    And(Body),
+    Or(Body),
+    Case(Rc<Node>, Rc<[CaseClause]>),
+}
+
+fn bad_form(message: &'static str, expr: &LispVal) -> LispError {
+    LispError::BadSpecialForm(message, expr.clone())
+}
+
+fn is_keyword(expr: &LispVal, keyword: &str) -> bool {
+    matches!(expr, LispVal::Atom(name) if *name == keyword)
+}
+
+// Holds the value a `cond` or `case` clause passes on with `=>`. Neither the
+// reader nor the expander produces a name with a leading space, so the
+// clauses' own expressions cannot refer to it.
+fn temporary() -> Symbol {
+    Symbol::intern(" value")
+}
+
+/// `((lambda (name) body) value)`.
+fn bind_value(name: Symbol, value: Rc<Node>, body: Rc<Node>) -> Rc<Node> {
+    let proto = Rc::new(Proto {
+        params: vec![name],
+        vararg: None,
+        body: vec![body].into(),
+    });
+    Rc::new(Node::Apply(
+        vec![Rc::new(Node::Lambda(proto)), value].into(),
+    ))
+}
+
+/// Calls the receiver of a `(test => receiver)` clause with the value bound
+/// to `temporary()`.
+fn pass_value(receiver: &LispVal) -> Result<Rc<Node>, LispError> {
+    let value = Rc::new(Node::Var(temporary()));
+    Ok(Rc::new(Node::Apply(vec![analyze(receiver)?, value].into())))
+}
+
+/// Checks the syntax of `expr` and converts it to the tree the evaluator runs.
+pub fn analyze(expr: &LispVal) -> Result<Rc<Node>, LispError> {
+    match expr {
+        LispVal::Atom(name) => Ok(Rc::new(Node::Var(*name))),
+        LispVal::Pair(_) => match expr.list_to_vec() {
+            Some(items) => analyze_list(expr, &items),
+            None => Err(bad_form("Cannot evaluate dotted list", expr)),
+        },
+        LispVal::Nil => Err(bad_form("Empty application", expr)),
+        _ => Ok(Rc::new(Node::Const(expr.clone()))),
+    }
+}
+
+fn analyze_all(exprs: &[LispVal]) -> Result<Body, LispError> {
+    exprs.iter().map(analyze).collect()
+}
+
+fn analyze_list(expr: &LispVal, items: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    let (head, args) = items
+        .split_first()
+        .expect("a pair has at least one element");
+    if let LispVal::Atom(keyword) = head {
+        match keyword.as_str() {
+            "quote" => return analyze_quote(expr, args),
+            "if" => return analyze_if(expr, args),
+            "define" => return analyze_define(expr, args),
+            "set!" => return analyze_set(expr, args),
+            "lambda" => return analyze_lambda(expr, args),
+            "begin" => return Ok(Rc::new(Node::Sequence(analyze_all(args)?))),
+            "let" => return analyze_let(expr, args),
+            "cond" => return analyze_cond(expr, args),
+            "case" => return analyze_case(expr, args),
+            "and" => return Ok(Rc::new(Node::And(analyze_all(args)?))),
+            "or" => return Ok(Rc::new(Node::Or(analyze_all(args)?))),
+            "when" => return analyze_when(expr, args, true),
+            "unless" => return analyze_when(expr, args, false),
+            _ => {}
+        }
+        if let Some((_, op)) = CONTROL_OPS.iter().find(|(name, _)| keyword == name) {
+            return Ok(Rc::new(Node::Control(*op, analyze_all(args)?)));
+        }
+    }
+    Ok(Rc::new(Node::Apply(analyze_all(items)?)))
+}
+
+fn analyze_quote(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    match args {
+        [datum] => Ok(Rc::new(Node::Const(datum.clone()))),
+        _ => Err(bad_form("Malformed quote", expr)),
+    }
+}
+
+fn analyze_if(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    match args {
+        [pred, conseq] => Ok(Rc::new(Node::If(analyze(pred)?, analyze(conseq)?, None))),
+        [pred, conseq, alt] => Ok(Rc::new(Node::If(
+            analyze(pred)?,
+            analyze(conseq)?,
+            Some(analyze(alt)?),
+        ))),
+        _ => Err(bad_form("Malformed if", expr)),
+    }
+}
+
+fn analyze_define(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    let (name, value) = match args {
+        [LispVal::Atom(name), value] => (*name, analyze(value)?),
+        [signature @ LispVal::Pair(_), body @ ..] if !body.is_empty() => {
+            match split_formals(signature) {
+                Some((signature, vararg)) => match signature.split_first() {
+                    Some((LispVal::Atom(name), params)) => {
+                        (*name, make_lambda(expr, params, vararg.as_ref(), body)?)
+                    }
+                    _ => return Err(bad_form("Malformed define", expr)),
+                },
+                None => return Err(bad_form("Malformed define", expr)),
+            }
+        }
+        _ => return Err(bad_form("Malformed define", expr)),
+    };
+    Ok(Rc::new(Node::Define(name, value)))
+}
+
+fn analyze_set(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    match args {
+        [LispVal::Atom(name), value] => Ok(Rc::new(Node::Set(*name, analyze(value)?))),
+        _ => Err(bad_form("Malformed set!", expr)),
+    }
+}
+
+fn analyze_lambda(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    match args {
+        [formals, body @ ..] if !body.is_empty() => match split_formals(formals) {
+            Some((params, vararg)) => make_lambda(expr, &params, vararg.as_ref(), body),
+            None => Err(bad_form("Malformed lambda", expr)),
+        },
+        _ => Err(bad_form("Malformed lambda", expr)),
+    }
+}
+
+/// Splits a parameter list into the required parameters and the rest
+/// parameter, if any: `(a b . c)`, `(a b)` or just `c`.
+fn split_formals(formals: &LispVal) -> Option<(Vec<LispVal>, Option<LispVal>)> {
+    match formals {
+        LispVal::Atom(_) => Some((vec![], Some(formals.clone()))),
+        _ => match formals.split_tail()? {
+            (params, LispVal::Nil) => Some((params, None)),
+            (params, vararg) => Some((params, Some(vararg))),
+        },
+    }
+}
+
+fn make_proto(
+    expr: &LispVal,
+    params: &[LispVal],
+    vararg: Option<&LispVal>,
+    body: &[LispVal],
+) -> Result<Rc<Proto>, LispError> {
+    let params = params
+        .iter()
+        .map(|param| match param {
+            LispVal::Atom(name) => Ok(*name),
+            _ => Err(bad_form("Invalid parameter", expr)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let vararg = match vararg {
+        Some(LispVal::Atom(name)) => Some(*name),
+        Some(_) => return Err(bad_form("Invalid parameter", expr)),
+        None => None,
+    };
+    Ok(Rc::new(Proto {
+        params,
+        vararg,
+        body: analyze_all(body)?,
+    }))
+}
+
+fn make_lambda(
+    expr: &LispVal,
+    params: &[LispVal],
+    vararg: Option<&LispVal>,
+    body: &[LispVal],
+) -> Result<Rc<Node>, LispError> {
+    Ok(Rc::new(Node::Lambda(make_proto(
+        expr, params, vararg, body,
+    )?)))
+}
+
+// `(let ((v e) ...) body)` is `((lambda (v ...) body) e ...)`, and a named
+// `let` calls a procedure that can refer to itself by the given name.
+fn analyze_let(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    let (name, bindings, body) = match args {
+        [LispVal::Atom(name), bindings, body @ ..] if !body.is_empty() => {
+            (Some(name), bindings, body)
+        }
+        [bindings, body @ ..] if !body.is_empty() => (None, bindings, body),
+        _ => return Err(bad_form("Malformed let", expr)),
+    };
+    let bindings = bindings
+        .list_to_vec()
+        .ok_or_else(|| bad_form("Malformed let", expr))?;
+    let mut params = Vec::with_capacity(bindings.len());
+    let mut call = vec![];
+    for binding in &bindings {
+        match binding.list_to_vec().as_deref() {
+            Some([param, init]) => {
+                params.push(param.clone());
+                call.push(analyze(init)?);
+            }
+            _ => return Err(bad_form("Malformed let binding", binding)),
+        }
+    }
+    let proto = make_proto(expr, &params, None, body)?;
+    let func = match name {
+        Some(name) => Node::NamedLambda(*name, proto),
+        None => Node::Lambda(proto),
+    };
+    call.insert(0, Rc::new(func));
+    Ok(Rc::new(Node::Apply(call.into())))
+}
+
+// Each `cond` clause becomes an `if` whose alternative is the rest of the
+// clauses; a clause with no body yields the value of its test, like `or`,
+// and `(test => receiver)` calls receiver with it.
+fn analyze_cond(expr: &LispVal, clauses: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    let (clause, rest) = match clauses.split_first() {
+        Some(split) => split,
+        None => return Ok(Rc::new(Node::Const(LispVal::Unspecified))),
+    };
+    let (test, body) = match clause.list_to_vec() {
+        Some(items) if !items.is_empty() => (items[0].clone(), items[1..].to_vec()),
+        _ => return Err(bad_form("Malformed cond clause", clause)),
+    };
+    if is_keyword(&test, "else") {
+        if !rest.is_empty() || body.is_empty() {
+            return Err(bad_form("Malformed cond", expr));
+        }
+        return Ok(Rc::new(Node::Sequence(analyze_all(&body)?)));
+    }
+    let test = analyze(&test)?;
+    let otherwise = analyze_cond(expr, rest)?;
+    if let Some(first) = body.first().filter(|first| is_keyword(first, "=>")) {
+        let receiver = match &body[1..] {
+            [receiver] => pass_value(receiver)?,
+            _ => return Err(bad_form("Malformed cond clause", first)),
+        };
+        let value = Rc::new(Node::Var(temporary()));
+        let body = Rc::new(Node::If(value, receiver, Some(otherwise)));
+        return Ok(bind_value(temporary(), test, body));
+    }
+    if body.is_empty() {
+        return Ok(Rc::new(Node::Or(vec![test, otherwise].into())));
+    }
+    let body = Rc::new(Node::Sequence(analyze_all(&body)?));
+    Ok(Rc::new(Node::If(test, body, Some(otherwise))))
+}
+
+// A clause `(data => receiver)` calls receiver with the key, so a `case`
+// with any such clause keeps the key in a variable.
+fn analyze_case(expr: &LispVal, args: &[LispVal]) -> Result<Rc<Node>, LispError> {
+    let (key, clauses) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(bad_form("Malformed case", expr)),
+    };
+    let mut passes_key = false;
+    let clauses = clauses
+        .iter()
+        .enumerate()
+        .map(|(i, clause)| {
+            let items = match clause.list_to_vec() {
+                Some(items) if items.len() >= 2 => items,
+                _ => return Err(bad_form("Malformed case clause", clause)),
+            };
+            let data = match &items[0] {
+                data if is_keyword(data, "else") && i + 1 == clauses.len() => None,
+                data => match data.list_to_vec() {
+                    Some(data) => Some(data),
+                    None => return Err(bad_form("Malformed case clause", clause)),
+                },
+            };
+            let body = match &items[1..] {
+                [arrow, receiver] if is_keyword(arrow, "=>") => {
+                    passes_key = true;
+                    vec![pass_value(receiver)?].into()
+                }
+                body if body.iter().any(|item| is_keyword(item, "=>")) => {
+                    return Err(bad_form("Malformed case clause", clause))
+                }
+                body => analyze_all(body)?,
+            };
+            Ok(CaseClause { data, body })
+        })
+        .collect::<Result<Rc<[_]>, _>>()?;
+    if passes_key {
+        let key_var = Rc::new(Node::Var(temporary()));
+        let case = Rc::new(Node::Case(key_var, clauses));
+        return Ok(bind_value(temporary(), analyze(key)?, case));
+    }
+    Ok(Rc::new(Node::Case(analyze(key)?, clauses)))
+}
+
+/// `when` runs its body if the test is true, `unless` if it is false.
+fn analyze_when(expr: &LispVal, args: &[LispVal], expect: bool) -> Result<Rc<Node>, LispError> {
+    let (test, body) = match args.split_first() {
+        Some((test, body)) if !body.is_empty() => (analyze(test)?, analyze_all(body)?),
+        _ if expect => return Err(bad_form("Malformed when", expr)),
+        _ => return Err(bad_form("Malformed unless", expr)),
+    };
+    let body = Rc::new(Node::Sequence(body));
+    let nothing = Rc::new(Node::Const(LispVal::Unspecified));
+    if expect {
+        Ok(Rc::new(Node::If(test, body, None)))
+    } else {
+        Ok(Rc::new(Node::If(test, nothing, Some(body))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::analyze::*;
+    use crate::parser::parse_lisp_expr;
+
+    fn analyze_str(input: &str) -> Result<Rc<Node>, LispError> {
+        let (_, expr) = parse_lisp_expr(input).unwrap();
+        analyze(&expr)
+    }
+
+    #[test]
+    fn syntax_error_test() {
+        // Malformed forms are reported before anything is evaluated, even
+        // inside bodies that have not run yet.
+        for input in &[
+            "(if)",
+            "(lambda (x 1) x)",
+            "(lambda (x) (quote))",
+            "(define (f) (let ((x)) x))",
+            "(cond (else 1) (#t 2))",
+            "(case 1 (2 'two))",
+            "(case 1 ((1) => f g))",
+            "(cond (1 =>))",
+            "(unless #t)",
+            "(f . x)",
+            "()",
+        ] {
+            assert!(
+                matches!(analyze_str(input), Err(LispError::BadSpecialForm(..))),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn derived_form_test() {
+        assert!(matches!(
+            &*analyze_str("(let ((x 1)) x)").unwrap(),
+            Node::Apply(call) if matches!(&*call[0], Node::Lambda(_))
+        ));
+        assert!(matches!(
+            &*analyze_str("(let loop () (loop))").unwrap(),
+            Node::Apply(call) if matches!(&*call[0], Node::NamedLambda(name, _) if name == "loop")
+        ));
+        assert!(matches!(
+            &*analyze_str("(cond (a) (else b))").unwrap(),
+            Node::Or(exprs) if exprs.len() == 2
+        ));
+        // A `=>` clause binds the value it passes on.
+        assert!(matches!(
+            &*analyze_str("(cond (a => f))").unwrap(),
+            Node::Apply(call) if matches!(&*call[0], Node::Lambda(proto) if proto.params.len() == 1)
+        ));
+        assert!(matches!(
+            &*analyze_str("(case a ((1) 'one) (else => f))").unwrap(),
+            Node::Apply(call) if matches!(&*call[0], Node::Lambda(_))
+        ));
+        assert!(matches!(
+            &*analyze_str("(%call/cc f)").unwrap(),
+            Node::Control(ControlOp::CallCC, _)
+        ));
+    }
+}
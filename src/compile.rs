@@ -0,0 +1,432 @@
+use crate::analyze::{Body, ControlOp, Node, Proto};
+use crate::parser::LispVal;
+use crate::symbol::Symbol;
+use std::rc::Rc;
+
+/// One instruction for the VM. Operands are evaluated onto a stack, and
+/// jumps are offsets into the instructions of the same template.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    /// Pushes a constant from the template's table.
+    Const(u32),
+    /// Pushes a local variable, found `depth` frames out from the current one.
+    Local(u32, u32),
+    /// Pops a value into a local variable and pushes the unspecified value.
+    SetLocal(u32, u32),
+    Global(Symbol),
+    SetGlobal(Symbol),
+    DefineGlobal(Symbol),
+    /// Pushes a procedure made from a template and the current frame.
+    Closure(u32),
+    /// Like `Closure`, but inside a frame of its own whose only variable is
+    /// the procedure, for named `let`.
+    NamedClosure(u32),
+    Pop,
+    Jump(u32),
+    /// Pops a value and jumps if it is false.
+    JumpIfFalse(u32),
+    /// Jumps if the value on top is false, leaving it there; otherwise pops
+    /// it. `and` uses it to stop at the first false value.
+    JumpIfFalseOrPop(u32),
+    /// Jumps if the value on top is true, leaving it there; otherwise pops
+    /// it. `or` uses it to stop at the first true value.
+    JumpIfTrueOrPop(u32),
+    /// Pops the key of a `case` if it is in the data of the given table, and
+    /// otherwise leaves it and jumps to the next clause.
+    Case(u32, u32),
+    /// Calls the procedure below the given number of arguments.
+    Call(u32),
+    /// Calls in tail position, replacing the current frame.
+    TailCall(u32),
+    Control(ControlOp, u32),
+    TailControl(ControlOp, u32),
+    Return,
+}
+
+/// The code for a procedure body or top-level form.
+pub struct Template {
+    /// The number of required parameters.
+    pub params: usize,
+    /// Whether the procedure takes a rest list after its required parameters.
+    pub vararg: bool,
+    /// Slots in the procedure's frame: its parameters, then its internal
+    /// definitions.
+    pub frame_size: usize,
+    pub ops: Vec<Op>,
+    pub consts: Vec<LispVal>,
+    pub templates: Vec<Rc<Template>>,
+    pub cases: Vec<Vec<LispVal>>,
+}
+
+/// Compiles a top-level form. Its definitions are global, and any variable
+/// that is not bound by an enclosing `lambda` is looked up globally.
+pub fn compile(node: &Node) -> Rc<Template> {
+    let mut compiler = Compiler::new(0, false, 0);
+    compiler.expr(node, true, &mut vec![]);
+    Rc::new(compiler.finish())
+}
+
+struct Compiler {
+    template: Template,
+}
+
+/// The variables of each enclosing frame, innermost last.
+type Scopes = Vec<Vec<Symbol>>;
+
+impl Compiler {
+    fn new(params: usize, vararg: bool, frame_size: usize) -> Compiler {
+        Compiler {
+            template: Template {
+                params,
+                vararg,
+                frame_size,
+                ops: vec![],
+                consts: vec![],
+                templates: vec![],
+                cases: vec![],
+            },
+        }
+    }
+
+    fn finish(self) -> Template {
+        self.template
+    }
+
+    fn emit(&mut self, op: Op) {
+        self.template.ops.push(op);
+    }
+
+    fn here(&self) -> u32 {
+        self.template.ops.len() as u32
+    }
+
+    /// Emits a jump to be pointed at its target later with `patch`.
+    fn emit_jump(&mut self, op: fn(u32) -> Op) -> usize {
+        self.emit(op(0));
+        self.template.ops.len() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+        self.template.ops[at] = match self.template.ops[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::JumpIfFalseOrPop(_) => Op::JumpIfFalseOrPop(target),
+            Op::JumpIfTrueOrPop(_) => Op::JumpIfTrueOrPop(target),
+            Op::Case(data, _) => Op::Case(data, target),
+            op => unreachable!("{:?} is not a jump", op),
+        };
+    }
+
+    fn constant(&mut self, value: LispVal) {
+        self.template.consts.push(value);
+        let index = self.template.consts.len() as u32 - 1;
+        self.emit(Op::Const(index));
+    }
+
+    fn ret(&mut self, tail: bool) {
+        if tail {
+            self.emit(Op::Return);
+        }
+    }
+
+    fn lookup(scopes: &Scopes, name: Symbol) -> Option<(u32, u32)> {
+        scopes.iter().rev().enumerate().find_map(|(depth, vars)| {
+            vars.iter()
+                .rposition(|var| *var == name)
+                .map(|index| (depth as u32, index as u32))
+        })
+    }
+
+    /// Compiles `node`, returning from the template afterwards if it is in
+    /// tail position.
+    fn expr(&mut self, node: &Node, tail: bool, scopes: &mut Scopes) {
+        match node {
+            Node::Const(value) => {
+                self.constant(value.clone());
+                self.ret(tail);
+            }
+            Node::Var(name) => {
+                match Compiler::lookup(scopes, *name) {
+                    Some((depth, index)) => self.emit(Op::Local(depth, index)),
+                    None => self.emit(Op::Global(*name)),
+                }
+                self.ret(tail);
+            }
+            Node::If(pred, conseq, alt) => {
+                self.expr(pred, false, scopes);
+                let to_alt = self.emit_jump(Op::JumpIfFalse);
+                self.expr(conseq, tail, scopes);
+                let to_end = if tail {
+                    None
+                } else {
+                    Some(self.emit_jump(Op::Jump))
+                };
+                self.patch(to_alt);
+                match alt {
+                    Some(alt) => self.expr(alt, tail, scopes),
+                    None => {
+                        self.constant(LispVal::Unspecified);
+                        self.ret(tail);
+                    }
+                }
+                if let Some(to_end) = to_end {
+                    self.patch(to_end);
+                }
+            }
+            // Definitions inside a procedure were given slots in its frame
+            // when it was compiled.
+            Node::Define(name, value) => {
+                self.expr(value, false, scopes);
+                match scopes.last() {
+                    Some(_) => {
+                        let (depth, index) =
+                            Compiler::lookup(scopes, *name).expect("definitions have slots");
+                        self.emit(Op::SetLocal(depth, index));
+                    }
+                    None => self.emit(Op::DefineGlobal(*name)),
+                }
+                self.ret(tail);
+            }
+            Node::Set(name, value) => {
+                self.expr(value, false, scopes);
+                match Compiler::lookup(scopes, *name) {
+                    Some((depth, index)) => self.emit(Op::SetLocal(depth, index)),
+                    None => self.emit(Op::SetGlobal(*name)),
+                }
+                self.ret(tail);
+            }
+            Node::Lambda(proto) => {
+                let index = self.template(proto, scopes);
+                self.emit(Op::Closure(index));
+                self.ret(tail);
+            }
+            Node::NamedLambda(name, proto) => {
+                scopes.push(vec![*name]);
+                let index = self.template(proto, scopes);
+                scopes.pop();
+                self.emit(Op::NamedClosure(index));
+                self.ret(tail);
+            }
+            Node::Sequence(body) => self.sequence(body, tail, scopes),
+            Node::Apply(exprs) => {
+                for expr in exprs.iter() {
+                    self.expr(expr, false, scopes);
+                }
+                match exprs.len() {
+                    0 => {
+                        self.constant(LispVal::Unspecified);
+                        self.ret(tail);
+                    }
+                    n if tail => self.emit(Op::TailCall(n as u32 - 1)),
+                    n => self.emit(Op::Call(n as u32 - 1)),
+                }
+            }
+            Node::Control(op, args) => {
+                for arg in args.iter() {
+                    self.expr(arg, false, scopes);
+                }
+                let argc = args.len() as u32;
+                if tail {
+                    self.emit(Op::TailControl(*op, argc));
+                } else {
+                    self.emit(Op::Control(*op, argc));
+                }
+            }
+            Node::And(exprs) => self.and_or(exprs, true, tail, scopes),
+            Node::Or(exprs) => self.and_or(exprs, false, tail, scopes),
+            Node::Case(key, clauses) => {
+                self.expr(key, false, scopes);
+                let mut to_end = vec![];
+                for clause in clauses.iter() {
+                    let to_next = match &clause.data {
+                        Some(data) => {
+                            self.template.cases.push(data.clone());
+                            let data = self.template.cases.len() as u32 - 1;
+                            self.emit(Op::Case(data, 0));
+                            Some(self.template.ops.len() - 1)
+                        }
+                        None => {
+                            self.emit(Op::Pop);
+                            None
+                        }
+                    };
+                    self.sequence(&clause.body, tail, scopes);
+                    if !tail {
+                        to_end.push(self.emit_jump(Op::Jump));
+                    }
+                    match to_next {
+                        Some(to_next) => self.patch(to_next),
+                        None => break,
+                    }
+                }
+                // No clause matched, or the last one was `else`.
+                if clauses.last().is_none_or(|clause| clause.data.is_some()) {
+                    self.emit(Op::Pop);
+                    self.constant(LispVal::Unspecified);
+                    self.ret(tail);
+                }
+                for at in to_end {
+                    self.patch(at);
+                }
+            }
+        }
+    }
+
+    fn sequence(&mut self, body: &Body, tail: bool, scopes: &mut Scopes) {
+        match body.split_last() {
+            Some((last, init)) => {
+                for expr in init {
+                    self.expr(expr, false, scopes);
+                    self.emit(Op::Pop);
+                }
+                self.expr(last, tail, scopes);
+            }
+            None => {
+                self.constant(LispVal::Unspecified);
+                self.ret(tail);
+            }
+        }
+    }
+
+    fn and_or(&mut self, exprs: &Body, is_and: bool, tail: bool, scopes: &mut Scopes) {
+        let (last, init) = match exprs.split_last() {
+            Some(split) => split,
+            None => {
+                self.constant(LispVal::Boolean(is_and));
+                self.ret(tail);
+                return;
+            }
+        };
+        let mut to_end = vec![];
+        for expr in init {
+            self.expr(expr, false, scopes);
+            to_end.push(self.emit_jump(if is_and {
+                Op::JumpIfFalseOrPop
+            } else {
+                Op::JumpIfTrueOrPop
+            }));
+        }
+        self.expr(last, false, scopes);
+        for at in to_end {
+            self.patch(at);
+        }
+        self.ret(tail);
+    }
+
+    /// Compiles a procedure body into a template of its own, returning its
+    /// index in this template's table.
+    fn template(&mut self, proto: &Proto, scopes: &mut Scopes) -> u32 {
+        let mut vars = proto.params.clone();
+        vars.extend(proto.vararg);
+        let params = proto.params.len();
+        let vararg = proto.vararg.is_some();
+        for expr in proto.body.iter() {
+            definitions(expr, &mut vars);
+        }
+        let mut compiler = Compiler::new(params, vararg, vars.len());
+        scopes.push(vars);
+        compiler.sequence(&proto.body, true, scopes);
+        scopes.pop();
+        self.template.templates.push(Rc::new(compiler.finish()));
+        self.template.templates.len() as u32 - 1
+    }
+}
+
+/// Adds the names defined by `node` to `vars`, without looking inside the
+/// procedures it creates, which have frames of their own.
+fn definitions(node: &Node, vars: &mut Vec<Symbol>) {
+    let all = |exprs: &[Rc<Node>], vars: &mut Vec<Symbol>| {
+        for expr in exprs {
+            definitions(expr, vars);
+        }
+    };
+    match node {
+        Node::Define(name, value) => {
+            if !vars.contains(name) {
+                vars.push(*name);
+            }
+            definitions(value, vars);
+        }
+        Node::Set(_, value) => definitions(value, vars),
+        Node::If(pred, conseq, alt) => {
+            definitions(pred, vars);
+            definitions(conseq, vars);
+            if let Some(alt) = alt {
+                definitions(alt, vars);
+            }
+        }
+        Node::Sequence(exprs)
+        | Node::Apply(exprs)
+        | Node::Control(_, exprs)
+        | Node::And(exprs)
+        | Node::Or(exprs) => all(exprs, vars),
+        Node::Case(key, clauses) => {
+            definitions(key, vars);
+            for clause in clauses.iter() {
+                all(&clause.body, vars);
+            }
+        }
+        Node::Const(_) | Node::Var(_) | Node::Lambda(_) | Node::NamedLambda(..) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::analyze::analyze;
+    use crate::compile::*;
+    use crate::parser::parse_lisp_expr;
+
+    fn compile_str(input: &str) -> Rc<Template> {
+        let (_, expr) = parse_lisp_expr(input).unwrap();
+        compile(&analyze(&expr).unwrap())
+    }
+
+    #[test]
+    fn lexical_address_test() {
+        let top = compile_str("(lambda (a b) (lambda (c) (define d c) (a d)))");
+        let inner = &top.templates[0].templates[0];
+        assert_eq!((inner.params, inner.frame_size), (1, 2));
+        assert_eq!(
+            inner.ops,
+            vec![
+                Op::Local(0, 0),
+                Op::SetLocal(0, 1),
+                Op::Pop,
+                Op::Local(1, 0),
+                Op::Local(0, 1),
+                Op::TailCall(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn tail_position_test() {
+        let top = compile_str("(define (f x) (if x (g x) (begin (h) 1)))");
+        assert_eq!(
+            top.ops,
+            vec![
+                Op::Closure(0),
+                Op::DefineGlobal(Symbol::intern("f")),
+                Op::Return
+            ]
+        );
+        let f = &top.templates[0];
+        assert_eq!(
+            f.ops,
+            vec![
+                Op::Local(0, 0),
+                Op::JumpIfFalse(5),
+                Op::Global(Symbol::intern("g")),
+                Op::Local(0, 0),
+                Op::TailCall(1),
+                Op::Global(Symbol::intern("h")),
+                Op::Call(0),
+                Op::Pop,
+                Op::Const(0),
+                Op::Return,
+            ]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::LispVal;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Conversion from a Rust value into a Scheme value.
+///
+/// Sequences become lists, `None` becomes `#f`, tuples become lists of their
+/// fields and maps become association lists. `#[derive(IntoLisp)]` maps a
+/// struct with named fields to an association list keyed by the field names.
+///
+/// `Some(false)` and `None` both become `#f`, so an `Option<bool>` does not
+/// survive a round trip through `FromLisp`.
+pub trait IntoLisp {
+    fn into_lisp(self) -> LispVal;
+}
+
+/// Conversion from a Scheme value into a Rust value, failing with a
+/// `TypeMismatch` when the value has the wrong shape.
+///
+/// `#f` converts to `None` for any `Option<T>`, even where `T` accepts `#f`
+/// itself: an `Option<bool>` is never `Some(false)`.
+pub trait FromLisp: Sized {
+    fn from_lisp(value: &LispVal) -> Result<Self, LispError>;
+}
+
+impl IntoLisp for LispVal {
+    fn into_lisp(self) -> LispVal {
+        self
+    }
+}
+
+impl FromLisp for LispVal {
+    fn from_lisp(value: &LispVal) -> Result<LispVal, LispError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoLisp for () {
+    fn into_lisp(self) -> LispVal {
+        LispVal::Unspecified
+    }
+}
+
+impl IntoLisp for Number {
+    fn into_lisp(self) -> LispVal {
+        LispVal::Number(self)
+    }
+}
+
+impl FromLisp for Number {
+    fn from_lisp(value: &LispVal) -> Result<Number, LispError> {
+        match value {
+            LispVal::Number(n) => Ok(n.clone()),
+            _ => Err(LispError::TypeMismatch("number", value.clone())),
+        }
+    }
+}
+
+// Integers convert from any exact integer that fits; `Number` keeps integers
+// in the smallest representation, so a fixnum or bignum check covers them.
+macro_rules! impl_integer {
+    ($($t:ty => $to:ident),*) => {$(
+        impl IntoLisp for $t {
+            fn into_lisp(self) -> LispVal {
+                LispVal::Number(Number::from(BigInt::from(self)))
+            }
+        }
+
+        impl FromLisp for $t {
+            fn from_lisp(value: &LispVal) -> Result<$t, LispError> {
+                let n = match value {
+                    LispVal::Number(Number::Integer(n)) => n.$to(),
+                    LispVal::Number(Number::BigInteger(n)) => n.$to(),
+                    _ => None,
+                };
+                n.ok_or_else(|| LispError::TypeMismatch(stringify!($t), value.clone()))
+            }
+        }
+    )*};
+}
+
+impl_integer!(
+    i8 => to_i8, i16 => to_i16, i32 => to_i32, i64 => to_i64, i128 => to_i128,
+    isize => to_isize, u8 => to_u8, u16 => to_u16, u32 => to_u32, u64 => to_u64,
+    u128 => to_u128, usize => to_usize
+);
+
+macro_rules! impl_float {
+    ($($t:ty),*) => {$(
+        impl IntoLisp for $t {
+            fn into_lisp(self) -> LispVal {
+                LispVal::Number(Number::Real(self.into()))
+            }
+        }
+
+        impl FromLisp for $t {
+            fn from_lisp(value: &LispVal) -> Result<$t, LispError> {
+                match value {
+                    LispVal::Number(n) => match n.to_f64() {
+                        Some(x) => Ok(x as $t),
+                        None => Err(LispError::TypeMismatch("real", value.clone())),
+                    },
+                    _ => Err(LispError::TypeMismatch("real", value.clone())),
+                }
+            }
+        }
+    )*};
+}
+
+impl_float!(f32, f64);
+
+impl IntoLisp for bool {
+    fn into_lisp(self) -> LispVal {
+        LispVal::Boolean(self)
+    }
+}
+
+impl FromLisp for bool {
+    fn from_lisp(value: &LispVal) -> Result<bool, LispError> {
+        match value {
+            LispVal::Boolean(b) => Ok(*b),
+            _ => Err(LispError::TypeMismatch("boolean", value.clone())),
+        }
+    }
+}
+
+impl IntoLisp for char {
+    fn into_lisp(self) -> LispVal {
+        LispVal::Char(self)
+    }
+}
+
+impl FromLisp for char {
+    fn from_lisp(value: &LispVal) -> Result<char, LispError> {
+        match value {
+            LispVal::Char(c) => Ok(*c),
+            _ => Err(LispError::TypeMismatch("char", value.clone())),
+        }
+    }
+}
+
+impl IntoLisp for String {
+    fn into_lisp(self) -> LispVal {
+        LispVal::string(self)
+    }
+}
+
+impl IntoLisp for &str {
+    fn into_lisp(self) -> LispVal {
+        LispVal::string(self.to_owned())
+    }
+}
+
+impl FromLisp for String {
+    fn from_lisp(value: &LispVal) -> Result<String, LispError> {
+        match value {
+            LispVal::String(s) => Ok(s.borrow().iter().collect()),
+            _ => Err(LispError::TypeMismatch("string", value.clone())),
+        }
+    }
+}
+
+impl<T: IntoLisp> IntoLisp for Vec<T> {
+    fn into_lisp(self) -> LispVal {
+        self.into_iter().map(IntoLisp::into_lisp).collect()
+    }
+}
+
+// Vectors convert as well as lists, so either can be passed to a procedure
+// expecting a `Vec`.
+impl<T: FromLisp> FromLisp for Vec<T> {
+    fn from_lisp(value: &LispVal) -> Result<Vec<T>, LispError> {
+        let items = match value {
+            LispVal::Vector(items) => items.borrow().clone(),
+            _ => value
+                .list_to_vec()
+                .ok_or_else(|| LispError::TypeMismatch("list", value.clone()))?,
+        };
+        items.iter().map(T::from_lisp).collect()
+    }
+}
+
+impl<T: IntoLisp> IntoLisp for Option<T> {
+    fn into_lisp(self) -> LispVal {
+        match self {
+            Some(value) => value.into_lisp(),
+            None => LispVal::Boolean(false),
+        }
+    }
+}
+
+impl<T: FromLisp> FromLisp for Option<T> {
+    fn from_lisp(value: &LispVal) -> Result<Option<T>, LispError> {
+        match value {
+            LispVal::Boolean(false) => Ok(None),
+            _ => T::from_lisp(value).map(Some),
+        }
+    }
+}
+
+macro_rules! impl_tuple {
+    ($len:expr; $($t:ident),*) => {
+        impl<$($t: IntoLisp),*> IntoLisp for ($($t,)*) {
+            #[allow(non_snake_case)]
+            fn into_lisp(self) -> LispVal {
+                let ($($t,)*) = self;
+                LispVal::list(vec![$($t.into_lisp()),*])
+            }
+        }
+
+        impl<$($t: FromLisp),*> FromLisp for ($($t,)*) {
+            fn from_lisp(value: &LispVal) -> Result<($($t,)*), LispError> {
+                match value.list_to_vec() {
+                    Some(items) if items.len() == $len => {
+                        let mut items = items.iter();
+                        Ok(($($t::from_lisp(items.next().expect("length was checked"))?,)*))
+                    }
+                    _ => Err(LispError::TypeMismatch(
+                        concat!("list of length ", $len),
+                        value.clone(),
+                    )),
+                }
+            }
+        }
+    };
+}
+
+impl_tuple!(1; A);
+impl_tuple!(2; A, B);
+impl_tuple!(3; A, B, C);
+impl_tuple!(4; A, B, C, D);
+impl_tuple!(5; A, B, C, D, E);
+impl_tuple!(6; A, B, C, D, E, F);
+
+impl<K: IntoLisp, V: IntoLisp> IntoLisp for HashMap<K, V> {
+    fn into_lisp(self) -> LispVal {
+        self.into_iter()
+            .map(|(k, v)| LispVal::cons(k.into_lisp(), v.into_lisp()))
+            .collect()
+    }
+}
+
+// Earlier entries of an association list shadow later ones, as with `assq`.
+impl<K: FromLisp + Eq + Hash, V: FromLisp> FromLisp for HashMap<K, V> {
+    fn from_lisp(value: &LispVal) -> Result<HashMap<K, V>, LispError> {
+        let entries = value
+            .list_to_vec()
+            .ok_or_else(|| LispError::TypeMismatch("association list", value.clone()))?;
+        let mut map = HashMap::new();
+        for entry in entries.iter().rev() {
+            match entry {
+                LispVal::Pair(pair) => {
+                    map.insert(K::from_lisp(&pair.car())?, V::from_lisp(&pair.cdr())?);
+                }
+                _ => return Err(LispError::TypeMismatch("pair", entry.clone())),
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Finds the value of `field` in an association list keyed by symbols. Used
+/// by the code `#[derive(FromLisp)]` generates, which fails with a
+/// `TypeMismatch` when the field is missing.
+#[doc(hidden)]
+pub fn assoc_field(alist: &LispVal, field: &str) -> Option<LispVal> {
+    for entry in alist.iter() {
+        if let LispVal::Pair(pair) = entry {
+            if matches!(pair.car(), LispVal::Atom(name) if name == field) {
+                return Some(pair.cdr());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::convert::*;
+    use crate::parser::parse_lisp_expr;
+    use crate::{FromLisp, IntoLisp};
+
+    fn read(source: &str) -> LispVal {
+        parse_lisp_expr(source).unwrap().1
+    }
+
+    fn round_trip<T: IntoLisp + FromLisp + Clone + PartialEq + std::fmt::Debug>(value: T) {
+        assert_eq!(T::from_lisp(&value.clone().into_lisp()).unwrap(), value);
+    }
+
+    #[test]
+    fn scalar_test() {
+        round_trip(-5i8);
+        round_trip(u64::MAX);
+        round_trip(i128::MIN);
+        round_trip(2.5f64);
+        round_trip(true);
+        round_trip('λ');
+        round_trip("text".to_owned());
+        assert_eq!(u64::MAX.into_lisp(), read("18446744073709551615"));
+        assert_eq!(f64::from_lisp(&read("1/4")).unwrap(), 0.25);
+        assert_eq!(
+            u8::from_lisp(&read("256")).unwrap_err(),
+            LispError::TypeMismatch("u8", read("256"))
+        );
+        assert!(i64::from_lisp(&read("1.5")).is_err());
+        assert!(String::from_lisp(&read("sym")).is_err());
+        assert!(!bool::from_lisp(&read("#f")).unwrap());
+        assert_eq!(
+            bool::from_lisp(&read("0")).unwrap_err(),
+            LispError::TypeMismatch("boolean", read("0"))
+        );
+        assert_eq!("hi".into_lisp(), read("\"hi\""));
+    }
+
+    #[test]
+    fn compound_test() {
+        round_trip(vec![1, 2, 3]);
+        round_trip(Some(vec![Some('a'), None]));
+        // `Some(false)` is `#f`, which reads back as `None`.
+        assert_eq!(Some(false).into_lisp(), LispVal::Boolean(false));
+        assert_eq!(Option::<bool>::from_lisp(&read("#f")).unwrap(), None);
+        round_trip((1, "two".to_owned(), 3.0));
+        assert_eq!(Vec::<i32>::from_lisp(&read("#(1 2)")).unwrap(), vec![1, 2]);
+        assert!(Vec::<i32>::from_lisp(&read("(1 . 2)")).is_err());
+        assert!(<(i32, i32)>::from_lisp(&read("(1 2 3)")).is_err());
+
+        let mut map = HashMap::new();
+        map.insert("a".to_owned(), 1);
+        round_trip(map.clone());
+        assert_eq!(
+            HashMap::<String, i32>::from_lisp(&read("((\"a\" . 1) (\"a\" . 2))")).unwrap(),
+            map
+        );
+    }
+
+    #[derive(Clone, Debug, PartialEq, IntoLisp, FromLisp)]
+    struct Point {
+        x: i64,
+        y_pos: Option<f64>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, IntoLisp, FromLisp)]
+    struct Meters(f64);
+
+    #[derive(Clone, Debug, PartialEq, IntoLisp, FromLisp)]
+    enum Shape {
+        Empty,
+        Circle(Point, Meters),
+        Rect { width: u32, height: u32 },
+    }
+
+    #[test]
+    fn derive_test() {
+        let point = Point {
+            x: 1,
+            y_pos: Some(2.0),
+        };
+        assert_eq!(point.clone().into_lisp(), read("((x . 1) (y-pos . 2.0))"));
+        assert_eq!(
+            Point::from_lisp(&read("((y-pos . #f) (x . 3))")).unwrap().x,
+            3
+        );
+        assert_eq!(
+            Point::from_lisp(&read("((y-pos . 1.0))")).unwrap_err(),
+            LispError::TypeMismatch("Point", read("((y-pos . 1.0))"))
+        );
+        assert_eq!(Meters(2.5).into_lisp(), read("2.5"));
+
+        assert_eq!(Shape::Empty.into_lisp(), read("empty"));
+        assert_eq!(
+            Shape::Circle(point.clone(), Meters(1.0)).into_lisp(),
+            read("(circle ((x . 1) (y-pos . 2.0)) 1.0)")
+        );
+        round_trip(Shape::Empty);
+        round_trip(Shape::Circle(point, Meters(1.0)));
+        round_trip(Shape::Rect {
+            width: 2,
+            height: 3,
+        });
+        assert!(Shape::from_lisp(&read("(triangle 1 2 3)")).is_err());
+    }
+}
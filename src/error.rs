@@ -0,0 +1,165 @@
+use crate::gc::{self, Trace, Tracer};
+use crate::parser::{LispVal, ParseError};
+use crate::symbol::Symbol;
+use std::fmt;
+
+/// Everything that can go wrong while reading or running a program.
+///
+/// Inside a `with-exception-handler` or `guard`, errors signalled by the
+/// evaluator and primitives are raised as Scheme error objects wrapping the
+/// `LispError`, so programs can inspect them with `error-object-message` and
+/// `error-object-irritants`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LispError {
+    NumArgs(usize, Vec<LispVal>),
+    TypeMismatch(&'static str, LispVal),
+    BadSpecialForm(&'static str, LispVal),
+    NotFunction(LispVal),
+    UnboundVar(Symbol),
+    Parser(ParseError),
+    /// A call to `error`, with its message and irritants.
+    User(String, Vec<LispVal>),
+    /// An object passed to `raise` that no handler dealt with.
+    Raised(LispVal),
+    Default(String),
+}
+
+impl LispError {
+    /// The message returned by `error-object-message`.
+    pub fn message(&self) -> String {
+        match self {
+            LispError::NumArgs(expected, _) => {
+                format!("Expected {} args; found values", expected)
+            }
+            LispError::TypeMismatch(expected, _) => format!("Invalid type: expected {}", expected),
+            LispError::BadSpecialForm(message, _) => (*message).to_owned(),
+            LispError::NotFunction(_) => "Not a function".to_owned(),
+            LispError::UnboundVar(_) => "Unbound variable".to_owned(),
+            LispError::Parser(err) => err.to_string(),
+            LispError::User(message, _) => message.clone(),
+            LispError::Raised(_) => "Uncaught exception".to_owned(),
+            LispError::Default(message) => message.clone(),
+        }
+    }
+
+    /// The values returned by `error-object-irritants`.
+    pub fn irritants(&self) -> Vec<LispVal> {
+        match self {
+            LispError::NumArgs(_, found) => found.clone(),
+            LispError::TypeMismatch(_, found) => vec![found.clone()],
+            LispError::BadSpecialForm(_, form) => vec![form.clone()],
+            LispError::NotFunction(value) => vec![value.clone()],
+            LispError::UnboundVar(name) => vec![LispVal::Atom(*name)],
+            LispError::User(_, irritants) => irritants.clone(),
+            LispError::Raised(value) => vec![value.clone()],
+            LispError::Parser(_) | LispError::Default(_) => vec![],
+        }
+    }
+}
+
+impl LispVal {
+    /// Wraps `err` as a Scheme error object.
+    pub fn error(err: LispError) -> LispVal {
+        LispVal::Error(gc::alloc(err))
+    }
+}
+
+// An error object never changes, but its irritants can be changed to refer
+// back to it.
+impl Trace for LispError {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            LispError::NumArgs(_, values) | LispError::User(_, values) => {
+                for value in values {
+                    tracer.value(value);
+                }
+            }
+            LispError::TypeMismatch(_, value)
+            | LispError::BadSpecialForm(_, value)
+            | LispError::NotFunction(value)
+            | LispError::Raised(value) => tracer.value(value),
+            LispError::UnboundVar(_) | LispError::Parser(_) | LispError::Default(_) => {}
+        }
+    }
+}
+
+impl From<ParseError> for LispError {
+    fn from(err: ParseError) -> LispError {
+        LispError::Parser(err)
+    }
+}
+
+// Errors from host code registered with `Interpreter::register_fn` can be
+// plain messages.
+impl From<String> for LispError {
+    fn from(message: String) -> LispError {
+        LispError::User(message, vec![])
+    }
+}
+
+impl From<&str> for LispError {
+    fn from(message: &str) -> LispError {
+        LispError::User(message.to_owned(), vec![])
+    }
+}
+
+impl fmt::Display for LispError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LispError::NumArgs(expected, found) => {
+                let found = LispVal::list(found.clone());
+                write!(f, "Expected {} args; found values {}", expected, found)
+            }
+            LispError::TypeMismatch(expected, found) => {
+                write!(f, "Invalid type: expected {}, found {}", expected, found)
+            }
+            LispError::BadSpecialForm(message, form) => write!(f, "{}: {}", message, form),
+            LispError::NotFunction(value) => write!(f, "Not a function: {}", value),
+            LispError::UnboundVar(name) => write!(f, "Unbound variable: {}", name),
+            LispError::Parser(err) => write!(f, "{}", err),
+            LispError::User(message, irritants) => {
+                write!(f, "{}", message)?;
+                for irritant in irritants {
+                    write!(f, " {}", irritant)?;
+                }
+                Ok(())
+            }
+            LispError::Raised(value) => write!(f, "Uncaught exception: {}", value),
+            LispError::Default(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::error::*;
+    use crate::number::Number;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn message_and_irritants_test() {
+        let err = LispError::User(
+            "bad thing".to_owned(),
+            vec![
+                LispVal::Number(Number::Integer(1)),
+                LispVal::string("x".to_owned()),
+            ],
+        );
+        assert_eq!(err.message(), "bad thing");
+        assert_eq!(err.irritants().len(), 2);
+        assert_eq!(err.to_string(), "bad thing 1 \"x\"");
+
+        let err = LispError::UnboundVar(Symbol::intern("y"));
+        assert_eq!(err.message(), "Unbound variable");
+        assert_eq!(err.irritants(), vec![LispVal::symbol("y")]);
+        assert_eq!(err.to_string(), "Unbound variable: y");
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let err = LispError::from(parse_program("(a").unwrap_err());
+        assert!(err.to_string().starts_with("Parse error at line 1"));
+        assert!(err.irritants().is_empty());
+    }
+}
@@ -0,0 +1,1168 @@
+use crate::analyze::{analyze, Body, CaseClause, ControlOp, Node, Proto};
+use crate::error::LispError;
+use crate::expand::{expand, Macro};
+use crate::gc::{self, Trace, Tracer};
+use crate::parser::LispVal;
+use crate::primitives::is_eqv;
+use crate::symbol::Symbol;
+use crate::vm;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A lexical environment frame. Frames are shared between closures through
+/// `Rc`, so a `define` or `set!` made through one closure is visible to every
+/// other closure that captured the same frame.
+#[derive(Default)]
+pub struct Env {
+    vars: RefCell<HashMap<Symbol, LispVal>>,
+    parent: Option<Rc<Env>>,
+}
+
+impl Env {
+    pub fn new() -> Rc<Env> {
+        gc::alloc(Env::default())
+    }
+
+    pub fn extend(parent: &Rc<Env>) -> Rc<Env> {
+        gc::alloc(Env {
+            vars: RefCell::new(HashMap::new()),
+            parent: Some(parent.clone()),
+        })
+    }
+
+    pub fn get(&self, name: Symbol) -> Result<LispVal, LispError> {
+        match self.vars.borrow().get(&name) {
+            Some(value) => Ok(value.clone()),
+            None => match &self.parent {
+                Some(parent) => parent.get(name),
+                None => Err(LispError::UnboundVar(name)),
+            },
+        }
+    }
+
+    pub fn set(&self, name: Symbol, value: LispVal) -> Result<(), LispError> {
+        if let Some(slot) = self.vars.borrow_mut().get_mut(&name) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.parent {
+            Some(parent) => parent.set(name, value),
+            None => Err(LispError::UnboundVar(name)),
+        }
+    }
+
+    pub fn define(&self, name: Symbol, value: LispVal) {
+        self.vars.borrow_mut().insert(name, value);
+    }
+
+    /// Looks up a macro defined by `define-syntax`, without copying any other
+    /// value found along the way.
+    pub fn get_macro(&self, name: Symbol) -> Option<Rc<Macro>> {
+        match self.vars.borrow().get(&name) {
+            Some(LispVal::Macro(m)) => Some(m.clone()),
+            Some(_) => None,
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.get_macro(name)),
+        }
+    }
+}
+
+impl Trace for Env {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self.vars.borrow().values() {
+            tracer.value(value);
+        }
+        if let Some(parent) = &self.parent {
+            tracer.rc(parent);
+        }
+    }
+
+    fn clear(&self) {
+        let vars = self.vars.take();
+        drop(vars);
+    }
+}
+
+/// A user-defined procedure together with the environment it closes over.
+pub struct Lambda {
+    pub proto: Rc<Proto>,
+    pub closure: Rc<Env>,
+}
+
+// Procedures compare by identity, like `eq?`; comparing bodies would say
+// nothing useful and comparing closures could recurse forever.
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Lambda) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Trace for Lambda {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.rc(&self.closure);
+    }
+}
+
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<_> = self.proto.params.iter().map(|p| p.as_str()).collect();
+        write!(f, "(lambda ({}", params.join(" "))?;
+        if let Some(vararg) = &self.proto.vararg {
+            write!(f, " . {}", vararg)?;
+        }
+        write!(f, ") ...)")
+    }
+}
+
+/// An entry in the list of active `dynamic-wind` calls, innermost first.
+pub(crate) struct Wind {
+    pub before: LispVal,
+    pub after: LispVal,
+    pub parent: Option<Rc<Wind>>,
+    pub depth: usize,
+}
+
+impl Trace for Wind {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.value(&self.before);
+        tracer.value(&self.after);
+        if let Some(parent) = &self.parent {
+            tracer.rc(parent);
+        }
+    }
+}
+
+pub(crate) fn wind_depth(winders: &Option<Rc<Wind>>) -> usize {
+    winders.as_ref().map_or(0, |wind| wind.depth)
+}
+
+fn same_winders(a: &Option<Rc<Wind>>, b: &Option<Rc<Wind>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// An entry in the list of installed exception handlers, innermost first.
+pub(crate) struct Handler {
+    pub handler: LispVal,
+    pub parent: Option<Rc<Handler>>,
+}
+
+impl Trace for Handler {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.value(&self.handler);
+        if let Some(parent) = &self.parent {
+            tracer.rc(parent);
+        }
+    }
+}
+
+/// A thunk to call while control travels between two sets of winders, and the
+/// winders in effect while it runs.
+#[derive(Clone)]
+pub(crate) struct WindStep {
+    pub thunk: LispVal,
+    pub winders: Option<Rc<Wind>>,
+}
+
+/// The thunks to run when control passes from the winders `from` to the
+/// winders `to`: the `after` thunks of the `dynamic-wind` calls being left and
+/// then the `before` thunks of those being re-entered. The steps are stored
+/// last step first, ready to be popped.
+pub(crate) fn travel(from: &Option<Rc<Wind>>, to: &Option<Rc<Wind>>) -> Vec<WindStep> {
+    let mut from = from.clone();
+    let mut to = to.clone();
+    let mut afters = vec![];
+    let mut befores = vec![];
+    while !same_winders(&from, &to) {
+        if wind_depth(&from) >= wind_depth(&to) {
+            let wind = from.unwrap();
+            afters.push(WindStep {
+                thunk: wind.after.clone(),
+                winders: wind.parent.clone(),
+            });
+            from = wind.parent.clone();
+        } else {
+            let wind = to.unwrap();
+            befores.push(WindStep {
+                thunk: wind.before.clone(),
+                winders: wind.parent.clone(),
+            });
+            to = wind.parent.clone();
+        }
+    }
+    // Afters run innermost first and befores outermost first.
+    afters.reverse();
+    befores.extend(afters);
+    befores
+}
+
+/// The arguments of `(%apply f (a b (c d)))`, which calls `(f a b c d)`.
+pub(crate) fn spread_args(spread: LispVal) -> Result<Vec<LispVal>, LispError> {
+    let mut args = match spread.list_to_vec() {
+        Some(args) => args,
+        None => return Err(LispError::TypeMismatch("list", spread)),
+    };
+    match args.pop() {
+        Some(last) => match last.list_to_vec() {
+            Some(rest) => {
+                args.extend(rest);
+                Ok(args)
+            }
+            None => Err(LispError::TypeMismatch("list", last)),
+        },
+        None => Err(LispError::NumArgs(1, args)),
+    }
+}
+
+/// The work remaining once the value being computed is known. The stack of
+/// frames is the continuation of the current expression; `call/cc` copies it,
+/// which is what lets a continuation be resumed any number of times.
+#[derive(Clone)]
+enum Frame {
+    If(Rc<Env>, Rc<Node>, Option<Rc<Node>>),
+    Sequence(Rc<Env>, Body, usize),
+    Define(Rc<Env>, Symbol),
+    Set(Rc<Env>, Symbol),
+    // The operands of a call evaluated so far, and the call itself.
+    Args(Rc<Env>, Option<ControlOp>, Body, Vec<LispVal>),
+    And(Rc<Env>, Body, usize),
+    Or(Rc<Env>, Body, usize),
+    Case(Rc<Env>, Rc<[CaseClause]>),
+    WindBefore(LispVal, LispVal, LispVal),
+    WindBody(Rc<Wind>),
+    WindAfter(LispVal),
+    // The steps still to run before a continuation's frames take over, stored
+    // last step first.
+    Travel(
+        Vec<WindStep>,
+        Option<Rc<Wind>>,
+        Option<Rc<Handler>>,
+        LispVal,
+    ),
+    // The handlers to reinstate once a handler or its thunk returns.
+    Handlers(Option<Rc<Handler>>),
+    // Reached when the handler for a `raise` returns.
+    Raised(LispVal),
+}
+
+/// A continuation captured by `call/cc`.
+pub struct Continuation {
+    frames: Vec<Frame>,
+    winders: Option<Rc<Wind>>,
+    handlers: Option<Rc<Handler>>,
+}
+
+impl Frame {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Frame::If(env, ..)
+            | Frame::Sequence(env, ..)
+            | Frame::Define(env, _)
+            | Frame::Set(env, _)
+            | Frame::And(env, ..)
+            | Frame::Or(env, ..)
+            | Frame::Case(env, _) => tracer.rc(env),
+            Frame::Args(env, _, _, values) => {
+                tracer.rc(env);
+                for value in values {
+                    tracer.value(value);
+                }
+            }
+            Frame::WindBefore(before, thunk, after) => {
+                tracer.value(before);
+                tracer.value(thunk);
+                tracer.value(after);
+            }
+            Frame::WindBody(wind) => tracer.rc(wind),
+            Frame::WindAfter(value) | Frame::Raised(value) => tracer.value(value),
+            Frame::Travel(steps, winders, handlers, result) => {
+                trace_steps(steps, tracer);
+                trace_dynamic(winders, handlers, tracer);
+                tracer.value(result);
+            }
+            Frame::Handlers(handlers) => trace_dynamic(&None, handlers, tracer),
+        }
+    }
+}
+
+pub(crate) fn trace_steps(steps: &[WindStep], tracer: &mut Tracer) {
+    for step in steps {
+        tracer.value(&step.thunk);
+        if let Some(winders) = &step.winders {
+            tracer.rc(winders);
+        }
+    }
+}
+
+pub(crate) fn trace_dynamic(
+    winders: &Option<Rc<Wind>>,
+    handlers: &Option<Rc<Handler>>,
+    tracer: &mut Tracer,
+) {
+    if let Some(winders) = winders {
+        tracer.rc(winders);
+    }
+    if let Some(handlers) = handlers {
+        tracer.rc(handlers);
+    }
+}
+
+impl Trace for Continuation {
+    fn trace(&self, tracer: &mut Tracer) {
+        for frame in &self.frames {
+            frame.trace(tracer);
+        }
+        trace_dynamic(&self.winders, &self.handlers, tracer);
+    }
+}
+
+impl PartialEq for Continuation {
+    fn eq(&self, other: &Continuation) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Continuation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<continuation>")
+    }
+}
+
+enum State {
+    Eval(Rc<Env>, Rc<Node>),
+    Apply(LispVal, Vec<LispVal>),
+    Return(LispVal),
+}
+
+type StateResult = Result<State, LispError>;
+
+pub(crate) fn is_true(value: &LispVal) -> bool {
+    *value != LispVal::Boolean(false)
+}
+
+pub fn eval(env: &Rc<Env>, expr: &LispVal) -> Result<LispVal, LispError> {
+    let node = analyze(&expand(env, expr)?)?;
+    Machine::default().run(State::Eval(env.clone(), node))
+}
+
+/// Calls a procedure from outside the evaluator, such as from the bytecode
+/// VM. Continuations captured inside cannot escape it.
+pub(crate) fn apply(func: LispVal, args: Vec<LispVal>) -> Result<LispVal, LispError> {
+    Machine::default().run(State::Apply(func, args))
+}
+
+#[derive(Default)]
+struct Machine {
+    frames: Vec<Frame>,
+    winders: Option<Rc<Wind>>,
+    handlers: Option<Rc<Handler>>,
+}
+
+impl Machine {
+    fn run(&mut self, mut state: State) -> Result<LispVal, LispError> {
+        loop {
+            gc::safe_point();
+            let next = match state {
+                State::Eval(env, node) => self.eval(env, &node),
+                State::Apply(func, args) => self.apply(func, args),
+                State::Return(value) => match self.frames.pop() {
+                    Some(frame) => self.resume(frame, value),
+                    None => return Ok(value),
+                },
+            };
+            // Errors become error objects raised to the current handler, if
+            // there is one.
+            state = match next {
+                Ok(state) => state,
+                Err(err) if self.handlers.is_some() => self.raise(LispVal::error(err), false)?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn eval(&mut self, env: Rc<Env>, node: &Node) -> StateResult {
+        match node {
+            Node::Const(value) => Ok(State::Return(value.clone())),
+            Node::Var(name) => Ok(State::Return(env.get(*name)?)),
+            Node::If(pred, conseq, alt) => {
+                self.frames
+                    .push(Frame::If(env.clone(), conseq.clone(), alt.clone()));
+                Ok(State::Eval(env, pred.clone()))
+            }
+            Node::Define(name, value) => {
+                self.frames.push(Frame::Define(env.clone(), *name));
+                Ok(State::Eval(env, value.clone()))
+            }
+            Node::Set(name, value) => {
+                self.frames.push(Frame::Set(env.clone(), *name));
+                Ok(State::Eval(env, value.clone()))
+            }
+            Node::Lambda(proto) => Ok(State::Return(LispVal::Func(gc::alloc(Lambda {
+                proto: proto.clone(),
+                closure: env,
+            })))),
+            Node::NamedLambda(name, proto) => {
+                let env = Env::extend(&env);
+                let func = LispVal::Func(gc::alloc(Lambda {
+                    proto: proto.clone(),
+                    closure: env.clone(),
+                }));
+                env.define(*name, func.clone());
+                Ok(State::Return(func))
+            }
+            Node::Sequence(body) => Ok(self.sequence(env, body, 0)),
+            Node::Apply(exprs) => Ok(self.call(env, None, exprs)),
+            Node::Control(op, exprs) => Ok(self.call(env, Some(*op), exprs)),
+            Node::And(exprs) => Ok(self.and_or(env, exprs, 0, true)),
+            Node::Or(exprs) => Ok(self.and_or(env, exprs, 0, false)),
+            Node::Case(key, clauses) => {
+                self.frames.push(Frame::Case(env.clone(), clauses.clone()));
+                Ok(State::Eval(env, key.clone()))
+            }
+        }
+    }
+
+    /// Evaluates `body` from `next` on, leaving the last expression in tail
+    /// position with no frame of its own.
+    fn sequence(&mut self, env: Rc<Env>, body: &Body, next: usize) -> State {
+        match body.len() - next {
+            0 => State::Return(LispVal::Unspecified),
+            1 => State::Eval(env, body[next].clone()),
+            _ => {
+                self.frames
+                    .push(Frame::Sequence(env.clone(), body.clone(), next + 1));
+                State::Eval(env, body[next].clone())
+            }
+        }
+    }
+
+    fn call(&mut self, env: Rc<Env>, op: Option<ControlOp>, exprs: &Body) -> State {
+        match exprs.first() {
+            Some(first) => {
+                let first = first.clone();
+                let values = Vec::with_capacity(exprs.len());
+                self.frames
+                    .push(Frame::Args(env.clone(), op, exprs.clone(), values));
+                State::Eval(env, first)
+            }
+            None => State::Return(LispVal::Unspecified),
+        }
+    }
+
+    /// `and` stops at the first false value and `or` at the first true one.
+    fn and_or(&mut self, env: Rc<Env>, exprs: &Body, next: usize, is_and: bool) -> State {
+        match exprs.len() - next {
+            0 => State::Return(LispVal::Boolean(is_and)),
+            1 => State::Eval(env, exprs[next].clone()),
+            _ => {
+                let frame = if is_and {
+                    Frame::And(env.clone(), exprs.clone(), next + 1)
+                } else {
+                    Frame::Or(env.clone(), exprs.clone(), next + 1)
+                };
+                self.frames.push(frame);
+                State::Eval(env, exprs[next].clone())
+            }
+        }
+    }
+
+    fn resume(&mut self, frame: Frame, value: LispVal) -> StateResult {
+        match frame {
+            Frame::If(env, conseq, alt) => match (is_true(&value), alt) {
+                (true, _) => Ok(State::Eval(env, conseq)),
+                (false, Some(alt)) => Ok(State::Eval(env, alt)),
+                (false, None) => Ok(State::Return(LispVal::Unspecified)),
+            },
+            Frame::Sequence(env, body, next) => Ok(self.sequence(env, &body, next)),
+            Frame::Define(env, name) => {
+                env.define(name, value);
+                Ok(State::Return(LispVal::Unspecified))
+            }
+            Frame::Set(env, name) => {
+                env.set(name, value)?;
+                Ok(State::Return(LispVal::Unspecified))
+            }
+            Frame::Args(env, op, exprs, mut values) => {
+                values.push(value);
+                if values.len() < exprs.len() {
+                    let next = exprs[values.len()].clone();
+                    self.frames
+                        .push(Frame::Args(env.clone(), op, exprs, values));
+                    return Ok(State::Eval(env, next));
+                }
+                match op {
+                    Some(op) => self.control(op, values),
+                    None => {
+                        let func = values.remove(0);
+                        Ok(State::Apply(func, values))
+                    }
+                }
+            }
+            Frame::And(env, exprs, next) if is_true(&value) => {
+                Ok(self.and_or(env, &exprs, next, true))
+            }
+            Frame::Or(env, exprs, next) if !is_true(&value) => {
+                Ok(self.and_or(env, &exprs, next, false))
+            }
+            Frame::And(..) | Frame::Or(..) => Ok(State::Return(value)),
+            Frame::Case(env, clauses) => {
+                let clause = clauses.iter().find(|clause| match &clause.data {
+                    Some(data) => data.iter().any(|datum| is_eqv(datum, &value)),
+                    None => true,
+                });
+                match clause {
+                    Some(clause) => Ok(self.sequence(env, &clause.body, 0)),
+                    None => Ok(State::Return(LispVal::Unspecified)),
+                }
+            }
+            Frame::WindBefore(before, thunk, after) => {
+                let wind = gc::alloc(Wind {
+                    before,
+                    after,
+                    depth: wind_depth(&self.winders) + 1,
+                    parent: self.winders.take(),
+                });
+                self.winders = Some(wind.clone());
+                self.frames.push(Frame::WindBody(wind));
+                Ok(State::Apply(thunk, vec![]))
+            }
+            Frame::WindBody(wind) => {
+                self.winders = wind.parent.clone();
+                self.frames.push(Frame::WindAfter(value));
+                Ok(State::Apply(wind.after.clone(), vec![]))
+            }
+            Frame::WindAfter(result) => Ok(State::Return(result)),
+            Frame::Travel(mut steps, target, handlers, result) => match steps.pop() {
+                Some(step) => {
+                    self.winders = step.winders;
+                    self.frames
+                        .push(Frame::Travel(steps, target, handlers, result));
+                    Ok(State::Apply(step.thunk, vec![]))
+                }
+                None => {
+                    self.winders = target;
+                    self.handlers = handlers;
+                    Ok(State::Return(result))
+                }
+            },
+            Frame::Handlers(handlers) => {
+                self.handlers = handlers;
+                Ok(State::Return(value))
+            }
+            Frame::Raised(obj) => Err(LispError::Default(format!(
+                "Handler returned from non-continuable raise of {}",
+                obj
+            ))),
+        }
+    }
+
+    fn apply(&mut self, func: LispVal, args: Vec<LispVal>) -> StateResult {
+        match func {
+            LispVal::PrimitiveFunc(primitive) => Ok(State::Return((primitive.func)(&args)?)),
+            LispVal::NativeFunc(native) => Ok(State::Return(native.call(args)?)),
+            LispVal::Func(lambda) => {
+                let proto = &lambda.proto;
+                let arity_ok = match proto.vararg {
+                    Some(_) => args.len() >= proto.params.len(),
+                    None => args.len() == proto.params.len(),
+                };
+                if !arity_ok {
+                    return Err(LispError::NumArgs(proto.params.len(), args));
+                }
+                let env = Env::extend(&lambda.closure);
+                for (param, arg) in proto.params.iter().zip(&args) {
+                    env.define(*param, arg.clone());
+                }
+                if let Some(vararg) = &proto.vararg {
+                    env.define(*vararg, LispVal::list(args[proto.params.len()..].to_vec()));
+                }
+                Ok(self.sequence(env, &proto.body, 0))
+            }
+            LispVal::Continuation(k) => match args.len() {
+                0 | 1 => {
+                    let result = args.into_iter().next().unwrap_or(LispVal::Unspecified);
+                    Ok(self.throw(&k, result))
+                }
+                _ => Err(LispError::NumArgs(1, args)),
+            },
+            LispVal::Closure(closure) => Ok(State::Return(vm::apply(&closure, args)?)),
+            _ => Err(LispError::NotFunction(func)),
+        }
+    }
+
+    fn control(&mut self, op: ControlOp, mut args: Vec<LispVal>) -> StateResult {
+        match (op, args.len()) {
+            (ControlOp::CallCC, 1) => {
+                let k = Continuation {
+                    frames: self.frames.clone(),
+                    winders: self.winders.clone(),
+                    handlers: self.handlers.clone(),
+                };
+                let func = args.remove(0);
+                Ok(State::Apply(
+                    func,
+                    vec![LispVal::Continuation(gc::alloc(k))],
+                ))
+            }
+            (ControlOp::DynamicWind, 3) => {
+                let after = args.pop().unwrap();
+                let thunk = args.pop().unwrap();
+                let before = args.pop().unwrap();
+                self.frames
+                    .push(Frame::WindBefore(before.clone(), thunk, after));
+                Ok(State::Apply(before, vec![]))
+            }
+            (ControlOp::Apply, 2) => {
+                let spread = args.pop().unwrap();
+                let func = args.pop().unwrap();
+                Ok(State::Apply(func, spread_args(spread)?))
+            }
+            (ControlOp::WithHandler, 2) => {
+                let thunk = args.pop().unwrap();
+                let handler = args.pop().unwrap();
+                self.frames.push(Frame::Handlers(self.handlers.clone()));
+                self.handlers = Some(gc::alloc(Handler {
+                    handler,
+                    parent: self.handlers.take(),
+                }));
+                Ok(State::Apply(thunk, vec![]))
+            }
+            (ControlOp::Raise, 1) => self.raise(args.remove(0), false),
+            (ControlOp::RaiseContinuable, 1) => self.raise(args.remove(0), true),
+            (ControlOp::CallCC, _) | (ControlOp::Raise, _) | (ControlOp::RaiseContinuable, _) => {
+                Err(LispError::NumArgs(1, args))
+            }
+            (ControlOp::DynamicWind, _) => Err(LispError::NumArgs(3, args)),
+            (ControlOp::Apply, _) | (ControlOp::WithHandler, _) => Err(LispError::NumArgs(2, args)),
+        }
+    }
+
+    /// Calls the current handler with `obj`, with the outer handlers installed
+    /// while it runs. A non-continuable raise is an error if the handler
+    /// returns; with no handler at all, `obj` escapes to the caller of `eval`.
+    fn raise(&mut self, obj: LispVal, continuable: bool) -> StateResult {
+        let handler = match self.handlers.clone() {
+            Some(handler) => handler,
+            None => {
+                return Err(match obj {
+                    LispVal::Error(err) => (*err).clone(),
+                    obj => LispError::Raised(obj),
+                })
+            }
+        };
+        self.frames.push(Frame::Handlers(self.handlers.take()));
+        if !continuable {
+            self.frames.push(Frame::Raised(obj.clone()));
+        }
+        self.handlers = handler.parent.clone();
+        Ok(State::Apply(handler.handler.clone(), vec![obj]))
+    }
+
+    /// Passes `result` to the continuation `k`, first running the `after`
+    /// thunks of the `dynamic-wind` calls being left and then the `before`
+    /// thunks of those being re-entered.
+    fn throw(&mut self, k: &Continuation, result: LispVal) -> State {
+        let steps = travel(&self.winders, &k.winders);
+        self.frames = k.frames.clone();
+        self.frames.push(Frame::Travel(
+            steps,
+            k.winders.clone(),
+            k.handlers.clone(),
+            result,
+        ));
+        State::Return(LispVal::Unspecified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::eval::*;
+    use crate::interpreter::Engine;
+    use crate::number;
+    use crate::parser::int;
+    use crate::parser::parse_lisp_expr;
+    use crate::primitives::primitive_env;
+
+    fn run(env: &Rc<Env>, input: &str) -> Result<LispVal, LispError> {
+        let (_, expr) = parse_lisp_expr(input).unwrap();
+        eval(env, &expr)
+    }
+
+    #[test]
+    fn self_evaluating_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        assert_eq!(run(&env, "42").unwrap(), int(42));
+        assert_eq!(
+            run(&env, "\"hi\"").unwrap(),
+            LispVal::string("hi".to_owned())
+        );
+        assert_eq!(run(&env, "#t").unwrap(), LispVal::Boolean(true));
+        assert_eq!(run(&env, "#\\a").unwrap(), LispVal::Char('a'));
+        assert_eq!(
+            run(&env, "#(1 x)").unwrap(),
+            LispVal::vector(vec![int(1), LispVal::symbol("x")])
+        );
+        assert_eq!(run(&env, "#u8(7)").unwrap(), LispVal::bytevector(vec![7]));
+    }
+
+    #[test]
+    fn quote_and_if_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        assert_eq!(
+            run(&env, "'(a b)").unwrap(),
+            LispVal::list(vec![LispVal::symbol("a"), LispVal::symbol("b")])
+        );
+        assert_eq!(run(&env, "(if #f 1 2)").unwrap(), int(2));
+        assert_eq!(run(&env, "(if 0 1 2)").unwrap(), int(1));
+        assert_eq!(run(&env, "(if #f 1)").unwrap(), LispVal::Unspecified);
+    }
+
+    #[test]
+    fn define_and_set_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(&env, "(define x 10)").unwrap();
+        assert_eq!(run(&env, "x").unwrap(), int(10));
+        run(&env, "(set! x (+ x 1))").unwrap();
+        assert_eq!(run(&env, "x").unwrap(), int(11));
+        assert_eq!(
+            run(&env, "(set! y 1)"),
+            Err(LispError::UnboundVar(Symbol::intern("y")))
+        );
+    }
+
+    #[test]
+    fn lambda_and_closure_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(
+            &env,
+            "(define (make-counter) ((lambda (n) (lambda () (set! n (+ n 1)) n)) 0))",
+        )
+        .unwrap();
+        run(&env, "(define c (make-counter))").unwrap();
+        run(&env, "(c)").unwrap();
+        assert_eq!(run(&env, "(c)").unwrap(), int(2));
+        run(&env, "(define (list . xs) xs)").unwrap();
+        assert_eq!(
+            run(&env, "(list 1 2)").unwrap(),
+            LispVal::list(vec![int(1), int(2)])
+        );
+        assert_eq!(run(&env, "((lambda (a . b) a) 3 4 5)").unwrap(), int(3));
+    }
+
+    #[test]
+    fn begin_and_recursion_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(
+            &env,
+            "(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))",
+        )
+        .unwrap();
+        assert_eq!(run(&env, "(begin 1 (fact 5))").unwrap(), int(120));
+        assert_eq!(
+            run(&env, "(fact 25)").unwrap(),
+            LispVal::Number(number::parse_number("15511210043330985984000000", 10).unwrap())
+        );
+    }
+
+    #[test]
+    fn derived_forms_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        assert_eq!(run(&env, "(let ((x 2) (y 3)) (* x y))").unwrap(), int(6));
+        assert_eq!(
+            run(
+                &env,
+                "(let loop ((i 0) (acc 1)) (if (= i 5) acc (loop (+ i 1) (* acc 2))))"
+            )
+            .unwrap(),
+            int(32)
+        );
+        assert_eq!(
+            run(&env, "(cond ((= 1 2) 'a) ((+ 1 1)) (else 'c))").unwrap(),
+            int(2)
+        );
+        assert_eq!(run(&env, "(cond (#f 1) (else 2 3))").unwrap(), int(3));
+        assert_eq!(run(&env, "(cond (#f 1))").unwrap(), LispVal::Unspecified);
+        assert_eq!(
+            run(
+                &env,
+                "(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite))"
+            )
+            .unwrap(),
+            LispVal::symbol("composite")
+        );
+        assert_eq!(run(&env, "(case 'x ((a) 1) (else 2))").unwrap(), int(2));
+        assert_eq!(
+            run(&env, "(cond ((assv 'b '((a 1) (b 2))) => cadr) (else #f))").unwrap(),
+            int(2)
+        );
+        assert_eq!(run(&env, "(cond (#f => car) (else 3))").unwrap(), int(3));
+        assert_eq!(
+            run(
+                &env,
+                "(case (car '(c d))
+                   ((a e i o u) 'vowel)
+                   ((w y) 'semivowel)
+                   (else => (lambda (x) x)))"
+            )
+            .unwrap(),
+            LispVal::symbol("c")
+        );
+        assert_eq!(
+            run(&env, "(case 2 ((1 2) => (lambda (x) (* x 10))) (else 0))").unwrap(),
+            int(20)
+        );
+        // The value passed on by `=>` does not shadow the clause's variables.
+        assert_eq!(
+            run(
+                &env,
+                "(let ((value 1)) (cond (value => (lambda (v) (+ v value)))))"
+            )
+            .unwrap(),
+            int(2)
+        );
+        assert_eq!(run(&env, "(and 1 2)").unwrap(), int(2));
+        assert_eq!(run(&env, "(and 1 #f 2)").unwrap(), LispVal::Boolean(false));
+        assert_eq!(run(&env, "(and)").unwrap(), LispVal::Boolean(true));
+        assert_eq!(run(&env, "(or #f 3)").unwrap(), int(3));
+        assert_eq!(run(&env, "(or)").unwrap(), LispVal::Boolean(false));
+        assert_eq!(run(&env, "(when (= 1 1) 1 2)").unwrap(), int(2));
+        assert_eq!(
+            run(&env, "(unless (= 1 1) 1 2)").unwrap(),
+            LispVal::Unspecified
+        );
+        assert!(run(&env, "(cond (else 1) (#t 2))").is_err());
+        assert!(run(&env, "(let ((x)) x)").is_err());
+    }
+
+    #[test]
+    fn tail_call_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        let n = 1_000_000;
+        assert_eq!(
+            run(
+                &env,
+                &format!("(let loop ((i 0)) (if (< i {}) (loop (+ i 1)) i))", n)
+            )
+            .unwrap(),
+            int(n)
+        );
+        // Every tail position: the last expression of a body, `begin`, `cond`,
+        // `case`, `and`, `or`, `when` and `unless`.
+        run(
+            &env,
+            "(define (count i n)
+               (cond ((= i n) i)
+                     ((= (remainder i 2) 0)
+                      (case (remainder i 3)
+                        ((0) (and #t (count (+ i 1) n)))
+                        (else (or #f (count (+ i 1) n)))))
+                     (else
+                      (when #t
+                        (unless #f
+                          (begin 'ignored (count (+ i 1) n)))))))",
+        )
+        .unwrap();
+        assert_eq!(run(&env, &format!("(count 0 {})", n)).unwrap(), int(n));
+        run(&env, "(define (even? n) (if (= n 0) #t (odd? (- n 1))))").unwrap();
+        run(&env, "(define (odd? n) (if (= n 0) #f (even? (- n 1))))").unwrap();
+        assert_eq!(
+            run(&env, &format!("(even? {})", n)).unwrap(),
+            LispVal::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn deep_recursion_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(
+            &env,
+            "(define (sum-to n) (if (= n 0) 0 (+ n (sum-to (- n 1)))))",
+        )
+        .unwrap();
+        assert_eq!(run(&env, "(sum-to 100000)").unwrap(), int(5000050000));
+    }
+
+    #[test]
+    fn call_cc_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        assert_eq!(
+            run(&env, "(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))").unwrap(),
+            int(3)
+        );
+        assert_eq!(
+            run(&env, "(call-with-current-continuation (lambda (k) 5))").unwrap(),
+            int(5)
+        );
+        // Early exit from a loop.
+        run(
+            &env,
+            "(define (find-first pred items)
+               (call/cc
+                 (lambda (return)
+                   (let loop ((items items))
+                     (when (pred (car items)) (return (car items)))
+                     (loop (cdr items))))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run(&env, "(find-first (lambda (n) (> n 2)) '(1 2 3 4))").unwrap(),
+            int(3)
+        );
+        assert!(matches!(
+            run(&env, "(call/cc (lambda (k) k))").unwrap(),
+            LispVal::Continuation(_)
+        ));
+        assert_eq!(run(&env, "(apply + 1 2 '(3 4))").unwrap(), int(10));
+        assert_eq!(
+            run(&env, "(apply (lambda xs xs) '())").unwrap(),
+            LispVal::Nil
+        );
+    }
+
+    #[test]
+    fn reentrant_continuation_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(&env, "(define k #f)").unwrap();
+        run(&env, "(define n 0)").unwrap();
+        // Re-entering the continuation of the `call/cc` runs the rest of the
+        // `begin` again, until `n` reaches 3.
+        assert_eq!(
+            run(
+                &env,
+                "(begin
+                   (define r (+ 100 (call/cc (lambda (c) (set! k c) 0))))
+                   (set! n (+ n 1))
+                   (if (< n 3) (k n) r))",
+            )
+            .unwrap(),
+            int(102)
+        );
+        // A continuation from an earlier evaluation can be resumed later.
+        assert_eq!(run(&env, "(k 5)").unwrap(), int(105));
+        // A generator that hands control back and forth.
+        run(
+            &env,
+            "(define (make-generator items)
+               (define return #f)
+               (define (resume-here)
+                 (let loop ((items items))
+                   (when (< 0 (length items))
+                     (call/cc
+                       (lambda (next)
+                         (set! resume-here (lambda () (next #f)))
+                         (return (car items))))
+                     (loop (cdr items))))
+                 (return 'done))
+               (lambda () (call/cc (lambda (k) (set! return k) (resume-here)))))",
+        )
+        .unwrap();
+        run(
+            &env,
+            "(define (length items) (if (eqv? items '()) 0 (+ 1 (length (cdr items)))))",
+        )
+        .unwrap();
+        run(&env, "(define next (make-generator '(a b)))").unwrap();
+        let atom = |name: &str| LispVal::symbol(name);
+        assert_eq!(run(&env, "(next)").unwrap(), atom("a"));
+        assert_eq!(run(&env, "(next)").unwrap(), atom("b"));
+        assert_eq!(run(&env, "(next)").unwrap(), atom("done"));
+    }
+
+    #[test]
+    fn dynamic_wind_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(&env, "(define trace '())").unwrap();
+        run(&env, "(define (note x) (set! trace (cons x trace)))").unwrap();
+        run(
+            &env,
+            "(define (wind name thunk)
+               (dynamic-wind (lambda () (note (cons 'in name)))
+                             thunk
+                             (lambda () (note (cons 'out name)))))",
+        )
+        .unwrap();
+        let trace = |env: &Rc<Env>| {
+            let value = run(env, "trace").unwrap().to_string();
+            run(env, "(set! trace '())").unwrap();
+            value
+        };
+
+        assert_eq!(
+            run(&env, "(wind 'a (lambda () (note 'body) 1))").unwrap(),
+            int(1)
+        );
+        assert_eq!(trace(&env), "((out . a) body (in . a))");
+
+        // Escaping runs the after thunks, innermost first.
+        assert_eq!(
+            run(
+                &env,
+                "(call/cc (lambda (k) (wind 'a (lambda () (wind 'b (lambda () (k 2)))))))"
+            )
+            .unwrap(),
+            int(2)
+        );
+        assert_eq!(trace(&env), "((out . a) (out . b) (in . b) (in . a))");
+
+        // Re-entering runs the before thunks again, outermost first, and
+        // jumping between extents only unwinds what is not shared.
+        run(&env, "(define k #f)").unwrap();
+        run(
+            &env,
+            "(wind 'a (lambda ()
+               (wind 'b (lambda () (call/cc (lambda (c) (set! k c)))))
+               (note 'rest)))",
+        )
+        .unwrap();
+        assert_eq!(trace(&env), "((out . a) rest (out . b) (in . b) (in . a))");
+        run(&env, "(define done #f)").unwrap();
+        run(
+            &env,
+            "(unless done (set! done #t) (wind 'c (lambda () (k 'again))))",
+        )
+        .unwrap();
+        assert_eq!(
+            trace(&env),
+            "((out . a) rest (out . b) (in . b) (in . a) (out . c) (in . c))"
+        );
+    }
+
+    #[test]
+    fn exception_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        let show = |env: &Rc<Env>, input: &str| run(env, input).unwrap().to_string();
+        assert_eq!(
+            show(
+                &env,
+                "(guard (e ((error-object? e)
+                            (cons (error-object-message e) (error-object-irritants e))))
+                   (error \"Something bad\" 1 'x))"
+            ),
+            "(\"Something bad\" 1 x)"
+        );
+        // Errors from the evaluator and primitives are error objects too.
+        assert_eq!(
+            show(&env, "(guard (e (#t (error-object-message e))) (car '()))"),
+            "\"Invalid type: expected pair\""
+        );
+        assert_eq!(
+            show(
+                &env,
+                "(guard (e (#t (error-object-irritants e))) (nowhere))"
+            ),
+            "(nowhere)"
+        );
+        // Any object can be raised; clauses are tried like `cond`.
+        assert_eq!(
+            show(
+                &env,
+                "(guard (e ((eq? e 'a) 1) ((eq? e 'b) => (lambda (x) x)) (else 3))
+                   (raise 'b))"
+            ),
+            "#t"
+        );
+        assert_eq!(show(&env, "(guard (e (else 3)) (+ 1 2))"), "3");
+        // With no matching clause the object is re-raised to the outer guard.
+        assert_eq!(
+            show(
+                &env,
+                "(guard (e (#t (cons 'outer e)))
+                   (guard (e ((eq? e 'other) 'inner)) (raise 'x)))"
+            ),
+            "(outer . x)"
+        );
+        // A handler's value is returned from `raise-continuable`, but not
+        // from `raise`.
+        assert_eq!(
+            show(
+                &env,
+                "(with-exception-handler
+                   (lambda (e) 10)
+                   (lambda () (+ 1 (raise-continuable 'c))))"
+            ),
+            "11"
+        );
+        assert!(run(
+            &env,
+            "(with-exception-handler (lambda (e) 10) (lambda () (+ 1 (raise 'c))))"
+        )
+        .is_err());
+        // Handlers run with the outer handlers installed.
+        assert_eq!(
+            show(
+                &env,
+                "(guard (e (#t (list 'outer e)))
+                   (with-exception-handler
+                     (lambda (e) (raise (list 'inner e)))
+                     (lambda () (raise 'x))))"
+            ),
+            "(outer (inner x))"
+        );
+        // Unwinding out of a guard runs the after thunks.
+        run(&env, "(define unwound #f)").unwrap();
+        assert_eq!(
+            show(
+                &env,
+                "(guard (e (#t e))
+                   (dynamic-wind (lambda () #f)
+                                 (lambda () (raise 'escaped))
+                                 (lambda () (set! unwound #t))))"
+            ),
+            "escaped"
+        );
+        assert_eq!(show(&env, "unwound"), "#t");
+        // Uncaught, errors surface as they would without a handler.
+        assert_eq!(
+            run(&env, "(error \"bad\" 2)"),
+            Err(LispError::User("bad".to_owned(), vec![int(2)]))
+        );
+        assert_eq!(
+            run(&env, "(raise 'oops)"),
+            Err(LispError::Raised(LispVal::symbol("oops")))
+        );
+        assert_eq!(
+            run(&env, "(guard (e (#f 1)) (car 5))"),
+            Err(LispError::TypeMismatch("pair", int(5)))
+        );
+    }
+
+    #[test]
+    fn pair_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        let show = |env: &Rc<Env>, input: &str| run(env, input).unwrap().to_string();
+        run(&env, "(define tail (list 2 3))").unwrap();
+        run(&env, "(define items (cons 1 tail))").unwrap();
+        run(&env, "(set-car! tail 'two)").unwrap();
+        assert_eq!(show(&env, "items"), "(1 two 3)");
+        assert_eq!(show(&env, "(eq? (cdr items) tail)"), "#t");
+        assert_eq!(show(&env, "(eq? (list 1) (list 1))"), "#f");
+        run(&env, "(set-cdr! (cdr tail) items)").unwrap();
+        assert_eq!(show(&env, "items"), "#0=(1 two 3 . #0#)");
+        assert_eq!(
+            show(&env, "(apply (lambda (a b . rest) rest) '(1 2 3 . ()))"),
+            "(3)"
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        assert_eq!(
+            run(&env, "(undefined 1)"),
+            Err(LispError::UnboundVar(Symbol::intern("undefined")))
+        );
+        assert_eq!(run(&env, "(1 2)"), Err(LispError::NotFunction(int(1))));
+        assert_eq!(
+            run(&env, "((lambda (x) x))"),
+            Err(LispError::NumArgs(1, vec![]))
+        );
+    }
+}
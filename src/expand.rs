@@ -0,0 +1,1495 @@
+use crate::error::LispError;
+use crate::eval::Env;
+use crate::gc::{self, Trace, Tracer};
+use crate::parser::LispVal;
+use crate::symbol::Symbol;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+// Keywords of the core forms. Each is recognised by what it resolves to, so a
+// local variable named `if` really does shadow `if`.
+const SPECIAL_FORMS: &[&str] = &[
+    "quote",
+    "quasiquote",
+    "unquote",
+    "unquote-splicing",
+    "if",
+    "define",
+    "set!",
+    "lambda",
+    "begin",
+    "let",
+    "cond",
+    "case",
+    "and",
+    "or",
+    "when",
+    "unless",
+    "define-syntax",
+    "let-syntax",
+    "letrec-syntax",
+    "syntax-rules",
+    "else",
+    "=>",
+    "...",
+    "_",
+];
+
+/// A macro defined by `syntax-rules`, closed over the scope of its
+/// definition.
+pub struct Macro {
+    ellipsis: Option<Symbol>,
+    literals: Vec<Symbol>,
+    rules: Vec<(LispVal, LispVal)>,
+    scope: Option<Rc<Scope>>,
+    /// The identifiers in the rules that were inserted by another macro,
+    /// with the global names they stand for. Only a macro defined at the top
+    /// level outlives the expansion whose aliases it was written with, and
+    /// at the top level every alias stands for a global name.
+    aliases: HashMap<Symbol, Symbol>,
+}
+
+impl Macro {
+    /// The name `id` stands for where the macro was defined.
+    fn unalias(&self, id: Symbol) -> Symbol {
+        self.aliases.get(&id).copied().unwrap_or(id)
+    }
+}
+
+// The rules are lists that host code may still hold and change.
+impl Trace for Macro {
+    fn trace(&self, tracer: &mut Tracer) {
+        for (pattern, template) in &self.rules {
+            tracer.value(pattern);
+            tracer.value(template);
+        }
+    }
+}
+
+// Macros compare by identity, like procedures.
+impl PartialEq for Macro {
+    fn eq(&self, other: &Macro) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Macro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<syntax-rules>")
+    }
+}
+
+#[derive(Clone)]
+enum Binding {
+    Variable(Symbol),
+    Macro(Rc<Macro>),
+}
+
+/// The identifiers bound by one lambda, `let`, body or `let-syntax`. Scopes
+/// only exist during expansion; the top-level scope is the environment.
+pub struct Scope {
+    bindings: RefCell<HashMap<Symbol, Binding>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    fn extend(parent: &Option<Rc<Scope>>) -> Rc<Scope> {
+        Rc::new(Scope {
+            bindings: RefCell::new(HashMap::new()),
+            parent: parent.clone(),
+        })
+    }
+
+    fn bind(&self, id: Symbol, binding: Binding) {
+        self.bindings.borrow_mut().insert(id, binding);
+    }
+}
+
+/// What an identifier means at the point it is used.
+#[derive(Clone, PartialEq)]
+enum Resolved {
+    Local(Symbol),
+    Global(Symbol),
+    Special(&'static str),
+    Macro(Rc<Macro>),
+}
+
+/// An identifier inserted by a macro template, standing for `name` as seen
+/// from `scope`, the scope in which the macro was defined.
+struct Alias {
+    name: Symbol,
+    scope: Option<Rc<Scope>>,
+}
+
+fn bad_form(message: &'static str, expr: &LispVal) -> LispError {
+    LispError::BadSpecialForm(message, expr.clone())
+}
+
+fn atom(name: &str) -> LispVal {
+    LispVal::symbol(name)
+}
+
+/// Splits a list or dotted list into its elements and its tail, if any.
+fn split_list(value: &LispVal) -> Option<(Vec<LispVal>, Option<LispVal>)> {
+    match value {
+        LispVal::Pair(_) => match value.split_tail()? {
+            (items, LispVal::Nil) => Some((items, None)),
+            (items, tail) => Some((items, Some(tail))),
+        },
+        LispVal::Nil => Some((vec![], None)),
+        _ => None,
+    }
+}
+
+fn make_list(items: Vec<LispVal>, tail: Option<LispVal>) -> LispVal {
+    LispVal::dotted_list(items, tail.unwrap_or(LispVal::Nil))
+}
+
+fn quasi_quote(datum: LispVal) -> LispVal {
+    LispVal::list(vec![atom("quote"), datum])
+}
+
+/// The datum of a `(quote datum)` form made by `quasi_quote`.
+fn quoted(code: &LispVal) -> Option<LispVal> {
+    match code.list_to_vec()?.as_slice() {
+        [LispVal::Atom(head), datum] if *head == "quote" => Some(datum.clone()),
+        _ => None,
+    }
+}
+
+/// Code consing the values of `car` and `cdr`, which is a constant when they
+/// both are.
+fn quasi_cons(car: LispVal, cdr: LispVal) -> LispVal {
+    match (quoted(&car), quoted(&cdr)) {
+        (Some(car), Some(cdr)) => quasi_quote(LispVal::cons(car, cdr)),
+        _ => LispVal::list(vec![atom("cons"), car, cdr]),
+    }
+}
+
+/// Expands every macro use in `expr`, returning code made only of core forms.
+///
+/// Expansion is hygienic: every local variable is renamed to a fresh name,
+/// and identifiers inserted by a macro refer to whatever they meant where the
+/// macro was defined, so neither side can capture the other's bindings.
+/// Top-level `define-syntax` forms add their macros to `env`.
+pub fn expand(env: &Rc<Env>, expr: &LispVal) -> Result<LispVal, LispError> {
+    Expander::new(env).expand(expr, &None)
+}
+
+/// The state of one expansion. Fresh names are numbered from one again for
+/// every top-level form, so expanding the same code twice reuses the same
+/// symbols. That is safe because the names only ever bind local variables,
+/// and each form's locals are out of reach of every other form's code.
+struct Expander<'a> {
+    global: &'a Rc<Env>,
+    next_id: Cell<usize>,
+    aliases: RefCell<HashMap<Symbol, Alias>>,
+    /// The scopes macros were bound in. Such a macro is closed over the scope
+    /// that holds it, so the scope is emptied once expansion is over.
+    macro_scopes: RefCell<Vec<Rc<Scope>>>,
+}
+
+impl Drop for Expander<'_> {
+    fn drop(&mut self) {
+        for scope in self.macro_scopes.take() {
+            scope.bindings.borrow_mut().clear();
+        }
+    }
+}
+
+/// A pattern variable's match: one datum, or one match per repetition of the
+/// ellipsis that follows it.
+#[derive(Clone)]
+enum Match {
+    One(LispVal),
+    Many(Vec<Match>),
+}
+
+type Matches = HashMap<Symbol, Match>;
+
+enum BodyForm {
+    Define(Symbol, LispVal),
+    Expr(LispVal),
+}
+
+impl<'a> Expander<'a> {
+    fn new(global: &'a Rc<Env>) -> Expander<'a> {
+        Expander {
+            global,
+            next_id: Cell::new(0),
+            aliases: RefCell::new(HashMap::new()),
+            macro_scopes: RefCell::new(vec![]),
+        }
+    }
+
+    /// Makes a name that cannot clash with any other identifier in the form
+    /// being expanded. The space keeps it from ever being read back from
+    /// source text without `|...|`.
+    fn fresh_name(&self, id: Symbol) -> Symbol {
+        self.next_id.set(self.next_id.get() + 1);
+        Symbol::intern(&format!("{} {}", self.strip_name(id), self.next_id.get()))
+    }
+
+    fn strip_name(&self, id: Symbol) -> Symbol {
+        let aliases = self.aliases.borrow();
+        let mut id = id;
+        while let Some(alias) = aliases.get(&id) {
+            id = alias.name;
+        }
+        id
+    }
+
+    /// Replaces the identifiers a macro inserted in a datum with the symbols
+    /// they were written as, for `quote` and other places where code becomes
+    /// data.
+    fn strip(&self, datum: &LispVal) -> LispVal {
+        match datum {
+            LispVal::Atom(id) => LispVal::Atom(self.strip_name(*id)),
+            LispVal::Pair(pair) => LispVal::cons(self.strip(&pair.car()), self.strip(&pair.cdr())),
+            LispVal::Vector(items) => {
+                LispVal::vector(items.borrow().iter().map(|item| self.strip(item)).collect())
+            }
+            _ => datum.clone(),
+        }
+    }
+
+    /// Every alias in `datum`, with the name it stands for.
+    fn collect_aliases(&self, datum: &LispVal, out: &mut HashMap<Symbol, Symbol>) {
+        match datum {
+            LispVal::Atom(id) if self.aliases.borrow().contains_key(id) => {
+                out.insert(*id, self.strip_name(*id));
+            }
+            LispVal::Pair(pair) => {
+                self.collect_aliases(&pair.car(), out);
+                self.collect_aliases(&pair.cdr(), out);
+            }
+            LispVal::Vector(items) => {
+                for item in items.borrow().iter() {
+                    self.collect_aliases(item, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn bind_macro(&self, scope: &Rc<Scope>, id: Symbol, m: Macro) {
+        scope.bind(id, Binding::Macro(gc::alloc(m)));
+        let mut scopes = self.macro_scopes.borrow_mut();
+        if !scopes.iter().any(|other| Rc::ptr_eq(other, scope)) {
+            scopes.push(scope.clone());
+        }
+    }
+
+    fn resolve(&self, id: Symbol, scope: &Option<Rc<Scope>>) -> Resolved {
+        let mut frame = scope.clone();
+        while let Some(current) = frame {
+            match current.bindings.borrow().get(&id) {
+                Some(Binding::Variable(name)) => return Resolved::Local(*name),
+                Some(Binding::Macro(m)) => return Resolved::Macro(m.clone()),
+                None => {}
+            }
+            frame = current.parent.clone();
+        }
+        let alias = self
+            .aliases
+            .borrow()
+            .get(&id)
+            .map(|alias| (alias.name, alias.scope.clone()));
+        if let Some((name, scope)) = alias {
+            return self.resolve(name, &scope);
+        }
+        if let Some(m) = self.global.get_macro(id) {
+            return Resolved::Macro(m);
+        }
+        match SPECIAL_FORMS.iter().find(|keyword| id == **keyword) {
+            Some(keyword) => Resolved::Special(keyword),
+            None => Resolved::Global(id),
+        }
+    }
+
+    /// Resolves the keyword of a form, if it starts with an identifier.
+    fn resolve_head(&self, expr: &LispVal, scope: &Option<Rc<Scope>>) -> Option<Resolved> {
+        match expr {
+            LispVal::Pair(pair) => match pair.car() {
+                LispVal::Atom(id) => Some(self.resolve(id, scope)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn is_special(&self, expr: &LispVal, keyword: &str, scope: &Option<Rc<Scope>>) -> bool {
+        match expr {
+            LispVal::Atom(id) => {
+                matches!(self.resolve(*id, scope), Resolved::Special(k) if k == keyword)
+            }
+            _ => false,
+        }
+    }
+
+    fn expand(&self, expr: &LispVal, scope: &Option<Rc<Scope>>) -> Result<LispVal, LispError> {
+        match expr {
+            LispVal::Atom(id) => match self.resolve(*id, scope) {
+                Resolved::Local(name) | Resolved::Global(name) => Ok(LispVal::Atom(name)),
+                Resolved::Special(keyword) => Ok(atom(keyword)),
+                Resolved::Macro(_) => Err(bad_form("Syntax keyword used as a variable", expr)),
+            },
+            LispVal::Pair(_) => {
+                if let Some(Resolved::Macro(m)) = self.resolve_head(expr, scope) {
+                    let expansion = self.transcribe(&m, expr, scope)?;
+                    return self.expand(&expansion, scope);
+                }
+                let items = match expr.list_to_vec() {
+                    Some(items) => items,
+                    None => return Ok(self.strip(expr)),
+                };
+                if let Some(Resolved::Special(keyword)) = self.resolve_head(expr, scope) {
+                    return self.expand_special(keyword, expr, &items[1..], scope);
+                }
+                Ok(LispVal::list(self.expand_all(&items, scope)?))
+            }
+            _ => Ok(self.strip(expr)),
+        }
+    }
+
+    fn expand_all(
+        &self,
+        exprs: &[LispVal],
+        scope: &Option<Rc<Scope>>,
+    ) -> Result<Vec<LispVal>, LispError> {
+        exprs.iter().map(|expr| self.expand(expr, scope)).collect()
+    }
+
+    fn expand_special(
+        &self,
+        keyword: &'static str,
+        expr: &LispVal,
+        args: &[LispVal],
+        scope: &Option<Rc<Scope>>,
+    ) -> Result<LispVal, LispError> {
+        let mut out = vec![atom(keyword)];
+        match keyword {
+            "quote" => out.extend(args.iter().map(|arg| self.strip(arg))),
+            "quasiquote" => match args {
+                [template] => return self.expand_quasiquote(template, 1, scope),
+                _ => return Err(bad_form("Malformed quasiquote", expr)),
+            },
+            "lambda" => match args {
+                [formals, body @ ..] => {
+                    let (inner, formals) = self.bind_formals(expr, formals, scope)?;
+                    out.push(formals);
+                    out.extend(self.expand_body(body, &inner)?);
+                }
+                _ => return Err(bad_form("Malformed lambda", expr)),
+            },
+            "define" if scope.is_none() => {
+                let (id, value) = normalize_define(expr)?;
+                out.push(LispVal::Atom(self.strip_name(id)));
+                out.push(self.expand(&value, scope)?);
+            }
+            "define" => return Err(bad_form("Definition in expression context", expr)),
+            "set!" => match args {
+                [LispVal::Atom(id), value] => match self.resolve(*id, scope) {
+                    Resolved::Local(name) | Resolved::Global(name) => {
+                        out.push(LispVal::Atom(name));
+                        out.push(self.expand(value, scope)?);
+                    }
+                    _ => return Err(bad_form("Cannot assign to a syntax keyword", expr)),
+                },
+                _ => return Err(bad_form("Malformed set!", expr)),
+            },
+            "let" => return self.expand_let(expr, args, scope),
+            "cond" => {
+                for clause in args {
+                    match clause.list_to_vec() {
+                        Some(items) if !items.is_empty() => {
+                            let mut clause = vec![];
+                            if self.is_special(&items[0], "else", scope) {
+                                clause.push(atom("else"));
+                            } else {
+                                clause.push(self.expand(&items[0], scope)?);
+                            }
+                            clause.extend(self.expand_all(&items[1..], scope)?);
+                            out.push(LispVal::list(clause));
+                        }
+                        _ => return Err(bad_form("Malformed cond clause", clause)),
+                    }
+                }
+            }
+            "case" => match args.split_first() {
+                Some((key, clauses)) => {
+                    out.push(self.expand(key, scope)?);
+                    for clause in clauses {
+                        match clause.list_to_vec() {
+                            Some(items) if !items.is_empty() => {
+                                let mut clause = vec![];
+                                if self.is_special(&items[0], "else", scope) {
+                                    clause.push(atom("else"));
+                                } else {
+                                    clause.push(self.strip(&items[0]));
+                                }
+                                clause.extend(self.expand_all(&items[1..], scope)?);
+                                out.push(LispVal::list(clause));
+                            }
+                            _ => return Err(bad_form("Malformed case clause", clause)),
+                        }
+                    }
+                }
+                None => return Err(bad_form("Malformed case", expr)),
+            },
+            "define-syntax" if scope.is_none() => match args {
+                [LispVal::Atom(id), spec] => {
+                    let mut m = self.make_macro(spec, scope)?;
+                    self.collect_aliases(spec, &mut m.aliases);
+                    self.global
+                        .define(self.strip_name(*id), LispVal::Macro(gc::alloc(m)));
+                    return Ok(LispVal::Unspecified);
+                }
+                _ => return Err(bad_form("Malformed define-syntax", expr)),
+            },
+            "define-syntax" => return Err(bad_form("Definition in expression context", expr)),
+            "let-syntax" | "letrec-syntax" => match args {
+                [bindings, body @ ..] if !body.is_empty() => {
+                    let bindings = bindings
+                        .list_to_vec()
+                        .ok_or_else(|| bad_form("Malformed let-syntax", expr))?;
+                    let inner = Scope::extend(scope);
+                    let recursive = Some(inner.clone());
+                    let spec_scope = if keyword == "letrec-syntax" {
+                        &recursive
+                    } else {
+                        scope
+                    };
+                    let mut macros = vec![];
+                    for binding in &bindings {
+                        match binding.list_to_vec().as_deref() {
+                            Some([LispVal::Atom(id), spec]) => {
+                                macros.push((*id, self.make_macro(spec, spec_scope)?))
+                            }
+                            _ => return Err(bad_form("Malformed syntax binding", expr)),
+                        }
+                    }
+                    for (id, m) in macros {
+                        self.bind_macro(&inner, id, m);
+                    }
+                    out[0] = atom("let");
+                    out.push(LispVal::Nil);
+                    out.extend(self.expand_body(body, &inner)?);
+                }
+                _ => return Err(bad_form("Malformed let-syntax", expr)),
+            },
+            "syntax-rules" => return Err(bad_form("syntax-rules outside of a macro", expr)),
+            "else" | "=>" | "..." | "_" | "unquote" | "unquote-splicing" => {
+                return Err(bad_form("Misplaced syntax", expr))
+            }
+            _ => out.extend(self.expand_all(args, scope)?),
+        }
+        Ok(LispVal::list(out))
+    }
+
+    /// Expands the template of a quasiquote nested `depth` levels deep into
+    /// code that builds it with `cons`, `append` and `list->vector`. Only the
+    /// unquotes at depth one are evaluated; deeper ones are kept as data,
+    /// with their own quasiquotes and unquotes.
+    fn expand_quasiquote(
+        &self,
+        template: &LispVal,
+        depth: usize,
+        scope: &Option<Rc<Scope>>,
+    ) -> Result<LispVal, LispError> {
+        if let Some((keyword, operand)) = self.quasiquote_form(template, scope) {
+            let depth = match keyword {
+                "quasiquote" => depth + 1,
+                _ if depth > 1 => depth - 1,
+                "unquote" => return self.expand(&operand, scope),
+                _ => return Err(bad_form("unquote-splicing outside of a list", template)),
+            };
+            // The operand is expanded as the rest of a list, so that a
+            // nested unquote-splicing can splice into it.
+            let rest = self.expand_quasiquote(&LispVal::list(vec![operand]), depth, scope)?;
+            return Ok(quasi_cons(quasi_quote(atom(keyword)), rest));
+        }
+        match template {
+            LispVal::Pair(pair) => {
+                let rest = self.expand_quasiquote(&pair.cdr(), depth, scope)?;
+                match self.quasiquote_form(&pair.car(), scope) {
+                    Some(("unquote-splicing", operand)) if depth == 1 => Ok(LispVal::list(vec![
+                        atom("append"),
+                        self.expand(&operand, scope)?,
+                        rest,
+                    ])),
+                    _ => Ok(quasi_cons(
+                        self.expand_quasiquote(&pair.car(), depth, scope)?,
+                        rest,
+                    )),
+                }
+            }
+            LispVal::Vector(items) => {
+                let items = LispVal::list(items.borrow().clone());
+                match self.expand_quasiquote(&items, depth, scope)? {
+                    code if quoted(&code).is_some() => Ok(quasi_quote(self.strip(template))),
+                    code => Ok(LispVal::list(vec![atom("list->vector"), code])),
+                }
+            }
+            _ => Ok(quasi_quote(self.strip(template))),
+        }
+    }
+
+    /// Splits a `(quasiquote x)`, `(unquote x)` or `(unquote-splicing x)`
+    /// form into its keyword and `x`.
+    fn quasiquote_form(
+        &self,
+        form: &LispVal,
+        scope: &Option<Rc<Scope>>,
+    ) -> Option<(&'static str, LispVal)> {
+        match (
+            self.resolve_head(form, scope)?,
+            form.list_to_vec()?.as_slice(),
+        ) {
+            (Resolved::Special(keyword), [_, operand])
+                if matches!(keyword, "quasiquote" | "unquote" | "unquote-splicing") =>
+            {
+                Some((keyword, operand.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn bind_formals(
+        &self,
+        expr: &LispVal,
+        formals: &LispVal,
+        scope: &Option<Rc<Scope>>,
+    ) -> Result<(Rc<Scope>, LispVal), LispError> {
+        let inner = Scope::extend(scope);
+        let bind = |param: &LispVal| match param {
+            LispVal::Atom(id) => {
+                let name = self.fresh_name(*id);
+                inner.bind(*id, Binding::Variable(name));
+                Ok(LispVal::Atom(name))
+            }
+            _ => Err(bad_form("Invalid parameter", expr)),
+        };
+        let formals = match split_list(formals) {
+            Some((params, rest)) => make_list(
+                params.iter().map(bind).collect::<Result<_, _>>()?,
+                rest.as_ref().map(bind).transpose()?,
+            ),
+            None => bind(formals)?,
+        };
+        Ok((inner, formals))
+    }
+
+    fn expand_let(
+        &self,
+        expr: &LispVal,
+        args: &[LispVal],
+        scope: &Option<Rc<Scope>>,
+    ) -> Result<LispVal, LispError> {
+        let (name, bindings, body) = match args {
+            [LispVal::Atom(name), bindings, body @ ..] => (Some(name), bindings, body),
+            [bindings, body @ ..] => (None, bindings, body),
+            _ => return Err(bad_form("Malformed let", expr)),
+        };
+        let bindings = bindings
+            .list_to_vec()
+            .ok_or_else(|| bad_form("Malformed let", expr))?;
+        let mut params = vec![];
+        let mut inits = vec![];
+        for binding in &bindings {
+            match binding.list_to_vec().as_deref() {
+                Some([param, init]) => {
+                    params.push(param.clone());
+                    inits.push(self.expand(init, scope)?);
+                }
+                _ => return Err(bad_form("Malformed let binding", binding)),
+            }
+        }
+        let mut out = vec![atom("let")];
+        let outer = match name {
+            Some(id) => {
+                let loop_scope = Scope::extend(scope);
+                let name = self.fresh_name(*id);
+                loop_scope.bind(*id, Binding::Variable(name));
+                out.push(LispVal::Atom(name));
+                Some(loop_scope)
+            }
+            None => scope.clone(),
+        };
+        let (inner, params) = self.bind_formals(expr, &LispVal::list(params), &outer)?;
+        let bindings = params
+            .iter()
+            .zip(inits)
+            .map(|(param, init)| LispVal::list(vec![param, init]))
+            .collect();
+        out.push(bindings);
+        out.extend(self.expand_body(body, &inner)?);
+        Ok(LispVal::list(out))
+    }
+
+    /// Expands a body, in which definitions (including those produced by
+    /// macros or spliced in from `begin`) bind variables and macros in
+    /// `scope` for the whole body.
+    fn expand_body(&self, body: &[LispVal], scope: &Rc<Scope>) -> Result<Vec<LispVal>, LispError> {
+        let outer = Some(scope.clone());
+        let mut queue: VecDeque<LispVal> = body.iter().cloned().collect();
+        let mut forms = vec![];
+        while let Some(mut form) = queue.pop_front() {
+            while let Some(Resolved::Macro(m)) = self.resolve_head(&form, &outer) {
+                form = self.transcribe(&m, &form, &outer)?;
+            }
+            match self.resolve_head(&form, &outer) {
+                Some(Resolved::Special("begin")) => match form.list_to_vec() {
+                    Some(items) => {
+                        for item in items.into_iter().skip(1).rev() {
+                            queue.push_front(item);
+                        }
+                    }
+                    None => return Err(bad_form("Malformed begin", &form)),
+                },
+                Some(Resolved::Special("define")) => {
+                    let (id, value) = normalize_define(&form)?;
+                    let name = self.fresh_name(id);
+                    scope.bind(id, Binding::Variable(name));
+                    forms.push(BodyForm::Define(name, value));
+                }
+                Some(Resolved::Special("define-syntax")) => match form.list_to_vec().as_deref() {
+                    Some([_, LispVal::Atom(id), spec]) => {
+                        let m = self.make_macro(spec, &outer)?;
+                        self.bind_macro(scope, *id, m);
+                    }
+                    _ => return Err(bad_form("Malformed define-syntax", &form)),
+                },
+                _ => forms.push(BodyForm::Expr(form)),
+            }
+        }
+        forms
+            .into_iter()
+            .map(|form| match form {
+                BodyForm::Define(name, value) => Ok(LispVal::list(vec![
+                    atom("define"),
+                    LispVal::Atom(name),
+                    self.expand(&value, &outer)?,
+                ])),
+                BodyForm::Expr(expr) => self.expand(&expr, &outer),
+            })
+            .collect()
+    }
+
+    fn make_macro(&self, spec: &LispVal, scope: &Option<Rc<Scope>>) -> Result<Macro, LispError> {
+        let items = match spec.list_to_vec() {
+            Some(items) if !items.is_empty() => items,
+            _ => return Err(bad_form("Expected syntax-rules", spec)),
+        };
+        if !self.is_special(&items[0], "syntax-rules", scope) {
+            return Err(bad_form("Expected syntax-rules", spec));
+        }
+        let (ellipsis, literals, rules) = match &items[1..] {
+            [LispVal::Atom(ellipsis), literals, rules @ ..] => (*ellipsis, literals, rules),
+            [literals, rules @ ..] => (Symbol::intern("..."), literals, rules),
+            _ => return Err(bad_form("Malformed syntax-rules", spec)),
+        };
+        let literals = literals
+            .list_to_vec()
+            .ok_or_else(|| bad_form("Malformed syntax-rules", spec))?
+            .iter()
+            .map(|literal| match literal {
+                LispVal::Atom(id) => Ok(*id),
+                _ => Err(bad_form("Malformed syntax-rules literal", spec)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let rules = rules
+            .iter()
+            .map(|rule| match rule.list_to_vec().as_deref() {
+                Some([pattern @ LispVal::Pair(_), template]) if split_list(pattern).is_some() => {
+                    Ok((pattern.clone(), template.clone()))
+                }
+                _ => Err(bad_form("Malformed syntax rule", rule)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // An ellipsis listed as a literal is matched literally instead.
+        let ellipsis = if literals.contains(&ellipsis) {
+            None
+        } else {
+            Some(ellipsis)
+        };
+        Ok(Macro {
+            ellipsis,
+            literals,
+            rules,
+            scope: scope.clone(),
+            aliases: HashMap::new(),
+        })
+    }
+
+    /// Rewrites a use of `m` according to the first rule whose pattern it
+    /// matches.
+    fn transcribe(
+        &self,
+        m: &Macro,
+        form: &LispVal,
+        scope: &Option<Rc<Scope>>,
+    ) -> Result<LispVal, LispError> {
+        let (args, tail) = split_list(form).unwrap();
+        for (pattern, template) in &m.rules {
+            // The keyword position is ignored.
+            let (patterns, pattern_tail) = split_list(pattern).unwrap();
+            let (patterns, args) = match (patterns.split_first(), args.split_first()) {
+                (Some((_, patterns)), Some((_, args))) => (patterns, args),
+                _ => continue,
+            };
+            let mut matches = Matches::new();
+            if self.match_seq(
+                m,
+                patterns,
+                pattern_tail.as_ref(),
+                args,
+                tail.as_ref(),
+                scope,
+                &mut matches,
+            ) {
+                let mut renames = HashMap::new();
+                let transcriber = Transcriber {
+                    expander: self,
+                    m,
+                    ellipsis: m.ellipsis,
+                };
+                return transcriber.instantiate(template, &matches, &mut renames);
+            }
+        }
+        Err(bad_form("No syntax rule matches", &self.strip(form)))
+    }
+
+    /// Whether `id`, written in one of `m`'s rules, stands for `keyword`.
+    /// An identifier a macro inserted into the rules is an alias, so it is
+    /// resolved where `m` was defined rather than compared by name.
+    fn names_keyword(&self, m: &Macro, id: Symbol, keyword: &str) -> bool {
+        id == keyword
+            || matches!(self.resolve(m.unalias(id), &m.scope), Resolved::Special(k) if k == keyword)
+    }
+
+    fn is_ellipsis(&self, m: &Macro, pattern: &LispVal) -> bool {
+        match (pattern, m.ellipsis) {
+            (LispVal::Atom(id), Some(e)) if e == "..." => self.names_keyword(m, *id, "..."),
+            (LispVal::Atom(id), Some(e)) => *id == e,
+            _ => false,
+        }
+    }
+
+    fn match_pattern(
+        &self,
+        m: &Macro,
+        pattern: &LispVal,
+        form: &LispVal,
+        scope: &Option<Rc<Scope>>,
+        matches: &mut Matches,
+    ) -> bool {
+        match pattern {
+            LispVal::Atom(id) if m.literals.contains(id) => match form {
+                LispVal::Atom(other) => {
+                    self.resolve(m.unalias(*id), &m.scope) == self.resolve(*other, scope)
+                }
+                _ => false,
+            },
+            LispVal::Atom(id) if self.names_keyword(m, *id, "_") => true,
+            LispVal::Atom(id) => {
+                matches.insert(*id, Match::One(form.clone()));
+                true
+            }
+            LispVal::Pair(_) | LispVal::Nil => {
+                let (patterns, pattern_tail) = match split_list(pattern) {
+                    Some(split) => split,
+                    None => return false,
+                };
+                let pattern_tail = pattern_tail.as_ref();
+                match split_list(form) {
+                    Some((items, tail)) => self.match_seq(
+                        m,
+                        &patterns,
+                        pattern_tail,
+                        &items,
+                        tail.as_ref(),
+                        scope,
+                        matches,
+                    ),
+                    // A pattern like `(a ... . rest)` can match a non-list
+                    // through its tail.
+                    None => {
+                        self.match_seq(m, &patterns, pattern_tail, &[], Some(form), scope, matches)
+                    }
+                }
+            }
+            LispVal::Vector(patterns) => match form {
+                LispVal::Vector(items) => {
+                    let (patterns, items) = (patterns.borrow(), items.borrow());
+                    self.match_seq(m, &patterns, None, &items, None, scope, matches)
+                }
+                _ => false,
+            },
+            _ => *pattern == self.strip(form),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn match_seq(
+        &self,
+        m: &Macro,
+        patterns: &[LispVal],
+        pattern_tail: Option<&LispVal>,
+        items: &[LispVal],
+        tail: Option<&LispVal>,
+        scope: &Option<Rc<Scope>>,
+        matches: &mut Matches,
+    ) -> bool {
+        let ellipsis = patterns
+            .iter()
+            .position(|pattern| self.is_ellipsis(m, pattern));
+        let (before, repeated, after) = match ellipsis {
+            Some(0) => return false,
+            Some(i) => (
+                &patterns[..i - 1],
+                Some(&patterns[i - 1]),
+                &patterns[i + 1..],
+            ),
+            None => (patterns, None, &patterns[patterns.len()..]),
+        };
+        let fixed = before.len() + after.len();
+        let count = match (repeated, pattern_tail) {
+            _ if items.len() < fixed => return false,
+            (Some(_), _) => items.len() - fixed,
+            (None, None) if items.len() > fixed => return false,
+            (None, _) => 0,
+        };
+        let (first, rest) = items.split_at(before.len());
+        let (middle, last) = rest.split_at(count);
+        let (last, spare) = last.split_at(after.len());
+        let fixed_match = before
+            .iter()
+            .chain(after)
+            .zip(first.iter().chain(last))
+            .all(|(pattern, item)| self.match_pattern(m, pattern, item, scope, matches));
+        if !fixed_match {
+            return false;
+        }
+        if let Some(repeated) = repeated {
+            let mut each = vec![];
+            for item in middle {
+                let mut item_matches = Matches::new();
+                if !self.match_pattern(m, repeated, item, scope, &mut item_matches) {
+                    return false;
+                }
+                each.push(item_matches);
+            }
+            for var in self.pattern_vars(m, repeated) {
+                let seq = each
+                    .iter_mut()
+                    .map(|item_matches| item_matches.remove(&var).unwrap())
+                    .collect();
+                matches.insert(var, Match::Many(seq));
+            }
+        }
+        match pattern_tail {
+            Some(pattern_tail) => {
+                let rest = make_list(spare.to_vec(), tail.cloned());
+                self.match_pattern(m, pattern_tail, &rest, scope, matches)
+            }
+            None => tail.is_none(),
+        }
+    }
+
+    fn pattern_vars(&self, m: &Macro, pattern: &LispVal) -> Vec<Symbol> {
+        match pattern {
+            LispVal::Atom(id)
+                if m.literals.contains(id)
+                    || self.names_keyword(m, *id, "_")
+                    || self.is_ellipsis(m, pattern) =>
+            {
+                vec![]
+            }
+            LispVal::Atom(id) => vec![*id],
+            LispVal::Pair(pair) => {
+                let mut vars = self.pattern_vars(m, &pair.car());
+                vars.extend(self.pattern_vars(m, &pair.cdr()));
+                vars
+            }
+            LispVal::Vector(items) => items
+                .borrow()
+                .iter()
+                .flat_map(|item| self.pattern_vars(m, item))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Fills in a template. `ellipsis` is `None` inside `(... template)`, where
+/// the ellipsis stands for itself.
+struct Transcriber<'a> {
+    expander: &'a Expander<'a>,
+    m: &'a Macro,
+    ellipsis: Option<Symbol>,
+}
+
+impl Transcriber<'_> {
+    fn is_ellipsis(&self, template: &LispVal) -> bool {
+        self.ellipsis.is_some() && self.expander.is_ellipsis(self.m, template)
+    }
+
+    fn instantiate(
+        &self,
+        template: &LispVal,
+        matches: &Matches,
+        renames: &mut HashMap<Symbol, Symbol>,
+    ) -> Result<LispVal, LispError> {
+        match template {
+            LispVal::Atom(id) => match matches.get(id) {
+                Some(Match::One(value)) => Ok(value.clone()),
+                Some(Match::Many(_)) => Err(bad_form(
+                    "Pattern variable used without an ellipsis",
+                    template,
+                )),
+                None => Ok(LispVal::Atom(self.rename(*id, renames))),
+            },
+            LispVal::Pair(_) => {
+                let (items, tail) = match split_list(template) {
+                    Some(split) => split,
+                    None => return Err(bad_form("Circular template", template)),
+                };
+                match (items.as_slice(), &tail) {
+                    ([escape, inner], None) if self.is_ellipsis(escape) => {
+                        let literal = Transcriber {
+                            expander: self.expander,
+                            m: self.m,
+                            ellipsis: None,
+                        };
+                        literal.instantiate(inner, matches, renames)
+                    }
+                    _ => {
+                        let items = self.instantiate_seq(&items, matches, renames)?;
+                        let tail = match tail {
+                            Some(tail) => Some(self.instantiate(&tail, matches, renames)?),
+                            None => None,
+                        };
+                        Ok(make_list(items, tail))
+                    }
+                }
+            }
+            LispVal::Vector(items) => Ok(LispVal::vector(self.instantiate_seq(
+                &items.borrow(),
+                matches,
+                renames,
+            )?)),
+            _ => Ok(template.clone()),
+        }
+    }
+
+    fn instantiate_seq(
+        &self,
+        templates: &[LispVal],
+        matches: &Matches,
+        renames: &mut HashMap<Symbol, Symbol>,
+    ) -> Result<Vec<LispVal>, LispError> {
+        let mut out = vec![];
+        let mut i = 0;
+        while i < templates.len() {
+            let depth = templates[i + 1..]
+                .iter()
+                .take_while(|template| self.is_ellipsis(template))
+                .count();
+            if depth == 0 {
+                out.push(self.instantiate(&templates[i], matches, renames)?);
+            } else {
+                self.repeat(&templates[i], depth, matches, renames, &mut out)?;
+            }
+            i += 1 + depth;
+        }
+        Ok(out)
+    }
+
+    /// Instantiates a template followed by `depth` ellipses once for each
+    /// repetition of the pattern variables in it.
+    fn repeat(
+        &self,
+        template: &LispVal,
+        depth: usize,
+        matches: &Matches,
+        renames: &mut HashMap<Symbol, Symbol>,
+        out: &mut Vec<LispVal>,
+    ) -> Result<(), LispError> {
+        let mut vars = vec![];
+        self.template_vars(template, matches, &mut vars);
+        let mut count = None;
+        for var in &vars {
+            if let Some(Match::Many(seq)) = matches.get(var) {
+                match count {
+                    Some(n) if n != seq.len() => {
+                        return Err(bad_form("Ellipsis lengths do not match", template))
+                    }
+                    _ => count = Some(seq.len()),
+                }
+            }
+        }
+        let count = match count {
+            Some(count) => count,
+            None => return Err(bad_form("No pattern variable before ellipsis", template)),
+        };
+        for i in 0..count {
+            let mut inner = matches.clone();
+            for var in &vars {
+                if let Some(Match::Many(seq)) = matches.get(var) {
+                    inner.insert(*var, seq[i].clone());
+                }
+            }
+            if depth == 1 {
+                out.push(self.instantiate(template, &inner, renames)?);
+            } else {
+                self.repeat(template, depth - 1, &inner, renames, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn template_vars(&self, template: &LispVal, matches: &Matches, vars: &mut Vec<Symbol>) {
+        match template {
+            LispVal::Atom(id) if matches.contains_key(id) => vars.push(*id),
+            LispVal::Pair(pair) => {
+                self.template_vars(&pair.car(), matches, vars);
+                self.template_vars(&pair.cdr(), matches, vars);
+            }
+            LispVal::Vector(items) => {
+                for item in items.borrow().iter() {
+                    self.template_vars(item, matches, vars);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Gives an identifier inserted by the template a fresh alias, the same
+    /// one for every occurrence within a single expansion.
+    fn rename(&self, id: Symbol, renames: &mut HashMap<Symbol, Symbol>) -> Symbol {
+        *renames.entry(id).or_insert_with(|| {
+            let name = self.m.unalias(id);
+            let alias = self.expander.fresh_name(name);
+            self.expander.aliases.borrow_mut().insert(
+                alias,
+                Alias {
+                    name,
+                    scope: self.m.scope.clone(),
+                },
+            );
+            alias
+        })
+    }
+}
+
+/// Turns `(define (f . formals) body ...)` into the name `f` and the
+/// expression `(lambda formals body ...)`.
+fn normalize_define(expr: &LispVal) -> Result<(Symbol, LispVal), LispError> {
+    let items = match expr.list_to_vec() {
+        Some(items) => items,
+        None => return Err(bad_form("Malformed define", expr)),
+    };
+    match &items[1..] {
+        [LispVal::Atom(id), value] => Ok((*id, value.clone())),
+        [LispVal::Pair(signature), body @ ..] if !body.is_empty() => match signature.car() {
+            LispVal::Atom(id) => {
+                let lambda = LispVal::dotted_list(
+                    vec![atom("lambda"), signature.cdr()],
+                    LispVal::list(body.to_vec()),
+                );
+                Ok((id, lambda))
+            }
+            _ => Err(bad_form("Malformed define", expr)),
+        },
+        _ => Err(bad_form("Malformed define", expr)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::eval::eval;
+    use crate::expand::*;
+    use crate::interpreter::{Engine, Interpreter};
+    use crate::parser::int;
+    use crate::parser::parse_program;
+    use crate::primitives::primitive_env;
+
+    /// Evaluates every expression in `input`, returning the last value.
+    fn run(env: &Rc<Env>, input: &str) -> Result<LispVal, LispError> {
+        let mut result = LispVal::Unspecified;
+        for expr in parse_program(input).unwrap() {
+            result = eval(env, &expr)?;
+        }
+        Ok(result)
+    }
+
+    fn run_str(env: &Rc<Env>, input: &str) -> String {
+        run(env, input).unwrap().to_string()
+    }
+
+    #[test]
+    fn syntax_rules_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(
+            &env,
+            "(define-syntax swap!
+               (syntax-rules ()
+                 ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run_str(&env, "(let ((x 1) (y 2)) (swap! x y) (list x y))"),
+            "(2 1)"
+        );
+        run(
+            &env,
+            "(define-syntax my-if
+               (syntax-rules (then else)
+                 ((_ c then t else e) (cond (c t) (else e)))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(my-if #f then 1 else 2)"), "2");
+        assert!(run(&env, "(my-if #f 1 2)").is_err());
+        // Patterns may be dotted lists or vectors, and `_` matches anything.
+        run(
+            &env,
+            "(define-syntax parts
+               (syntax-rules ()
+                 ((_ #(a _ c)) '(a c))
+                 ((_ (a . b)) '(b))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(parts #(1 2 3))"), "(1 3)");
+        assert_eq!(run_str(&env, "(parts (1 2 3))"), "((2 3))");
+        assert_eq!(run_str(&env, "(parts (1 . 2))"), "(2)");
+    }
+
+    #[test]
+    fn hygiene_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        // The macro's `tmp` does not capture the user's.
+        run(
+            &env,
+            "(define-syntax swap!
+               (syntax-rules ()
+                 ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run_str(
+                &env,
+                "(let ((tmp 1) (other 2)) (swap! tmp other) (list tmp other))"
+            ),
+            "(2 1)"
+        );
+        run(
+            &env,
+            "(define-syntax my-or
+               (syntax-rules ()
+                 ((_) #f)
+                 ((_ e) e)
+                 ((_ e r ...) (let ((t e)) (if t t (my-or r ...))))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(let ((t 5)) (my-or #f t))"), "5");
+        // Local bindings of `if` and `list` do not change what the macro's
+        // `if` and `list` mean.
+        run(
+            &env,
+            "(define-syntax pair-if
+               (syntax-rules () ((_ c a b) (if c (list a b) (list b a)))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run_str(&env, "(let ((if #f) (list #f)) (pair-if #t 1 2))"),
+            "(1 2)"
+        );
+        // And a local variable named like a special form shadows it.
+        assert_eq!(run_str(&env, "(let ((if (lambda (x) x))) (if 7))"), "7");
+        // The macro's free identifiers refer to its definition, even when
+        // the use site binds the same names.
+        run(&env, "(define counter 0)").unwrap();
+        run(
+            &env,
+            "(define-syntax bump!
+               (syntax-rules () ((_) (set! counter (+ counter 1)))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run_str(&env, "(let ((counter 100)) (bump!) (bump!) counter)"),
+            "100"
+        );
+        assert_eq!(run_str(&env, "counter"), "2");
+        // Quoted identifiers inserted by a macro read as the symbols written.
+        run(
+            &env,
+            "(define-syntax quoted (syntax-rules () ((_ x) '(x inserted))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(quoted y)"), "(y inserted)");
+    }
+
+    #[test]
+    fn ellipsis_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(
+            &env,
+            "(define-syntax my-let*
+               (syntax-rules ()
+                 ((_ () body ...) (let () body ...))
+                 ((_ ((x v) rest ...) body ...)
+                  (let ((x v)) (my-let* (rest ...) body ...)))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run_str(&env, "(my-let* ((a 1) (b (+ a 1))) (list a b))"),
+            "(1 2)"
+        );
+        // Nested ellipses, and two ellipses in a row to flatten.
+        run(
+            &env,
+            "(define-syntax table
+               (syntax-rules ()
+                 ((_ (key value ...) ...)
+                  '((key (value ...)) ... (all value ... ...)))))",
+        )
+        .unwrap();
+        assert_eq!(
+            run_str(&env, "(table (a 1 2) (b) (c 3))"),
+            "((a (1 2)) (b ()) (c (3)) (all 1 2 3))"
+        );
+        // Items after the ellipsis and a dotted tail.
+        run(
+            &env,
+            "(define-syntax ends
+               (syntax-rules ()
+                 ((_ first middle ... last . rest) '(first last (middle ...) rest))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(ends 1 2 3 4)"), "(1 4 (2 3) ())");
+        assert_eq!(run_str(&env, "(ends 1 2 . 5)"), "(1 2 () 5)");
+        // A custom ellipsis leaves `...` as an ordinary identifier.
+        run(
+            &env,
+            "(define-syntax my-list
+               (syntax-rules ::: ()
+                 ((_ x :::) '(x ::: ...))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(my-list 1 2)"), "(1 2 ...)");
+        // `(... ...)` writes a literal ellipsis.
+        run(
+            &env,
+            "(define-syntax escaped
+               (syntax-rules ()
+                 ((_ x ...) '(x ... (... ...)))))",
+        )
+        .unwrap();
+        assert_eq!(run_str(&env, "(escaped 1 2)"), "(1 2 ...)");
+    }
+
+    #[test]
+    fn inserted_ellipsis_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        // From R7RS section 4.3.2: the `...` and `_` the outer template
+        // writes are the inner macro's ellipsis and wildcard.
+        run(
+            &env,
+            "(define-syntax be-like-begin
+               (syntax-rules ()
+                 ((be-like-begin name)
+                  (define-syntax name
+                    (syntax-rules ()
+                      ((name expr (... ...))
+                       (begin expr (... ...))))))))
+             (be-like-begin sequence)",
+        )
+        .unwrap();
+        assert_eq!(run(&env, "(sequence 1 2 3 4)").unwrap(), int(4));
+        run(
+            &env,
+            "(define-syntax define-second
+               (syntax-rules ()
+                 ((_ name)
+                  (define-syntax name
+                    (syntax-rules () ((_ _ x _ (... ...)) x))))))
+             (define-second second)",
+        )
+        .unwrap();
+        assert_eq!(run(&env, "(second 1 2 3 4)").unwrap(), int(2));
+        // The same inside a body, where the inner macro is local.
+        assert_eq!(
+            run_str(
+                &env,
+                "(let ()
+                   (define-syntax be-like-list
+                     (syntax-rules ()
+                       ((_ name)
+                        (define-syntax name
+                          (syntax-rules () ((_ x (... ...)) (list x (... ...))))))))
+                   (be-like-list my-list)
+                   (my-list 1 2 3))"
+            ),
+            "(1 2 3)"
+        );
+    }
+
+    #[test]
+    fn local_syntax_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        assert_eq!(
+            run_str(
+                &env,
+                "(let-syntax ((double (syntax-rules () ((_ x) (* 2 x)))))
+                   (double 21))"
+            ),
+            "42"
+        );
+        // `let-syntax` definitions see the outer binding of `double`;
+        // `letrec-syntax` ones see each other.
+        assert_eq!(
+            run_str(
+                &env,
+                "(let ((double (lambda (x) (+ x x x))))
+                   (let-syntax ((double (syntax-rules () ((_ x) (double x)))))
+                     (double 2)))"
+            ),
+            "6"
+        );
+        assert_eq!(
+            run_str(
+                &env,
+                "(letrec-syntax
+                     ((my-and (syntax-rules ()
+                                ((_) #t)
+                                ((_ e) e)
+                                ((_ e r ...) (if e (my-and r ...) #f)))))
+                   (my-and 1 2 3))"
+            ),
+            "3"
+        );
+        // Macros defined in a body, and definitions produced by macros. The
+        // `other` the macro defines is its own, not the body's.
+        assert_eq!(
+            run_str(
+                &env,
+                "(define (f x)
+                   (define-syntax def-twice
+                     (syntax-rules () ((_ name v) (begin (define name v) (define other v)))))
+                   (define other 1)
+                   (def-twice y (* x 2))
+                   (+ y other))
+                 (f 5)"
+            ),
+            "11"
+        );
+        assert!(run(&env, "(let-syntax ((m (syntax-rules () ((_) 1)))) (m 1))").is_err());
+        assert!(run(&env, "other").is_err());
+    }
+
+    #[test]
+    fn macro_defining_macro_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        run(
+            &env,
+            "(define-syntax define-getter
+               (syntax-rules ()
+                 ((_ name value)
+                  (define-syntax name
+                    (syntax-rules () ((_) (let ((v value)) v)))))))",
+        )
+        .unwrap();
+        run(&env, "(define-getter get-five 5)").unwrap();
+        assert_eq!(run(&env, "(let ((v 1)) (get-five))").unwrap(), int(5));
+        assert!(matches!(
+            run(&env, "get-five"),
+            Err(LispError::BadSpecialForm(..))
+        ));
+        // Redefining the name as a variable replaces the macro.
+        run(&env, "(define get-five (lambda () 6))").unwrap();
+        assert_eq!(run(&env, "(get-five)").unwrap(), int(6));
+    }
+
+    #[test]
+    fn quasiquote_test() {
+        let cases = [
+            ("`(list ,(+ 1 2) 4)", "(list 3 4)"),
+            ("(let ((name 'a)) `(list ,name ',name))", "(list a 'a)"),
+            ("`(a ,(+ 1 2) ,@(map abs '(4 -5 6)) b)", "(a 3 4 5 6 b)"),
+            (
+                "`((foo ,(- 10 3)) ,@(cdr '(c)) . ,(car '(cons)))",
+                "((foo 7) . cons)",
+            ),
+            ("`#(10 5 ,(* 2 1) ,@(map abs '(-4 3)) 8)", "#(10 5 2 4 3 8)"),
+            ("`(1 ,@'() . 2)", "(1 . 2)"),
+            ("`#(a b)", "#(a b)"),
+            // Only the unquotes as deep as the outermost quasiquote are
+            // evaluated.
+            (
+                "`(a `(b ,(+ 1 2) ,(foo ,(+ 1 3) d) e) f)",
+                "(a `(b ,(+ 1 2) ,(foo 4 d) e) f)",
+            ),
+            (
+                "(let ((name1 'x) (name2 'y)) `(a `(b ,,name1 ,',name2 d) e))",
+                "(a `(b ,x ,'y d) e)",
+            ),
+            ("`(1 ```,,@,,@(list (+ 1 2)) 4)", "(1 ```,,@,3 4)"),
+            ("(quasiquote (list (unquote (+ 1 2)) 4))", "(list 3 4)"),
+            // The procedures the expansion builds with are the global ones.
+            (
+                "(let ((cons 1) (append 2)) `(,cons ,@(list append)))",
+                "(1 2)",
+            ),
+        ];
+        for engine in [Engine::TreeWalking, Engine::Bytecode] {
+            let interp = Interpreter::with_engine(engine);
+            for (source, expected) in &cases {
+                assert_eq!(interp.eval_str(source).unwrap().to_string(), *expected);
+            }
+            assert!(interp.eval_str("`,@(list 1)").is_err());
+            assert!(interp.eval_str("(unquote 1)").is_err());
+            assert!(interp.eval_str("(quasiquote 1 2)").is_err());
+        }
+    }
+
+    #[test]
+    fn repeated_expansion_test() {
+        let interp = Interpreter::new();
+        let source = "(define (f) (let* ((a 1) (b 2)) (list a b)))
+                      (letrec-syntax ((count (syntax-rules ()
+                                               ((_) 0)
+                                               ((_ x y ...) (+ 1 (count y ...))))))
+                        (count a b c))
+                      (let () (define-syntax one (syntax-rules () ((_) 1))) (one))";
+        interp.eval_str(source).unwrap();
+        let symbols = Symbol::count();
+        for _ in 0..1000 {
+            interp.eval_str(source).unwrap();
+        }
+        assert_eq!(Symbol::count(), symbols);
+        assert_eq!(interp.eval_str("(f)").unwrap().to_string(), "(1 2)");
+    }
+
+    #[test]
+    fn macro_scope_release_test() {
+        let env = primitive_env(Engine::TreeWalking);
+        let expander = Expander::new(&env);
+        let expr = parse_program(
+            "(letrec-syntax ((ten (syntax-rules () ((_) 10)))
+                             (twice (syntax-rules () ((_) (* 2 (ten))))))
+               (twice))",
+        )
+        .unwrap()
+        .remove(0);
+        expander.expand(&expr, &None).unwrap();
+        let scopes = expander.macro_scopes.borrow().clone();
+        assert!(!scopes.is_empty());
+        drop(expander);
+        // Nothing but `scopes` still holds the scope the macros were bound in.
+        assert!(scopes.iter().all(|scope| Rc::strong_count(scope) == 1));
+    }
+}
@@ -0,0 +1,297 @@
+//! The garbage collector for cycles of Scheme objects.
+//!
+//! Scheme objects live behind `Rc`, which frees most of them as soon as the
+//! last reference goes away but leaks any that refer to each other in a
+//! cycle: a list made circular with `set-cdr!`, or a procedure stored in the
+//! environment it closes over. Every object that can hold other objects is
+//! allocated with [`alloc`], which registers it with the collector.
+//!
+//! A collection works out which registered objects are referenced from
+//! outside the heap, by subtracting the references the objects hold to each
+//! other from their reference counts. Those objects are the roots: values on
+//! the evaluator's stacks, in an `Interpreter`, or anywhere else in Rust code,
+//! so a `LispVal` held by the host stays valid however many collections run.
+//! Everything reachable from a root is marked, and the objects left unmarked
+//! are garbage held alive only by cycles. Sweeping clears their contents,
+//! which breaks the cycles and lets the reference counts free them.
+
+use crate::parser::LispVal;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// An object that holds references to other objects.
+pub trait Trace {
+    /// Passes every reference the object holds to `tracer`, each exactly once.
+    fn trace(&self, tracer: &mut Tracer);
+
+    /// Drops the references the object holds, once the collector has found
+    /// it to be garbage. Every cycle passes through an object that was
+    /// changed after it was made, so only objects with interior mutability
+    /// need to do anything here.
+    fn clear(&self) {}
+}
+
+/// Receives the references an object holds.
+pub struct Tracer<'a> {
+    visit: &'a mut dyn FnMut(*const ()),
+}
+
+impl Tracer<'_> {
+    pub fn rc<T: ?Sized>(&mut self, rc: &Rc<T>) {
+        (self.visit)(Rc::as_ptr(rc) as *const ());
+    }
+
+    pub fn value(&mut self, value: &LispVal) {
+        match value {
+            LispVal::Pair(pair) => self.rc(pair),
+            LispVal::Func(lambda) => self.rc(lambda),
+            LispVal::Continuation(k) => self.rc(k),
+            LispVal::Closure(closure) => self.rc(closure),
+            LispVal::VmContinuation(k) => self.rc(k),
+            LispVal::Vector(items) => self.rc(items),
+            LispVal::Record(record) => self.rc(record),
+            LispVal::Macro(m) => self.rc(m),
+            LispVal::Error(err) => self.rc(err),
+            LispVal::Values(values) => self.rc(values),
+            // A native procedure's closure cannot be looked into. The ones
+            // made by `define-record-type` only hold their record type, and
+            // any Scheme values a host closure holds stay alive with it.
+            _ => (),
+        }
+    }
+}
+
+impl Trace for RefCell<Vec<LispVal>> {
+    fn trace(&self, tracer: &mut Tracer) {
+        for item in self.borrow().iter() {
+            tracer.value(item);
+        }
+    }
+
+    fn clear(&self) {
+        drop(self.take());
+    }
+}
+
+impl Trace for Vec<LispVal> {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self {
+            tracer.value(value);
+        }
+    }
+}
+
+/// The counts reported by `(gc-stats)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Collections run so far.
+    pub collections: usize,
+    /// Objects allocated so far.
+    pub allocated: usize,
+    /// Objects freed by the collector, rather than by their reference counts
+    /// reaching zero.
+    pub collected: usize,
+    /// Objects still alive.
+    pub live: usize,
+}
+
+/// Allocations between collections, however few objects survive.
+const MIN_THRESHOLD: usize = 100_000;
+
+#[derive(Default)]
+struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    stats: Stats,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+    static UNTIL_COLLECTION: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+}
+
+/// Moves `value` into a new `Rc` known to the collector.
+pub fn alloc<T: Trace + 'static>(value: T) -> Rc<T> {
+    let rc = Rc::new(value);
+    let weak: Weak<dyn Trace> = Rc::downgrade(&rc) as Weak<dyn Trace>;
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.push(weak);
+        heap.stats.allocated += 1;
+    });
+    UNTIL_COLLECTION.with(|until| until.set(until.get().saturating_sub(1)));
+    rc
+}
+
+/// Collects garbage if enough has been allocated since the last collection.
+/// The evaluators call this between steps, when no object is borrowed.
+pub fn safe_point() {
+    if UNTIL_COLLECTION.with(Cell::get) == 0 {
+        collect();
+    }
+}
+
+pub fn stats() -> Stats {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        Stats {
+            live: heap.objects.iter().filter(|o| o.strong_count() > 0).count(),
+            ..heap.stats
+        }
+    })
+}
+
+/// Frees every object that is only kept alive by cycles, returning how many
+/// there were.
+pub fn collect() -> usize {
+    let objects: Vec<Rc<dyn Trace>> = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.retain(|o| o.strong_count() > 0);
+        heap.objects.iter().filter_map(Weak::upgrade).collect()
+    });
+    let index: HashMap<*const (), usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, o)| (Rc::as_ptr(o) as *const (), i))
+        .collect();
+
+    // What is left of an object's count once the references from other
+    // objects are taken away comes from outside the heap.
+    let mut external: Vec<usize> = objects.iter().map(|o| Rc::strong_count(o) - 1).collect();
+    for object in &objects {
+        object.trace(&mut Tracer {
+            visit: &mut |ptr| {
+                if let Some(&i) = index.get(&ptr) {
+                    external[i] -= 1;
+                }
+            },
+        });
+    }
+
+    let mut marked: Vec<bool> = external.iter().map(|&refs| refs > 0).collect();
+    let mut pending: Vec<usize> = (0..objects.len()).filter(|&i| marked[i]).collect();
+    let mut children: Vec<usize> = vec![];
+    while let Some(i) = pending.pop() {
+        objects[i].trace(&mut Tracer {
+            visit: &mut |ptr| children.extend(index.get(&ptr)),
+        });
+        for child in children.drain(..) {
+            if !marked[child] {
+                marked[child] = true;
+                pending.push(child);
+            }
+        }
+    }
+
+    let mut collected = 0;
+    for (object, marked) in objects.iter().zip(&marked) {
+        if !marked {
+            object.clear();
+            collected += 1;
+        }
+    }
+    let live = objects.len() - collected;
+    drop(objects);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.stats.collections += 1;
+        heap.stats.collected += collected;
+    });
+    UNTIL_COLLECTION.with(|until| until.set(live.max(MIN_THRESHOLD)));
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::gc::*;
+    use crate::interpreter::{Engine, Interpreter};
+    use crate::record::RecordType;
+    use crate::symbol::Symbol;
+
+    #[test]
+    fn cycle_test() {
+        let list = LispVal::list(vec![LispVal::Nil, LispVal::Nil]);
+        let (first, second) = match &list {
+            LispVal::Pair(first) => match first.cdr() {
+                LispVal::Pair(second) => (first.clone(), second),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        second.set_cdr(list.clone());
+        second.set_car(LispVal::vector(vec![list.clone()]));
+        let point = RecordType::new(Symbol::intern("<point>"), vec![Symbol::intern("x")]);
+        let record = point.make(vec![list.clone()]).unwrap();
+        first.set_car(record);
+        let weak = Rc::downgrade(&first);
+        drop((list, first, second));
+        assert!(weak.upgrade().is_some());
+        collect();
+        assert!(weak.upgrade().is_none());
+
+        // A procedure stored in the frame it closes over, under either engine.
+        for engine in [Engine::Bytecode, Engine::TreeWalking] {
+            let interp = Interpreter::with_engine(engine);
+            let weak: Weak<dyn Trace> = match interp.eval_str("((lambda () (define (g) g) g))") {
+                Ok(LispVal::Closure(closure)) => Rc::downgrade(&closure) as Weak<dyn Trace>,
+                Ok(LispVal::Func(lambda)) => Rc::downgrade(&lambda) as Weak<dyn Trace>,
+                other => panic!("{:?}", other),
+            };
+            collect();
+            assert!(weak.upgrade().is_none());
+        }
+
+        // Lists holding the error object they are an irritant of, and the
+        // multiple values they are one of.
+        let interp = Interpreter::new();
+        for source in [
+            "(let ((p (list 1)))
+               (guard (e (#t (set-car! p e) p))
+                 (error \"oops\" p)))",
+            "(let ((p (list 1))) (set-car! p (values p 2)) p)",
+        ] {
+            let weak = match interp.eval_str(source) {
+                Ok(LispVal::Pair(pair)) => Rc::downgrade(&pair),
+                other => panic!("{:?}", other),
+            };
+            collect();
+            assert!(weak.upgrade().is_none());
+        }
+    }
+
+    #[test]
+    fn rooted_test() {
+        let interp = Interpreter::new();
+        let value = interp
+            .eval_str(
+                "(define l (list 1 2 3))
+                 (set-cdr! (cdr (cdr l)) l)
+                 (define (make-counter)
+                   (define n 0)
+                   (define (next) (set! n (+ n 1)) n)
+                   next)
+                 (cons l (make-counter))",
+            )
+            .unwrap();
+        interp.eval_str("(set! l #f)").unwrap();
+        collect();
+        let (list, counter) = match &value {
+            LispVal::Pair(pair) => (pair.car(), pair.cdr()),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            list.iter()
+                .take(5)
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>(),
+            ["1", "2", "3", "1", "2"]
+        );
+        interp.define_global("counter", counter);
+        assert_eq!(
+            interp.eval_str("(counter) (counter)").unwrap().to_string(),
+            "2"
+        );
+    }
+}
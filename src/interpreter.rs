@@ -0,0 +1,351 @@
+use crate::convert::{FromLisp, IntoLisp};
+use crate::error::LispError;
+use crate::eval::{self, Env};
+use crate::parser::{parse_program, LispVal};
+use crate::primitives::primitive_env;
+use crate::symbol::Symbol;
+use crate::vm;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// A Scheme interpreter with its own global environment.
+///
+/// ```
+/// use scheme::{Interpreter, LispVal, Number};
+///
+/// let interp = Interpreter::new();
+/// interp.register_fn("square", |x: i64| x * x);
+/// let value = interp.eval_str("(square 12)").unwrap();
+/// assert_eq!(value, LispVal::Number(Number::Integer(144)));
+/// ```
+pub struct Interpreter {
+    env: Rc<Env>,
+    engine: Engine,
+}
+
+/// How an interpreter runs each top-level form once its macros are expanded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Engine {
+    /// Compiles the form to bytecode, with every local variable resolved to
+    /// a position in a frame, and runs it on a stack machine.
+    #[default]
+    Bytecode,
+    /// Evaluates the syntax tree directly, looking variables up by name.
+    TreeWalking,
+}
+
+impl Engine {
+    pub(crate) fn eval(self, env: &Rc<Env>, expr: &LispVal) -> Result<LispVal, LispError> {
+        match self {
+            Engine::Bytecode => vm::eval(env, expr),
+            Engine::TreeWalking => eval::eval(env, expr),
+        }
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter whose global environment holds the standard
+    /// procedures.
+    pub fn new() -> Interpreter {
+        Interpreter::with_engine(Engine::default())
+    }
+
+    /// Creates an interpreter that runs programs with `engine`. Procedures
+    /// from interpreters using different engines can call each other, but
+    /// continuations cannot be passed between them.
+    pub fn with_engine(engine: Engine) -> Interpreter {
+        Interpreter {
+            env: primitive_env(engine),
+            engine,
+        }
+    }
+
+    /// Evaluates a single datum, such as one returned by `parse_program`.
+    pub fn eval(&self, expr: &LispVal) -> Result<LispVal, LispError> {
+        self.engine.eval(&self.env, expr)
+    }
+
+    /// Evaluates every top-level form in `source` in order, returning the
+    /// value of the last one.
+    pub fn eval_str(&self, source: &str) -> Result<LispVal, LispError> {
+        let mut result = LispVal::Unspecified;
+        for expr in parse_program(source)? {
+            result = self.eval(&expr)?;
+        }
+        Ok(result)
+    }
+
+    /// Evaluates every top-level form in the file at `path`, stopping at the
+    /// first error.
+    pub fn eval_file<P: AsRef<Path>>(&self, path: P) -> Result<LispVal, LispError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|err| LispError::Default(format!("{}: {}", path.display(), err)))?;
+        self.eval_str(&source)
+    }
+
+    /// Binds `name` to `value` in the global environment, replacing any
+    /// existing binding.
+    pub fn define_global(&self, name: &str, value: LispVal) {
+        self.env.define(Symbol::intern(name), value);
+    }
+
+    /// Binds `name` to a procedure that calls `func`.
+    ///
+    /// `func` takes one parameter per argument, of any type implementing
+    /// `FromLisp`. Calling the procedure with the wrong number of arguments,
+    /// or with arguments that do not convert, fails before `func` runs. It
+    /// returns either a value implementing `IntoLisp` or a `Result` whose
+    /// error converts into a `LispError`; errors are raised as Scheme error
+    /// objects, so programs can catch them with `guard`.
+    pub fn register_fn<Args, F: IntoNativeFn<Args>>(&self, name: &str, func: F) {
+        let native = NativeFunc::new(name, F::ARITY, func.into_native());
+        self.define_global(name, LispVal::NativeFunc(Rc::new(native)));
+    }
+
+    /// Sets the arguments returned by `(command-line)` in this interpreter,
+    /// starting with the name of the script or program being run.
+    pub fn set_command_line(&self, args: Vec<String>) {
+        let args = args.into_iter().map(LispVal::string).collect();
+        self.define_global("%command-line", args);
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+type NativeFn = Box<dyn Fn(&[LispVal]) -> Result<LispVal, LispError>>;
+
+/// A procedure implemented by a Rust closure, either registered with
+/// [`Interpreter::register_fn`] or made by `define-record-type`.
+pub struct NativeFunc {
+    pub name: String,
+    arity: usize,
+    func: NativeFn,
+}
+
+impl NativeFunc {
+    pub(crate) fn new(name: &str, arity: usize, func: NativeFn) -> NativeFunc {
+        NativeFunc {
+            name: name.to_owned(),
+            arity,
+            func,
+        }
+    }
+
+    pub fn call(&self, args: Vec<LispVal>) -> Result<LispVal, LispError> {
+        if args.len() != self.arity {
+            return Err(LispError::NumArgs(self.arity, args));
+        }
+        (self.func)(&args)
+    }
+}
+
+impl PartialEq for NativeFunc {
+    fn eq(&self, other: &NativeFunc) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for NativeFunc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native {}>", self.name)
+    }
+}
+
+/// What a registered closure may return: a value that converts into a
+/// `LispVal`, or a `Result` whose error becomes a `LispError`.
+pub trait IntoLispResult {
+    fn into_lisp_result(self) -> Result<LispVal, LispError>;
+}
+
+impl<T: IntoLisp> IntoLispResult for T {
+    fn into_lisp_result(self) -> Result<LispVal, LispError> {
+        Ok(self.into_lisp())
+    }
+}
+
+impl<T: IntoLisp, E: Into<LispError>> IntoLispResult for Result<T, E> {
+    fn into_lisp_result(self) -> Result<LispVal, LispError> {
+        self.map(IntoLisp::into_lisp).map_err(Into::into)
+    }
+}
+
+/// A Rust closure that can be registered as a Scheme procedure. `Args` is a
+/// tuple of the closure's parameter types, which only serves to tell the
+/// implementations for each arity apart.
+pub trait IntoNativeFn<Args> {
+    const ARITY: usize;
+
+    fn into_native(self) -> NativeFn;
+}
+
+macro_rules! impl_into_native_fn {
+    ($arity:expr; $($arg:ident),*) => {
+        impl<Func, Ret, $($arg),*> IntoNativeFn<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + 'static,
+            Ret: IntoLispResult,
+            $($arg: FromLisp,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_native(self) -> NativeFn {
+                Box::new(move |args| {
+                    let mut args = args.iter();
+                    $(let $arg = $arg::from_lisp(args.next().expect("arity was checked"))?;)*
+                    self($($arg),*).into_lisp_result()
+                })
+            }
+        }
+    };
+}
+
+impl_into_native_fn!(0;);
+impl_into_native_fn!(1; A);
+impl_into_native_fn!(2; A, B);
+impl_into_native_fn!(3; A, B, C);
+impl_into_native_fn!(4; A, B, C, D);
+impl_into_native_fn!(5; A, B, C, D, E);
+impl_into_native_fn!(6; A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+
+    use crate::interpreter::*;
+    use crate::parser::int;
+
+    #[test]
+    fn eval_str_test() {
+        let interp = Interpreter::new();
+        assert_eq!(interp.eval_str("(define x 4) (* x x)").unwrap(), int(16));
+        assert_eq!(interp.eval_str("").unwrap(), LispVal::Unspecified);
+        assert!(matches!(
+            interp.eval_str("(+ 1").unwrap_err(),
+            LispError::Parser(_)
+        ));
+        interp.define_global("y", int(3));
+        assert_eq!(interp.eval_str("(+ x y)").unwrap(), int(7));
+    }
+
+    #[test]
+    fn engine_test() {
+        let source = "(define (f n) (let loop ((i 0) (acc '())) (if (= i n) acc (loop (+ i 1) (cons i acc))))) (f 3)";
+        for engine in [Engine::Bytecode, Engine::TreeWalking] {
+            let interp = Interpreter::with_engine(engine);
+            assert_eq!(interp.eval_str(source).unwrap().to_string(), "(2 1 0)");
+        }
+        // Procedures can be passed between interpreters using either engine.
+        let bytecode = Interpreter::with_engine(Engine::Bytecode);
+        let tree = Interpreter::with_engine(Engine::TreeWalking);
+        bytecode.eval_str("(define (twice f x) (f (f x)))").unwrap();
+        tree.define_global("twice", bytecode.eval_str("twice").unwrap());
+        assert_eq!(
+            tree.eval_str("(twice (lambda (x) (* x 3)) 2)").unwrap(),
+            int(18)
+        );
+    }
+
+    #[test]
+    fn case_test() {
+        // `case` matches keys with `eqv?`, so lists and strings only match
+        // themselves, never an equal datum.
+        let source = "(define (which x)
+                        (case x ((\"a\") 'string) (((1)) 'list) ((#\\a 2.5 b) 'atom) (else 'none)))
+                      (list (which \"a\") (which (list 1)) (which #\\a) (which 2.5) (which 'b))";
+        for engine in [Engine::Bytecode, Engine::TreeWalking] {
+            let interp = Interpreter::with_engine(engine);
+            assert_eq!(
+                interp.eval_str(source).unwrap().to_string(),
+                "(none none atom atom atom)"
+            );
+        }
+    }
+
+    #[test]
+    fn eval_file_test() {
+        let interp = Interpreter::new();
+        let path = std::env::temp_dir().join(format!("interp-load-{}.scm", std::process::id()));
+        fs::write(&path, "(define z 5)\n(+ z 1)\n").unwrap();
+        let result = interp.eval_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap(), int(6));
+        assert!(interp
+            .eval_file("/no/such/file.scm")
+            .unwrap_err()
+            .to_string()
+            .starts_with("/no/such/file.scm: "));
+    }
+
+    #[test]
+    fn register_fn_test() {
+        let interp = Interpreter::new();
+        interp.register_fn("answer", || int(42));
+        interp.register_fn("pick", |a: LispVal, b: LispVal, c: LispVal| {
+            LispVal::list(vec![c, b, a])
+        });
+        interp.register_fn("fail", |x: LispVal| -> Result<LispVal, String> {
+            Err(format!("bad {}", x))
+        });
+        assert_eq!(interp.eval_str("(answer)").unwrap(), int(42));
+        assert_eq!(
+            interp.eval_str("(pick 1 2 3)").unwrap(),
+            LispVal::list(vec![int(3), int(2), int(1)])
+        );
+        assert_eq!(
+            interp.eval_str("(pick 1 2)").unwrap_err(),
+            LispError::NumArgs(3, vec![int(1), int(2)])
+        );
+        assert_eq!(
+            interp.eval_str("(fail 1)").unwrap_err(),
+            LispError::User("bad 1".to_owned(), vec![])
+        );
+        assert_eq!(
+            interp
+                .eval_str("(guard (e (#t (error-object-message e))) (fail 2))")
+                .unwrap(),
+            LispVal::string("bad 2".to_owned())
+        );
+        assert_eq!(
+            interp.eval_str("answer").unwrap().to_string(),
+            "#<primitive answer>"
+        );
+    }
+
+    #[test]
+    fn typed_register_fn_test() {
+        let interp = Interpreter::new();
+        interp.register_fn("join", |words: Vec<String>, sep: char| {
+            words.join(&sep.to_string())
+        });
+        assert_eq!(
+            interp.eval_str(r#"(join '("a" "b") #\,)"#).unwrap(),
+            LispVal::string("a,b".to_owned())
+        );
+        assert_eq!(
+            interp.eval_str(r#"(join '(1) #\,)"#).unwrap_err(),
+            LispError::TypeMismatch("string", int(1))
+        );
+    }
+
+    #[test]
+    fn set_command_line_test() {
+        let interp = Interpreter::new();
+        let other = Interpreter::with_engine(Engine::TreeWalking);
+        assert_eq!(interp.eval_str("(command-line)").unwrap(), LispVal::Nil);
+        interp.set_command_line(vec!["run.scm".to_owned(), "-v".to_owned()]);
+        assert_eq!(
+            interp.eval_str("(command-line)").unwrap().to_string(),
+            r#"("run.scm" "-v")"#
+        );
+        // Other interpreters keep their own arguments.
+        assert_eq!(other.eval_str("(command-line)").unwrap(), LispVal::Nil);
+        assert!(interp.eval_str("(command-line 1)").is_err());
+    }
+}
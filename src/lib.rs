@@ -0,0 +1,51 @@
+//! A Scheme interpreter that can be embedded in Rust programs.
+//!
+//! An [`Interpreter`] owns a global environment. Programs are run with
+//! [`Interpreter::eval_str`] or [`Interpreter::eval_file`], and the host can
+//! add its own values with [`Interpreter::define_global`] and procedures with
+//! [`Interpreter::register_fn`]. Each form is compiled to bytecode for a
+//! stack machine unless [`Engine::TreeWalking`] asks for the syntax tree to
+//! be evaluated directly. The [`IntoLisp`] and [`FromLisp`] traits,
+//! and the derive macros of the same names, convert between Scheme values and
+//! Rust types. With the `serde` feature, [`sexp`] reads and writes Rust data
+//! as S-expressions. Records of types made by `define-record-type` can be
+//! made and inspected from Rust through [`RecordType`] and [`Record`].
+
+// Lets the code generated by the derive macros name this crate as `scheme`
+// from inside it too.
+extern crate self as scheme;
+
+mod analyze;
+mod compile;
+mod convert;
+mod error;
+mod eval;
+mod expand;
+mod gc;
+mod interpreter;
+mod number;
+mod pair;
+mod parser;
+mod port;
+mod primitives;
+mod printer;
+mod record;
+pub mod repl;
+pub mod script;
+#[cfg(feature = "serde")]
+pub mod sexp;
+mod symbol;
+mod vm;
+
+#[doc(hidden)]
+pub use crate::convert::assoc_field;
+pub use crate::convert::{FromLisp, IntoLisp};
+pub use crate::error::LispError;
+pub use crate::interpreter::{Engine, Interpreter, IntoLispResult, IntoNativeFn, NativeFunc};
+pub use crate::number::Number;
+pub use crate::pair::{ListIter, Pair};
+pub use crate::parser::{parse_lisp_expr, parse_program, LispVal, ParseError};
+pub use crate::port::Port;
+pub use crate::record::{Record, RecordType};
+pub use crate::symbol::Symbol;
+pub use scheme_derive::{FromLisp, IntoLisp};
@@ -1,10 +1,127 @@
-mod parser;
+use scheme::{repl, script, Interpreter, LispVal};
+use std::env;
+use std::io::{self, IsTerminal, Read};
+use std::process;
 
-use parser::parse_lisp_expr;
+const USAGE: &str = "\
+Usage: scheme [options] [file [args...]]
+
+With no file, starts an interactive REPL, or runs the program piped on stdin.
+Arguments after the file are available to the program via (command-line).
+
+Options:
+  -e <expr>  Evaluate <expr> and print its value
+  -          Read the program from stdin
+  -h, --help Show this message
+";
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Repl,
+    Stdin,
+    File(String),
+    Expr(String),
+    Help,
+}
+
+/// Splits the arguments after the program name into what to run and the
+/// arguments passed on to `(command-line)`.
+fn parse_args(mut args: Vec<String>) -> Result<(Mode, Vec<String>), String> {
+    if args.is_empty() {
+        return Ok((Mode::Repl, args));
+    }
+    let first = args.remove(0);
+    let mode = match first.as_str() {
+        "-h" | "--help" => Mode::Help,
+        "-" => Mode::Stdin,
+        "-e" if args.is_empty() => return Err("-e requires an expression".to_owned()),
+        "-e" => Mode::Expr(args.remove(0)),
+        option if option.starts_with('-') => {
+            return Err(format!("unknown option {}", option));
+        }
+        _ => Mode::File(first),
+    };
+    Ok((mode, args))
+}
+
+fn run(mode: Mode, program_name: String, mut args: Vec<String>) -> Result<(), String> {
+    let interp = Interpreter::new();
+    let script_name = match mode {
+        Mode::File(ref path) => path.clone(),
+        _ => program_name,
+    };
+    args.insert(0, script_name);
+    interp.set_command_line(args);
+    match mode {
+        Mode::Help => print!("{}", USAGE),
+        Mode::Repl => {
+            let stdin = io::stdin();
+            repl::run(&interp, stdin.lock(), &mut io::stdout()).map_err(|err| err.to_string())?;
+        }
+        Mode::Stdin => {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|err| format!("stdin: {}", err))?;
+            script::run_source(&interp, &source).map_err(|err| format!("stdin: {}", err))?;
+        }
+        Mode::File(path) => {
+            script::load_file(&interp, &path)?;
+        }
+        Mode::Expr(expr) => match script::run_source(&interp, &expr)? {
+            LispVal::Unspecified => (),
+            value => println!("{}", value),
+        },
+    }
+    Ok(())
+}
 
 fn main() {
-    let output = parse_lisp_expr("(a '(quoted (dotted special . list)) test)");
-    println!("Output is {:?}", output);
+    let mut args = env::args();
+    let program_name = args.next().unwrap_or_else(|| "scheme".to_owned());
+    let (mode, args) = match parse_args(args.collect()) {
+        Ok((Mode::Repl, args)) if !io::stdin().is_terminal() => (Mode::Stdin, args),
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("scheme: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(mode, program_name, args) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
 
+#[cfg(test)]
+mod tests {
+
+    use crate::*;
 
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_test() {
+        assert_eq!(parse_args(args(&[])).unwrap(), (Mode::Repl, args(&[])));
+        assert_eq!(
+            parse_args(args(&["run.scm", "-e", "x"])).unwrap(),
+            (Mode::File("run.scm".to_owned()), args(&["-e", "x"]))
+        );
+        assert_eq!(
+            parse_args(args(&["-e", "(+ 1 2)", "a"])).unwrap(),
+            (Mode::Expr("(+ 1 2)".to_owned()), args(&["a"]))
+        );
+        assert_eq!(
+            parse_args(args(&["-", "a"])).unwrap(),
+            (Mode::Stdin, args(&["a"]))
+        );
+        assert_eq!(
+            parse_args(args(&["--help"])).unwrap(),
+            (Mode::Help, args(&[]))
+        );
+        assert!(parse_args(args(&["-e"])).is_err());
+        assert!(parse_args(args(&["--frobnicate"])).is_err());
+    }
+}
@@ -0,0 +1,982 @@
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Pow, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// A Scheme number. Exact integers start out as fixnums and are promoted to
+/// bignums on overflow; every constructor below normalises back down to the
+/// simplest representation, so `Integer(2)` and `BigInteger(2)` never coexist.
+/// Complex numbers are always inexact.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    BigInteger(BigInt),
+    Rational(BigRational),
+    Real(f64),
+    Complex(Complex64),
+}
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Number {
+        Number::Integer(n)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(x: f64) -> Number {
+        Number::Real(x)
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(n: BigInt) -> Number {
+        match n.to_i64() {
+            Some(n) => Number::Integer(n),
+            None => Number::BigInteger(n),
+        }
+    }
+}
+
+impl From<BigRational> for Number {
+    fn from(r: BigRational) -> Number {
+        if r.is_integer() {
+            Number::from(r.to_integer())
+        } else {
+            Number::Rational(r)
+        }
+    }
+}
+
+impl From<Complex64> for Number {
+    fn from(z: Complex64) -> Number {
+        if z.im == 0.0 {
+            Number::Real(z.re)
+        } else {
+            Number::Complex(z)
+        }
+    }
+}
+
+fn ratio_to_f64(r: &BigRational) -> f64 {
+    let (n, d) = (r.numer(), r.denom());
+    if let (Some(n), Some(d)) = (n.to_f64(), d.to_f64()) {
+        if n.is_finite() && d.is_finite() {
+            return n / d;
+        }
+    }
+    // Scale the quotient into a 64-bit window so huge operands don't
+    // overflow to infinity before the division happens.
+    let shift = n.bits() as i64 - d.bits() as i64 - 64;
+    let q = if shift > 0 {
+        n / (d << shift as usize)
+    } else {
+        (n << (-shift) as usize) / d
+    };
+    q.to_f64().unwrap_or(f64::NAN) * 2f64.powi(shift as i32)
+}
+
+impl Number {
+    pub fn is_exact(&self) -> bool {
+        match self {
+            Number::Integer(_) | Number::BigInteger(_) | Number::Rational(_) => true,
+            Number::Real(_) | Number::Complex(_) => false,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Number::Integer(_) | Number::BigInteger(_) => true,
+            Number::Real(x) => x.is_finite() && x.fract() == 0.0,
+            _ => false,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(n) => *n == 0,
+            Number::Real(x) => *x == 0.0,
+            Number::Complex(z) => z.is_zero(),
+            // Normalisation guarantees bignums and ratios are never zero.
+            Number::BigInteger(_) | Number::Rational(_) => false,
+        }
+    }
+
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Integer(n) => Some(BigInt::from(*n)),
+            Number::BigInteger(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Number::Integer(n) => Some(BigRational::from_integer(BigInt::from(*n))),
+            Number::BigInteger(n) => Some(BigRational::from_integer(n.clone())),
+            Number::Rational(r) => Some(r.clone()),
+            Number::Real(x) => BigRational::from_float(*x),
+            Number::Complex(_) => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            Number::Integer(n) => Some(*n as f64),
+            Number::BigInteger(n) => Some(n.to_f64().unwrap_or_else(|| {
+                if n.is_negative() {
+                    f64::NEG_INFINITY
+                } else {
+                    f64::INFINITY
+                }
+            })),
+            Number::Rational(r) => Some(ratio_to_f64(r)),
+            Number::Real(x) => Some(*x),
+            Number::Complex(_) => None,
+        }
+    }
+
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Number::Complex(z) => *z,
+            _ => Complex64::new(self.to_f64().unwrap_or(f64::NAN), 0.0),
+        }
+    }
+
+    pub fn to_exact(&self) -> Option<Number> {
+        self.to_rational().map(Number::from)
+    }
+
+    pub fn to_inexact(&self) -> Number {
+        match self {
+            Number::Complex(z) => Number::Complex(*z),
+            _ => Number::Real(self.to_f64().unwrap_or(f64::NAN)),
+        }
+    }
+
+    // Position in the tower; binary operations work at the higher level of
+    // their two operands.
+    fn level(&self) -> u8 {
+        match self {
+            Number::Integer(_) | Number::BigInteger(_) => 0,
+            Number::Rational(_) => 1,
+            Number::Real(_) => 2,
+            Number::Complex(_) => 3,
+        }
+    }
+
+    fn binary_op(
+        &self,
+        other: &Number,
+        fixnum: fn(i64, i64) -> Option<i64>,
+        integer: fn(BigInt, BigInt) -> BigInt,
+        rational: fn(BigRational, BigRational) -> BigRational,
+        real: fn(f64, f64) -> f64,
+        complex: fn(Complex64, Complex64) -> Complex64,
+    ) -> Number {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            if let Some(n) = fixnum(*a, *b) {
+                return Number::Integer(n);
+            }
+        }
+        match self.level().max(other.level()) {
+            0 => Number::from(integer(
+                self.to_bigint().unwrap(),
+                other.to_bigint().unwrap(),
+            )),
+            1 => Number::from(rational(
+                self.to_rational().unwrap(),
+                other.to_rational().unwrap(),
+            )),
+            2 => Number::Real(real(self.to_f64().unwrap(), other.to_f64().unwrap())),
+            _ => Number::from(complex(self.to_complex(), other.to_complex())),
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        self.binary_op(
+            other,
+            i64::checked_add,
+            |a, b| a + b,
+            |a, b| a + b,
+            |a, b| a + b,
+            |a, b| a + b,
+        )
+    }
+
+    pub fn sub(&self, other: &Number) -> Number {
+        self.binary_op(
+            other,
+            i64::checked_sub,
+            |a, b| a - b,
+            |a, b| a - b,
+            |a, b| a - b,
+            |a, b| a - b,
+        )
+    }
+
+    pub fn mul(&self, other: &Number) -> Number {
+        self.binary_op(
+            other,
+            i64::checked_mul,
+            |a, b| a * b,
+            |a, b| a * b,
+            |a, b| a * b,
+            |a, b| a * b,
+        )
+    }
+
+    /// Divides two numbers, producing an exact rational when both operands
+    /// are exact. Returns `None` on exact division by zero; inexact division
+    /// by zero follows IEEE 754 and yields an infinity or NaN.
+    pub fn div(&self, other: &Number) -> Option<Number> {
+        if other.is_exact() && other.is_zero() {
+            return None;
+        }
+        Some(match self.level().max(other.level()) {
+            0 | 1 => Number::from(self.to_rational().unwrap() / other.to_rational().unwrap()),
+            2 => Number::Real(self.to_f64().unwrap() / other.to_f64().unwrap()),
+            _ => Number::from(self.to_complex() / other.to_complex()),
+        })
+    }
+
+    fn integer_op(
+        &self,
+        other: &Number,
+        fixnum: fn(i64, i64) -> Option<i64>,
+        integer: fn(&BigInt, &BigInt) -> BigInt,
+    ) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        self.integers_op(other, fixnum, integer)
+    }
+
+    // Like `integer_op`, for operations that are defined for a zero operand.
+    fn integers_op(
+        &self,
+        other: &Number,
+        fixnum: fn(i64, i64) -> Option<i64>,
+        integer: fn(&BigInt, &BigInt) -> BigInt,
+    ) -> Option<Number> {
+        if !self.is_integer() || !other.is_integer() {
+            return None;
+        }
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            if let Some(n) = fixnum(*a, *b) {
+                return Some(Number::Integer(n));
+            }
+        }
+        let exact = |n: &Number| n.to_exact().and_then(|n| n.to_bigint());
+        let result = Number::from(integer(&exact(self)?, &exact(other)?));
+        if self.is_exact() && other.is_exact() {
+            Some(result)
+        } else {
+            Some(result.to_inexact())
+        }
+    }
+
+    /// Truncating integer division. Returns `None` for non-integers or a
+    /// zero divisor.
+    pub fn quotient(&self, other: &Number) -> Option<Number> {
+        self.integer_op(other, i64::checked_div, |a, b| a / b)
+    }
+
+    /// Remainder with the sign of the dividend.
+    pub fn remainder(&self, other: &Number) -> Option<Number> {
+        self.integer_op(other, i64::checked_rem, |a, b| a % b)
+    }
+
+    /// Remainder with the sign of the divisor.
+    pub fn modulo(&self, other: &Number) -> Option<Number> {
+        self.integer_op(
+            other,
+            |a, b| a.checked_rem(b).map(|_| a.mod_floor(&b)),
+            |a, b| a.mod_floor(b),
+        )
+    }
+
+    /// Floored integer division, the partner of `modulo`.
+    pub fn floor_quotient(&self, other: &Number) -> Option<Number> {
+        self.integer_op(
+            other,
+            |a, b| a.checked_rem(b).map(|_| Integer::div_floor(&a, &b)),
+            |a, b| a.div_floor(b),
+        )
+    }
+
+    /// The non-negative greatest common divisor of two integers.
+    pub fn gcd(&self, other: &Number) -> Option<Number> {
+        // The fixnum `gcd` overflows on `i64::MIN`, so always use bignums.
+        self.integers_op(other, |_, _| None, |a, b| a.gcd(b))
+    }
+
+    /// The non-negative least common multiple of two integers.
+    pub fn lcm(&self, other: &Number) -> Option<Number> {
+        self.integers_op(other, |_, _| None, |a, b| a.lcm(b))
+    }
+
+    /// The exact integer `s` and remainder `r` with `s * s + r` equal to a
+    /// non-negative exact integer.
+    pub fn exact_integer_sqrt(&self) -> Option<(Number, Number)> {
+        match self.to_bigint() {
+            Some(n) if !n.is_negative() => {
+                let s = n.sqrt();
+                let rest = &n - &s * &s;
+                Some((Number::from(s), Number::from(rest)))
+            }
+            _ => None,
+        }
+    }
+
+    fn rounded(
+        &self,
+        rational: fn(&BigRational) -> BigRational,
+        real: fn(f64) -> f64,
+    ) -> Option<Number> {
+        match self {
+            Number::Integer(_) | Number::BigInteger(_) => Some(self.clone()),
+            Number::Rational(r) => Some(Number::from(rational(r))),
+            Number::Real(x) => Some(Number::Real(real(*x))),
+            Number::Complex(_) => None,
+        }
+    }
+
+    pub fn floor(&self) -> Option<Number> {
+        self.rounded(BigRational::floor, f64::floor)
+    }
+
+    pub fn ceiling(&self) -> Option<Number> {
+        self.rounded(BigRational::ceil, f64::ceil)
+    }
+
+    pub fn truncate(&self) -> Option<Number> {
+        self.rounded(BigRational::trunc, f64::trunc)
+    }
+
+    /// Rounds to the nearest integer, and to the even one when halfway.
+    pub fn round(&self) -> Option<Number> {
+        self.rounded(round_even, f64::round_ties_even)
+    }
+
+    // The parts of a rational, inexact if the number is.
+    fn ratio_part(&self, part: fn(&BigRational) -> &BigInt) -> Option<Number> {
+        let n = Number::from(part(&self.to_rational()?).clone());
+        Some(if self.is_exact() { n } else { n.to_inexact() })
+    }
+
+    pub fn numerator(&self) -> Option<Number> {
+        self.ratio_part(BigRational::numer)
+    }
+
+    pub fn denominator(&self) -> Option<Number> {
+        self.ratio_part(BigRational::denom)
+    }
+
+    /// Raises a number to a power, exactly when the base is exact and the
+    /// power an exact integer. Returns `None` when an exact zero is raised to
+    /// a negative power.
+    pub fn expt(&self, power: &Number) -> Option<Number> {
+        if self.is_exact() {
+            if let Number::Integer(n) = power {
+                let base = self.to_rational()?;
+                if base.is_zero() && *n < 0 {
+                    return None;
+                }
+                if let Ok(e) = u32::try_from(n.unsigned_abs()) {
+                    let numer = base.numer().clone().pow(e);
+                    let denom = base.denom().clone().pow(e);
+                    let power = BigRational::new(numer, denom);
+                    return Some(Number::from(if *n < 0 { power.recip() } else { power }));
+                }
+            }
+        }
+        match (self.to_f64(), power.to_f64()) {
+            (Some(base), Some(power)) if base >= 0.0 || power.fract() == 0.0 => {
+                Some(Number::Real(base.powf(power)))
+            }
+            _ => Some(Number::from(self.to_complex().powc(power.to_complex()))),
+        }
+    }
+
+    /// The simplest rational that differs from this number by no more than
+    /// `tolerance`. Returns `None` for infinities, NaN and complex numbers.
+    pub fn rationalize(&self, tolerance: &Number) -> Option<Number> {
+        let (x, y) = (self.to_rational()?, tolerance.to_rational()?.abs());
+        let simplest = Number::from(simplest_between(&(&x - &y), &(&x + &y)));
+        if self.is_exact() && tolerance.is_exact() {
+            Some(simplest)
+        } else {
+            Some(simplest.to_inexact())
+        }
+    }
+
+    /// Writes an exact number in another radix. Inexact numbers can only be
+    /// written in decimal.
+    pub fn to_string_radix(&self, radix: u32) -> Option<String> {
+        match self {
+            _ if radix == 10 => Some(self.to_string()),
+            Number::Integer(n) => Some(BigInt::from(*n).to_str_radix(radix)),
+            Number::BigInteger(n) => Some(n.to_str_radix(radix)),
+            Number::Rational(r) => Some(format!(
+                "{}/{}",
+                r.numer().to_str_radix(radix),
+                r.denom().to_str_radix(radix)
+            )),
+            Number::Real(_) | Number::Complex(_) => None,
+        }
+    }
+
+    /// Numeric equality, which unlike `PartialEq` ignores exactness.
+    pub fn num_eq(&self, other: &Number) -> bool {
+        match (self, other) {
+            (Number::Complex(_), _) | (_, Number::Complex(_)) => {
+                self.to_complex() == other.to_complex()
+            }
+            _ => self.num_cmp(other) == Some(Ordering::Equal),
+        }
+    }
+
+    /// Orders two real numbers. Exact operands are compared exactly so that
+    /// large integers never lose precision; `None` for complex or NaN.
+    pub fn num_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Complex(_), _) | (_, Number::Complex(_)) => None,
+            (Number::Integer(a), Number::Integer(b)) => Some(a.cmp(b)),
+            _ if self.is_exact() && other.is_exact() => {
+                Some(self.to_rational()?.cmp(&other.to_rational()?))
+            }
+            _ => {
+                let (a, b) = (self.to_f64()?, other.to_f64()?);
+                if a.is_finite() && b.is_finite() {
+                    // Compare exactly so 2^53 + 1 is still bigger than 2^53.
+                    Some(self.to_rational()?.cmp(&other.to_rational()?))
+                } else {
+                    a.partial_cmp(&b)
+                }
+            }
+        }
+    }
+}
+
+fn round_even(r: &BigRational) -> BigRational {
+    let floor = r.floor();
+    let half = BigRational::new(BigInt::from(1), BigInt::from(2));
+    match (r - &floor).cmp(&half) {
+        Ordering::Less => floor,
+        Ordering::Equal if floor.to_integer().is_even() => floor,
+        _ => floor + BigRational::one(),
+    }
+}
+
+// The rational with the smallest denominator in `[lo, hi]`, found by
+// walking the continued fractions of the two bounds.
+fn simplest_between(lo: &BigRational, hi: &BigRational) -> BigRational {
+    if lo.is_positive() {
+        let floor = lo.floor();
+        if &floor == lo {
+            floor
+        } else if floor < hi.floor() {
+            floor + BigRational::one()
+        } else {
+            let rest = simplest_between(&(hi - &floor).recip(), &(lo - &floor).recip());
+            floor + rest.recip()
+        }
+    } else if hi.is_negative() {
+        -simplest_between(&-hi, &-lo)
+    } else {
+        BigRational::zero()
+    }
+}
+
+fn format_real(x: f64) -> String {
+    if x.is_nan() {
+        "+nan.0".to_owned()
+    } else if x.is_infinite() {
+        if x > 0.0 { "+inf.0" } else { "-inf.0" }.to_owned()
+    } else if x != 0.0 && (x.abs() >= 1e21 || x.abs() < 1e-7) {
+        format!("{:e}", x)
+    } else {
+        let s = x.to_string();
+        if s.contains('.') {
+            s
+        } else {
+            s + ".0"
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Integer(n) => write!(f, "{}", n),
+            Number::BigInteger(n) => write!(f, "{}", n),
+            Number::Rational(r) => write!(f, "{}/{}", r.numer(), r.denom()),
+            Number::Real(x) => write!(f, "{}", format_real(*x)),
+            Number::Complex(z) => {
+                let im = format_real(z.im);
+                let sign = if im.starts_with('+') || im.starts_with('-') {
+                    ""
+                } else {
+                    "+"
+                };
+                write!(f, "{}{}{}i", format_real(z.re), sign, im)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Exactness {
+    Default,
+    Exact,
+    Inexact,
+}
+
+// A real number as read, before the exactness prefix is applied. Decimal
+// notation is read exactly but remembers that it defaults to inexact.
+enum Real {
+    Exact(BigRational),
+    Decimal(BigRational, f64),
+    Inexact(f64),
+}
+
+impl Real {
+    fn finish(self, exactness: Exactness) -> Option<Number> {
+        match (self, exactness) {
+            (Real::Exact(r), Exactness::Inexact) => Some(Number::Real(ratio_to_f64(&r))),
+            (Real::Exact(r), _) | (Real::Decimal(r, _), Exactness::Exact) => Some(Number::from(r)),
+            (Real::Decimal(_, x), _) => Some(Number::Real(x)),
+            (Real::Inexact(_), Exactness::Exact) => None,
+            (Real::Inexact(x), _) => Some(Number::Real(x)),
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Real::Exact(r) => ratio_to_f64(r),
+            Real::Decimal(_, x) | Real::Inexact(x) => *x,
+        }
+    }
+}
+
+fn parse_uinteger(text: &str, radix: u32) -> Option<BigInt> {
+    if text.is_empty() || !text.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    BigInt::parse_bytes(text.as_bytes(), radix)
+}
+
+fn parse_decimal(text: &str) -> Option<Real> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], &text[i + 1..]),
+        None => (text, ""),
+    };
+    let (int_part, frac_part) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+        None => (mantissa, ""),
+    };
+    let digits = format!("{}{}", int_part, frac_part);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let exponent: i64 = if text.len() > mantissa.len() {
+        let unsigned = exponent.trim_start_matches(['+', '-']);
+        if unsigned.is_empty()
+            || exponent.len() - unsigned.len() > 1
+            || !unsigned.chars().all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        exponent.parse().ok()?
+    } else {
+        0
+    };
+    let inexact = text.parse::<f64>().ok()?;
+    // Exponents this large only matter to `#e`, and building the exact value
+    // would take unbounded memory.
+    if exponent.abs() > 10_000 {
+        return Some(Real::Inexact(inexact));
+    }
+    let scale = exponent - frac_part.len() as i64;
+    let ten = BigInt::from(10);
+    let mantissa = BigInt::parse_bytes(digits.as_bytes(), 10)?;
+    let exact = if scale >= 0 {
+        BigRational::from_integer(mantissa * num_traits::pow(ten, scale as usize))
+    } else {
+        BigRational::new(mantissa, num_traits::pow(ten, (-scale) as usize))
+    };
+    Some(Real::Decimal(exact, inexact))
+}
+
+fn parse_ureal(text: &str, radix: u32) -> Option<Real> {
+    if let Some(i) = text.find('/') {
+        let numer = parse_uinteger(&text[..i], radix)?;
+        let denom = parse_uinteger(&text[i + 1..], radix)?;
+        if denom.is_zero() {
+            return None;
+        }
+        return Some(Real::Exact(BigRational::new(numer, denom)));
+    }
+    if let Some(n) = parse_uinteger(text, radix) {
+        return Some(Real::Exact(BigRational::from_integer(n)));
+    }
+    if radix == 10 {
+        parse_decimal(text)
+    } else {
+        None
+    }
+}
+
+fn parse_real(text: &str, radix: u32) -> Option<Real> {
+    match text {
+        "+inf.0" => return Some(Real::Inexact(f64::INFINITY)),
+        "-inf.0" => return Some(Real::Inexact(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => return Some(Real::Inexact(f64::NAN)),
+        _ => {}
+    }
+    let (negative, unsigned) = match text.as_bytes().first() {
+        Some(b'+') => (false, &text[1..]),
+        Some(b'-') => (true, &text[1..]),
+        _ => (false, text),
+    };
+    let real = parse_ureal(unsigned, radix)?;
+    Some(if !negative {
+        real
+    } else {
+        match real {
+            Real::Exact(r) => Real::Exact(-r),
+            Real::Decimal(r, x) => Real::Decimal(-r, -x),
+            Real::Inexact(x) => Real::Inexact(-x),
+        }
+    })
+}
+
+// Parses the imaginary part of a rectangular complex number, which must
+// carry an explicit sign; a bare sign stands for a unit coefficient.
+fn parse_imaginary(text: &str, radix: u32) -> Option<f64> {
+    match text {
+        "+" => Some(1.0),
+        "-" => Some(-1.0),
+        _ if text.starts_with('+') || text.starts_with('-') => {
+            parse_real(text, radix).map(|real| real.to_f64())
+        }
+        _ => None,
+    }
+}
+
+fn parse_complex(text: &str, radix: u32, exactness: Exactness) -> Option<Number> {
+    if let Some(i) = text.find('@') {
+        let magnitude = parse_real(&text[..i], radix)?.to_f64();
+        let angle = parse_real(&text[i + 1..], radix)?.to_f64();
+        return Some(Number::from(Complex64::from_polar(&magnitude, &angle)));
+    }
+    let body = match text.strip_suffix('i') {
+        Some(body) if !text.ends_with("inf.0") => body,
+        _ => return parse_real(text, radix)?.finish(exactness),
+    };
+    if exactness == Exactness::Exact {
+        return None;
+    }
+    // The imaginary part starts at the last sign that isn't part of an
+    // exponent. Only decimal numbers have exponents; in hex `e` is a digit.
+    let bytes = body.as_bytes();
+    let split = (1..bytes.len()).rev().find(|&i| {
+        (bytes[i] == b'+' || bytes[i] == b'-')
+            && !(radix == 10 && (bytes[i - 1] == b'e' || bytes[i - 1] == b'E'))
+    });
+    let (re, im) = match split {
+        Some(i) => (
+            parse_real(&body[..i], radix)?.to_f64(),
+            parse_imaginary(&body[i..], radix)?,
+        ),
+        None => (0.0, parse_imaginary(body, radix)?),
+    };
+    Some(Number::from(Complex64::new(re, im)))
+}
+
+/// Parses a number in R7RS external syntax, including `#x`/`#b`/`#o`/`#d`
+/// radix and `#e`/`#i` exactness prefixes. `radix` is used when the text
+/// carries no radix prefix of its own.
+pub fn parse_number(text: &str, radix: u32) -> Option<Number> {
+    let mut radix = radix;
+    let mut exactness = Exactness::Default;
+    let mut rest = text;
+    let (mut seen_radix, mut seen_exactness) = (false, false);
+    while rest.len() >= 2 && rest.starts_with('#') {
+        match rest.as_bytes()[1].to_ascii_lowercase() {
+            b'x' if !seen_radix => radix = 16,
+            b'd' if !seen_radix => radix = 10,
+            b'o' if !seen_radix => radix = 8,
+            b'b' if !seen_radix => radix = 2,
+            b'e' if !seen_exactness => exactness = Exactness::Exact,
+            b'i' if !seen_exactness => exactness = Exactness::Inexact,
+            _ => return None,
+        }
+        match rest.as_bytes()[1].to_ascii_lowercase() {
+            b'e' | b'i' => seen_exactness = true,
+            _ => seen_radix = true,
+        }
+        rest = &rest[2..];
+    }
+    let number = parse_complex(&rest.to_ascii_lowercase(), radix, exactness)?;
+    match exactness {
+        Exactness::Inexact => Some(number.to_inexact()),
+        _ => Some(number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::number::*;
+
+    fn rational(n: i64, d: i64) -> Number {
+        Number::from(BigRational::new(BigInt::from(n), BigInt::from(d)))
+    }
+
+    #[test]
+    fn parse_integer_test() {
+        assert_eq!(parse_number("42", 10), Some(Number::Integer(42)));
+        assert_eq!(parse_number("-17", 10), Some(Number::Integer(-17)));
+        assert_eq!(parse_number("+5", 10), Some(Number::Integer(5)));
+        assert_eq!(
+            parse_number("123456789012345678901234567890", 10),
+            Some(Number::BigInteger(
+                BigInt::parse_bytes(b"123456789012345678901234567890", 10).unwrap()
+            ))
+        );
+        assert_eq!(parse_number("abc", 10), None);
+        assert_eq!(parse_number("+", 10), None);
+        assert_eq!(parse_number("1+", 10), None);
+        assert_eq!(parse_number("", 10), None);
+    }
+
+    #[test]
+    fn parse_rational_and_real_test() {
+        assert_eq!(parse_number("1/3", 10), Some(rational(1, 3)));
+        assert_eq!(parse_number("-6/4", 10), Some(rational(-3, 2)));
+        assert_eq!(parse_number("4/2", 10), Some(Number::Integer(2)));
+        assert_eq!(parse_number("1/0", 10), None);
+        assert_eq!(parse_number("2.75", 10), Some(Number::Real(2.75)));
+        assert_eq!(parse_number(".5", 10), Some(Number::Real(0.5)));
+        assert_eq!(parse_number("5.", 10), Some(Number::Real(5.0)));
+        assert_eq!(parse_number("1e10", 10), Some(Number::Real(1e10)));
+        assert_eq!(parse_number("-2.5E-3", 10), Some(Number::Real(-2.5e-3)));
+        assert_eq!(
+            parse_number("+inf.0", 10),
+            Some(Number::Real(f64::INFINITY))
+        );
+        assert_eq!(
+            parse_number("-inf.0", 10),
+            Some(Number::Real(f64::NEG_INFINITY))
+        );
+        assert!(match parse_number("+nan.0", 10) {
+            Some(Number::Real(x)) => x.is_nan(),
+            _ => false,
+        });
+        assert_eq!(parse_number(".", 10), None);
+        assert_eq!(parse_number("1e", 10), None);
+        assert_eq!(parse_number("inf", 10), None);
+    }
+
+    #[test]
+    fn parse_prefix_test() {
+        assert_eq!(parse_number("#xff", 10), Some(Number::Integer(255)));
+        assert_eq!(parse_number("#X-1A", 10), Some(Number::Integer(-26)));
+        assert_eq!(parse_number("#b101", 10), Some(Number::Integer(5)));
+        assert_eq!(parse_number("#o17", 10), Some(Number::Integer(15)));
+        assert_eq!(parse_number("#d10", 10), Some(Number::Integer(10)));
+        assert_eq!(parse_number("#e1.5", 10), Some(rational(3, 2)));
+        assert_eq!(parse_number("#i3/4", 10), Some(Number::Real(0.75)));
+        assert_eq!(parse_number("#x#e10", 10), Some(Number::Integer(16)));
+        assert_eq!(parse_number("#e#x10", 10), Some(Number::Integer(16)));
+        assert_eq!(parse_number("#b2", 10), None);
+        assert_eq!(parse_number("#x#x1", 10), None);
+        assert_eq!(parse_number("#e+inf.0", 10), None);
+        assert_eq!(parse_number("ff", 16), Some(Number::Integer(255)));
+    }
+
+    #[test]
+    fn parse_complex_test() {
+        assert_eq!(
+            parse_number("1+2i", 10),
+            Some(Number::Complex(Complex64::new(1.0, 2.0)))
+        );
+        assert_eq!(
+            parse_number("-i", 10),
+            Some(Number::Complex(Complex64::new(0.0, -1.0)))
+        );
+        assert_eq!(
+            parse_number("1.5e2-3i", 10),
+            Some(Number::Complex(Complex64::new(150.0, -3.0)))
+        );
+        assert_eq!(
+            parse_number("+inf.0i", 10),
+            Some(Number::Complex(Complex64::new(0.0, f64::INFINITY)))
+        );
+        assert_eq!(parse_number("1+0i", 10), Some(Number::Real(1.0)));
+        assert_eq!(parse_number("1@0", 10), Some(Number::Real(1.0)));
+        assert_eq!(parse_number("i", 10), None);
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let max = Number::Integer(i64::MAX);
+        let promoted = max.add(&Number::Integer(1));
+        assert_eq!(
+            promoted,
+            Number::BigInteger(BigInt::from(i64::MAX) + BigInt::from(1))
+        );
+        assert_eq!(promoted.sub(&Number::Integer(1)), max);
+        assert_eq!(
+            Number::Integer(1).div(&Number::Integer(3)),
+            Some(rational(1, 3))
+        );
+        assert_eq!(rational(1, 3).add(&rational(2, 3)), Number::Integer(1));
+        assert_eq!(
+            Number::Integer(1).add(&Number::Real(0.5)),
+            Number::Real(1.5)
+        );
+        assert_eq!(Number::Integer(1).div(&Number::Integer(0)), None);
+        assert_eq!(
+            Number::Integer(1).div(&Number::Real(0.0)),
+            Some(Number::Real(f64::INFINITY))
+        );
+        assert_eq!(
+            Number::Integer(-7).modulo(&Number::Integer(2)),
+            Some(Number::Integer(1))
+        );
+        assert_eq!(
+            Number::Integer(-7).remainder(&Number::Integer(2)),
+            Some(Number::Integer(-1))
+        );
+        assert_eq!(
+            Number::Real(7.0).quotient(&Number::Integer(2)),
+            Some(Number::Real(3.0))
+        );
+        assert_eq!(Number::Real(7.5).quotient(&Number::Integer(2)), None);
+    }
+
+    #[test]
+    fn integer_test() {
+        let (n, d) = (Number::Integer(-7), Number::Integer(2));
+        assert_eq!(n.floor_quotient(&d), Some(Number::Integer(-4)));
+        assert_eq!(n.floor_quotient(&Number::Integer(0)), None);
+        assert_eq!(
+            Number::Integer(i64::MIN).gcd(&Number::Integer(0)),
+            Some(Number::from(-BigInt::from(i64::MIN)))
+        );
+        assert_eq!(
+            Number::Real(4.0).gcd(&Number::Integer(6)),
+            Some(Number::Real(2.0))
+        );
+        assert_eq!(
+            Number::Integer(-4).lcm(&Number::Integer(6)),
+            Some(Number::Integer(12))
+        );
+        assert_eq!(
+            Number::Integer(17).exact_integer_sqrt(),
+            Some((Number::Integer(4), Number::Integer(1)))
+        );
+        assert_eq!(Number::Integer(-1).exact_integer_sqrt(), None);
+    }
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(rational(-7, 2).floor(), Some(Number::Integer(-4)));
+        assert_eq!(rational(-7, 2).ceiling(), Some(Number::Integer(-3)));
+        assert_eq!(rational(-7, 2).truncate(), Some(Number::Integer(-3)));
+        assert_eq!(rational(7, 2).round(), Some(Number::Integer(4)));
+        assert_eq!(rational(5, 2).round(), Some(Number::Integer(2)));
+        assert_eq!(Number::Real(2.5).round(), Some(Number::Real(2.0)));
+        assert_eq!(Number::Real(-3.7).truncate(), Some(Number::Real(-3.0)));
+        assert_eq!(rational(6, 4).numerator(), Some(Number::Integer(3)));
+        assert_eq!(Number::Real(0.5).denominator(), Some(Number::Real(2.0)));
+        assert_eq!(
+            rational(3, 10).rationalize(&rational(1, 10)),
+            Some(rational(1, 3))
+        );
+        assert_eq!(
+            Number::Real(0.3).rationalize(&rational(1, 10)),
+            Some(Number::Real(1.0 / 3.0))
+        );
+    }
+
+    #[test]
+    fn expt_test() {
+        assert_eq!(
+            Number::Integer(2).expt(&Number::Integer(100)),
+            parse_number("1267650600228229401496703205376", 10)
+        );
+        assert_eq!(
+            Number::Integer(2).expt(&Number::Integer(-2)),
+            Some(rational(1, 4))
+        );
+        assert_eq!(
+            Number::Integer(0).expt(&Number::Integer(0)),
+            Some(Number::Integer(1))
+        );
+        assert_eq!(Number::Integer(0).expt(&Number::Integer(-1)), None);
+        assert_eq!(
+            Number::Integer(4).expt(&rational(1, 2)),
+            Some(Number::Real(2.0))
+        );
+        assert!(matches!(
+            Number::Integer(-1).expt(&Number::Real(0.5)),
+            Some(Number::Complex(_))
+        ));
+    }
+
+    #[test]
+    fn radix_test() {
+        assert_eq!(
+            Number::Integer(-255).to_string_radix(16),
+            Some("-ff".to_owned())
+        );
+        assert_eq!(rational(1, 2).to_string_radix(2), Some("1/10".to_owned()));
+        assert_eq!(
+            Number::Real(1.5).to_string_radix(10),
+            Some("1.5".to_owned())
+        );
+        assert_eq!(Number::Real(1.5).to_string_radix(2), None);
+    }
+
+    #[test]
+    fn comparison_test() {
+        assert!(Number::Integer(2).num_eq(&Number::Real(2.0)));
+        assert!(rational(1, 2).num_eq(&Number::Real(0.5)));
+        assert_eq!(
+            Number::Integer(1).num_cmp(&rational(1, 2)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(Number::Real(f64::NAN).num_cmp(&Number::Integer(1)), None);
+    }
+
+    #[test]
+    fn display_test() {
+        assert_eq!(Number::Integer(-5).to_string(), "-5");
+        assert_eq!(rational(-1, 3).to_string(), "-1/3");
+        assert_eq!(Number::Real(1.0).to_string(), "1.0");
+        assert_eq!(Number::Real(0.1).to_string(), "0.1");
+        assert_eq!(Number::Real(1e300).to_string(), "1e300");
+        assert_eq!(Number::Real(f64::NEG_INFINITY).to_string(), "-inf.0");
+        assert_eq!(
+            Number::Complex(Complex64::new(1.0, -2.0)).to_string(),
+            "1.0-2.0i"
+        );
+        for text in &[
+            "1e300",
+            "-1/3",
+            "1.0-2.0i",
+            "+nan.0",
+            "12345678901234567890123",
+        ] {
+            let number = parse_number(text, 10).unwrap();
+            assert_eq!(number.to_string(), *text);
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use crate::gc::{self, Trace, Tracer};
+use crate::parser::LispVal;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::FromIterator;
+use std::rc::Rc;
+
+/// A mutable cons cell. Pairs live behind an `Rc`, so copying a `LispVal`
+/// shares the cell: `set-car!` through one copy is seen through every other,
+/// and `eq?` compares cells by address.
+pub struct Pair {
+    car: RefCell<LispVal>,
+    cdr: RefCell<LispVal>,
+}
+
+impl Pair {
+    pub fn car(&self) -> LispVal {
+        self.car.borrow().clone()
+    }
+
+    pub fn cdr(&self) -> LispVal {
+        self.cdr.borrow().clone()
+    }
+
+    pub fn set_car(&self, value: LispVal) {
+        *self.car.borrow_mut() = value;
+    }
+
+    pub fn set_cdr(&self, value: LispVal) {
+        *self.cdr.borrow_mut() = value;
+    }
+}
+
+impl Trace for Pair {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.value(&self.car.borrow());
+        tracer.value(&self.cdr.borrow());
+    }
+
+    fn clear(&self) {
+        self.car.replace(LispVal::Nil);
+        self.cdr.replace(LispVal::Nil);
+    }
+}
+
+/// Compares two values by contents, with `same` deciding for everything but
+/// pairs, vectors and multiple values. The parts still to compare are kept
+/// on an explicit stack rather than recursed into, so that data nested deeply
+/// through either the car or the cdr does not exhaust the stack. Each two
+/// objects are compared only once: meeting them again means both structures
+/// have looped back to a point that is already being compared, so circular
+/// data compares in finite time.
+pub(crate) fn equal_contents(
+    a: &LispVal,
+    b: &LispVal,
+    same: fn(&LispVal, &LispVal) -> bool,
+) -> bool {
+    equal_all(vec![(a.clone(), b.clone())], same)
+}
+
+fn equal_all(mut pending: Vec<(LispVal, LispVal)>, same: fn(&LispVal, &LispVal) -> bool) -> bool {
+    let mut seen = HashSet::new();
+    while let Some((a, b)) = pending.pop() {
+        let (x, y) = match (&a, &b) {
+            (LispVal::Pair(x), LispVal::Pair(y)) => {
+                (Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize)
+            }
+            (LispVal::Vector(x), LispVal::Vector(y)) => {
+                (Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize)
+            }
+            (LispVal::Values(x), LispVal::Values(y)) => {
+                (Rc::as_ptr(x) as usize, Rc::as_ptr(y) as usize)
+            }
+            _ if same(&a, &b) => continue,
+            _ => return false,
+        };
+        if x == y || !seen.insert((x, y)) {
+            continue;
+        }
+        match (&a, &b) {
+            (LispVal::Pair(x), LispVal::Pair(y)) => {
+                pending.push((x.cdr(), y.cdr()));
+                pending.push((x.car(), y.car()));
+            }
+            (LispVal::Vector(x), LispVal::Vector(y)) => {
+                let (x, y) = (x.borrow(), y.borrow());
+                if x.len() != y.len() {
+                    return false;
+                }
+                pending.extend(x.iter().cloned().zip(y.iter().cloned()).rev());
+            }
+            (LispVal::Values(x), LispVal::Values(y)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                pending.extend(x.iter().cloned().zip(y.iter().cloned()).rev());
+            }
+            _ => unreachable!(),
+        }
+    }
+    true
+}
+
+// Everything else compares by value when it is immutable and by its own
+// `PartialEq` otherwise, which for procedures and records is identity. Error
+// objects are compared by identity too, since their irritants may lead back
+// to them.
+fn same_value(a: &LispVal, b: &LispVal) -> bool {
+    match (a, b) {
+        (LispVal::Atom(x), LispVal::Atom(y)) => x == y,
+        (LispVal::Nil, LispVal::Nil) => true,
+        (LispVal::Number(x), LispVal::Number(y)) => x == y,
+        (LispVal::Char(x), LispVal::Char(y)) => x == y,
+        (LispVal::Boolean(x), LispVal::Boolean(y)) => x == y,
+        (LispVal::String(x), LispVal::String(y)) => x == y,
+        (LispVal::Bytevector(x), LispVal::Bytevector(y)) => x == y,
+        (LispVal::Record(x), LispVal::Record(y)) => x == y,
+        (LispVal::RecordType(x), LispVal::RecordType(y)) => x == y,
+        (LispVal::PrimitiveFunc(x), LispVal::PrimitiveFunc(y)) => x == y,
+        (LispVal::NativeFunc(x), LispVal::NativeFunc(y)) => x == y,
+        (LispVal::Func(x), LispVal::Func(y)) => x == y,
+        (LispVal::Continuation(x), LispVal::Continuation(y)) => x == y,
+        (LispVal::Closure(x), LispVal::Closure(y)) => x == y,
+        (LispVal::VmContinuation(x), LispVal::VmContinuation(y)) => x == y,
+        (LispVal::Macro(x), LispVal::Macro(y)) => x == y,
+        (LispVal::Error(x), LispVal::Error(y)) => Rc::ptr_eq(x, y),
+        (LispVal::Port(x), LispVal::Port(y)) => Rc::ptr_eq(x, y),
+        (LispVal::Eof, LispVal::Eof) => true,
+        (LispVal::Unspecified, LispVal::Unspecified) => true,
+        _ => false,
+    }
+}
+
+// Pairs, vectors and multiple values compare by contents. `eqv?` compares
+// them by identity instead.
+impl PartialEq for LispVal {
+    fn eq(&self, other: &LispVal) -> bool {
+        equal_contents(self, other, same_value)
+    }
+}
+
+impl PartialEq for Pair {
+    fn eq(&self, other: &Pair) -> bool {
+        std::ptr::eq(self, other)
+            || equal_all(
+                vec![(self.cdr(), other.cdr()), (self.car(), other.car())],
+                same_value,
+            )
+    }
+}
+
+impl fmt::Debug for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({:?} . {:?})", self.car.borrow(), self.cdr.borrow())
+    }
+}
+
+// Dropping a long or deeply nested list would otherwise drop each cell from
+// inside its parent's destructor, one stack frame per level. Instead the
+// cells this one owns outright are taken apart here, with the ones still to
+// visit kept on a worklist.
+impl Drop for Pair {
+    fn drop(&mut self) {
+        let car = self.car.replace(LispVal::Nil);
+        let cdr = self.cdr.replace(LispVal::Nil);
+        if !matches!(car, LispVal::Pair(_)) && !matches!(cdr, LispVal::Pair(_)) {
+            return;
+        }
+        let mut pending = vec![car, cdr];
+        while let Some(value) = pending.pop() {
+            if let LispVal::Pair(pair) = value {
+                if let Ok(pair) = Rc::try_unwrap(pair) {
+                    pending.push(pair.car.replace(LispVal::Nil));
+                    pending.push(pair.cdr.replace(LispVal::Nil));
+                }
+            }
+        }
+    }
+}
+
+impl LispVal {
+    pub fn cons(car: LispVal, cdr: LispVal) -> LispVal {
+        LispVal::Pair(gc::alloc(Pair {
+            car: RefCell::new(car),
+            cdr: RefCell::new(cdr),
+        }))
+    }
+
+    /// Builds a proper list of `items`.
+    pub fn list(items: Vec<LispVal>) -> LispVal {
+        LispVal::dotted_list(items, LispVal::Nil)
+    }
+
+    /// Builds a list of `items` ending in `tail` instead of the empty list.
+    pub fn dotted_list(items: Vec<LispVal>, tail: LispVal) -> LispVal {
+        items
+            .into_iter()
+            .rev()
+            .fold(tail, |list, item| LispVal::cons(item, list))
+    }
+
+    /// Iterates over the elements of a list, stopping at the first cdr that
+    /// is not a pair. Circular lists never end.
+    pub fn iter(&self) -> ListIter {
+        ListIter { next: self.clone() }
+    }
+
+    /// The elements of a list together with whatever ends it: `Nil` for a
+    /// proper list. Returns `None` for a circular list.
+    pub fn split_tail(&self) -> Option<(Vec<LispVal>, LispVal)> {
+        let mut items = vec![];
+        let mut next = self.clone();
+        let mut slow = self.clone();
+        while let LispVal::Pair(pair) = next {
+            items.push(pair.car());
+            next = pair.cdr();
+            // The slow cursor moves at half speed; the two meet only if the
+            // list loops back on itself.
+            if items.len() % 2 == 0 {
+                slow = slow.cdr_or_nil();
+                if let (LispVal::Pair(a), LispVal::Pair(b)) = (&slow, &next) {
+                    if Rc::ptr_eq(a, b) {
+                        return None;
+                    }
+                }
+            }
+        }
+        Some((items, next))
+    }
+
+    /// The elements of a proper list, or `None` for anything else.
+    pub fn list_to_vec(&self) -> Option<Vec<LispVal>> {
+        match self.split_tail()? {
+            (items, LispVal::Nil) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn cdr_or_nil(&self) -> LispVal {
+        match self {
+            LispVal::Pair(pair) => pair.cdr(),
+            _ => LispVal::Nil,
+        }
+    }
+}
+
+impl FromIterator<LispVal> for LispVal {
+    fn from_iter<I: IntoIterator<Item = LispVal>>(iter: I) -> LispVal {
+        LispVal::list(iter.into_iter().collect())
+    }
+}
+
+/// An iterator over the elements of a list, returned by [`LispVal::iter`].
+pub struct ListIter {
+    next: LispVal,
+}
+
+impl Iterator for ListIter {
+    type Item = LispVal;
+
+    fn next(&mut self) -> Option<LispVal> {
+        let pair = match &self.next {
+            LispVal::Pair(pair) => pair.clone(),
+            _ => return None,
+        };
+        self.next = pair.cdr();
+        Some(pair.car())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pair::*;
+    use crate::parser::int;
+
+    #[test]
+    fn list_test() {
+        let list = LispVal::list(vec![int(1), int(2), int(3)]);
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            vec![int(1), int(2), int(3)]
+        );
+        assert_eq!(list.list_to_vec(), Some(vec![int(1), int(2), int(3)]));
+        assert_eq!(list, (1..=3).map(int).collect());
+        assert_eq!(LispVal::list(vec![]), LispVal::Nil);
+
+        let dotted = LispVal::dotted_list(vec![int(1), int(2)], int(3));
+        assert_eq!(dotted.list_to_vec(), None);
+        assert_eq!(dotted.split_tail(), Some((vec![int(1), int(2)], int(3))));
+        assert_eq!(dotted.iter().count(), 2);
+    }
+
+    #[test]
+    fn shared_structure_test() {
+        let tail = LispVal::list(vec![int(2)]);
+        let list = LispVal::cons(int(1), tail.clone());
+        match &tail {
+            LispVal::Pair(pair) => pair.set_car(int(5)),
+            _ => unreachable!(),
+        }
+        assert_eq!(list, LispVal::list(vec![int(1), int(5)]));
+    }
+
+    #[test]
+    fn circular_list_test() {
+        let list = LispVal::list(vec![int(1), int(2), int(3)]);
+        let mut last = list.clone();
+        while let LispVal::Pair(pair) = last.clone() {
+            match pair.cdr() {
+                LispVal::Nil => {
+                    pair.set_cdr(list.clone());
+                    break;
+                }
+                next => last = next,
+            }
+        }
+        assert_eq!(list.split_tail(), None);
+        assert_eq!(list.list_to_vec(), None);
+        assert_eq!(list.iter().take(7).last(), Some(int(1)));
+    }
+
+    #[test]
+    fn long_list_test() {
+        let list: LispVal = (0..1_000_000).map(int).collect();
+        let copy: LispVal = (0..1_000_000).map(int).collect();
+        assert_eq!(list, copy);
+        assert_eq!(list.iter().count(), 1_000_000);
+    }
+
+    #[test]
+    fn deep_nesting_test() {
+        // `((((...))))`, nested through the car a million times.
+        let nested = || (0..1_000_000).fold(LispVal::Nil, |inner, _| LispVal::list(vec![inner]));
+        let (a, b) = (nested(), nested());
+        assert_eq!(a, b);
+        assert_ne!(a, LispVal::list(vec![LispVal::Nil]));
+        drop((a, b));
+    }
+
+    #[test]
+    fn circular_equality_test() {
+        // Two separate lists that each loop back to their start.
+        let circular = || {
+            let list = LispVal::list(vec![int(1), int(2)]);
+            if let LispVal::Pair(first) = &list {
+                if let LispVal::Pair(second) = first.cdr() {
+                    second.set_cdr(list.clone());
+                }
+            }
+            list
+        };
+        let (a, b) = (circular(), circular());
+        assert_eq!(a, b);
+        assert_ne!(a, LispVal::list(vec![int(1), int(2)]));
+        // Two separate vectors that contain themselves.
+        let selfish = || {
+            let vector = LispVal::vector(vec![int(1), LispVal::Nil]);
+            if let LispVal::Vector(items) = &vector {
+                items.borrow_mut()[1] = vector.clone();
+            }
+            vector
+        };
+        assert_eq!(selfish(), selfish());
+    }
+}
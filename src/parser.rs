@@ -1,83 +1,543 @@
-use nom::character::complete::{alpha1, alphanumeric1, digit1, space0, space1};
+use crate::error::LispError;
+use crate::eval::{Continuation, Lambda};
+use crate::expand::Macro;
+use crate::gc;
+use crate::interpreter::NativeFunc;
+use crate::number::{self, Number};
+use crate::pair::Pair;
+use crate::port::Port;
+use crate::record::{Record, RecordType};
+use crate::symbol::Symbol;
+use crate::vm::{self, Closure};
+use nom::bytes::complete::{is_a, tag, take_till, take_till1};
+use nom::character::complete::{
+    alpha1, alphanumeric1, char, hex_digit1, line_ending, multispace1, none_of, space0,
+};
+use nom::error::{VerboseError, VerboseErrorKind};
 use nom::*;
-use std::iter::FromIterator;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 
-type AppErr<'a> = nom::Err<(&'a str, nom::error::ErrorKind)>;
+// nom 5 only ships `cut` and `context` as functions; these wrap them so they
+// compose with the `named!` parsers below.
+macro_rules! cut (
+    ($i:expr, $submac:ident!( $($args:tt)* )) => (
+        match $submac!($i, $($args)*) {
+            Err(nom::Err::Error(e)) => Err(nom::Err::Failure(e)),
+            result => result,
+        }
+    );
+    ($i:expr, $f:expr) => (cut!($i, call!($f)));
+);
+
+macro_rules! context (
+    ($i:expr, $context:expr, $submac:ident!( $($args:tt)* )) => ({
+        let input = $i;
+        match $submac!(input, $($args)*) {
+            Err(nom::Err::Error(e)) => Err(nom::Err::Error(nom::error::ParseError::add_context(input, $context, e))),
+            Err(nom::Err::Failure(e)) => Err(nom::Err::Failure(nom::error::ParseError::add_context(input, $context, e))),
+            result => result,
+        }
+    });
+);
 
+type ReadResult<'a, O> = IResult<&'a str, O, VerboseError<&'a str>>;
+
+/// A reader failure with enough position information to show the user where
+/// their source went wrong.
 #[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// 1-based line of the offending character.
+    pub line: usize,
+    /// 1-based column, counted in characters rather than bytes.
+    pub column: usize,
+    /// Byte offset into the input.
+    pub offset: usize,
+    /// What the reader was looking for, e.g. "`)`" or "expression".
+    pub expected: String,
+    /// The offending source line with a caret under the error position.
+    pub snippet: String,
+    /// Set when the input ended in the middle of a datum, so that reading
+    /// more input could make it parse.
+    pub incomplete: bool,
+}
+
+impl ParseError {
+    fn new(source: &str, offset: usize, expected: String, incomplete: bool) -> ParseError {
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        let column = before[line_start..].chars().count() + 1;
+        let snippet = format!(
+            "{}\n{}^",
+            &source[line_start..line_end],
+            " ".repeat(column - 1)
+        );
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column,
+            offset,
+            expected,
+            snippet,
+            incomplete,
+        }
+    }
+
+    fn from_nom(source: &str, err: Err<VerboseError<&str>>) -> ParseError {
+        let errors = match err {
+            Err::Error(e) | Err::Failure(e) => e.errors,
+            Err::Incomplete(_) => vec![],
+        };
+        let position = |rest: &str| source.len() - rest.len();
+        let offset = errors
+            .first()
+            .map_or(source.len(), |(rest, _)| position(rest));
+        // Describe the innermost thing that was expected at the failure
+        // point, or failing that the construct it happened inside.
+        let describe = |kind: &VerboseErrorKind| match kind {
+            VerboseErrorKind::Char(c) => Some(format!("`{}`", c)),
+            VerboseErrorKind::Context(context) => Some((*context).to_owned()),
+            VerboseErrorKind::Nom(_) => None,
+        };
+        let expected = errors
+            .iter()
+            .take_while(|(rest, _)| position(rest) == offset)
+            .find_map(|(_, kind)| describe(kind))
+            .or_else(|| errors.iter().find_map(|(_, kind)| describe(kind)))
+            .unwrap_or_else(|| "expression".to_owned());
+        if offset < source.len() {
+            return ParseError::new(source, offset, expected, false);
+        }
+        // Running out of input is best reported at whatever was left open.
+        match errors.iter().find(|(rest, kind)| {
+            position(rest) < offset && matches!(kind, VerboseErrorKind::Context(_))
+        }) {
+            Some((rest, VerboseErrorKind::Context(context))) => ParseError::new(
+                source,
+                position(rest),
+                format!("{} before end of input to close {}", expected, context),
+                true,
+            ),
+            _ => ParseError::new(
+                source,
+                offset,
+                format!("{} before end of input", expected),
+                true,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Parse error at line {}, column {}: expected {}\n{}",
+            self.line, self.column, self.expected, self.snippet
+        )
+    }
+}
+
+// `PartialEq` is implemented in `pair.rs`, so that comparing circular data
+// terminates.
+#[derive(Clone, Debug)]
 pub enum LispVal {
-    Atom(String),
-    List(Vec<LispVal>),
-    DottedList(Vec<LispVal>, Box<LispVal>),
-    Number(u64),
-    String(String),
+    Atom(Symbol),
+    Pair(Rc<Pair>),
+    Nil,
+    Number(Number),
+    Char(char),
     Boolean(bool),
+    // Strings, vectors and bytevectors are shared like pairs, so
+    // `string-set!` or `vector-set!` through one copy is seen through every
+    // other.
+    // Strings hold their characters rather than UTF-8, so that `string-ref`
+    // and `string-set!` take constant time.
+    String(Rc<RefCell<Vec<char>>>),
+    Vector(Rc<RefCell<Vec<LispVal>>>),
+    Bytevector(Rc<RefCell<Vec<u8>>>),
+    Record(Rc<Record>),
+    RecordType(Rc<RecordType>),
+    PrimitiveFunc(Primitive),
+    NativeFunc(Rc<NativeFunc>),
+    Func(Rc<Lambda>),
+    Continuation(Rc<Continuation>),
+    Closure(Rc<Closure>),
+    VmContinuation(Rc<vm::Continuation>),
+    Macro(Rc<Macro>),
+    Error(Rc<LispError>),
+    // What `values` returns for any number of values but one. Only
+    // `call-with-values` takes it apart.
+    Values(Rc<Vec<LispVal>>),
+    Port(Rc<Port>),
+    // What the input procedures return at the end of their input.
+    Eof,
+    Unspecified,
+}
+
+impl LispVal {
+    pub fn string(s: String) -> LispVal {
+        LispVal::String(Rc::new(RefCell::new(s.chars().collect())))
+    }
+
+    pub fn vector(items: Vec<LispVal>) -> LispVal {
+        LispVal::Vector(gc::alloc(RefCell::new(items)))
+    }
+
+    pub fn bytevector(bytes: Vec<u8>) -> LispVal {
+        LispVal::Bytevector(Rc::new(RefCell::new(bytes)))
+    }
 }
 
-fn match_symbols(input: String) -> LispVal {
-    match input.as_str() {
+/// An exact integer, the value the tests use most.
+#[cfg(test)]
+pub(crate) fn int(n: i64) -> LispVal {
+    LispVal::Number(Number::Integer(n))
+}
+
+pub type PrimitiveFn = fn(&[LispVal]) -> Result<LispVal, LispError>;
+
+#[derive(Clone, Copy)]
+pub struct Primitive {
+    pub name: &'static str,
+    pub func: PrimitiveFn,
+}
+
+impl PartialEq for Primitive {
+    fn eq(&self, other: &Primitive) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<primitive {}>", self.name)
+    }
+}
+
+fn match_symbols(input: &str) -> LispVal {
+    match input {
         "#t" => LispVal::Boolean(true),
         "#f" => LispVal::Boolean(false),
-        _ => LispVal::Atom(input),
+        _ => LispVal::Atom(Symbol::intern(input)),
+    }
+}
+
+// A symbol may start with `.` as long as it is not a lone `.`, as in `...`.
+fn dot_initial(input: &str) -> ReadResult<'_, &str> {
+    let mut chars = input.chars();
+    if chars.next() == Some('.') && chars.next().is_some_and(|c| !is_delimiter(c)) {
+        Ok((&input[1..], &input[..1]))
+    } else {
+        Err(nom::Err::Error(nom::error::ParseError::from_char(
+            input, '.',
+        )))
     }
 }
 
-named!(parse_atom<&str, LispVal>, do_parse!(
-        first: alt!(alpha1 | is_a!("!#$%&|*+-/:<=>?@^_~")) >>
-        rest: many0!(complete!(alt!(alphanumeric1 | is_a!("!#$%&|*+-/:<=>?@^_~")))) >>
-        (match_symbols(format!("{}{}", String::from(first), (String::from_iter(rest)))))
+// The symbol is interned straight from the input slice, without building an
+// intermediate `String`.
+named!(parse_atom<&str, LispVal, VerboseError<&str>>, map!(
+        recognize!(pair!(
+            alt!(alpha1 | call!(is_a("!#$%&*+-/:<=>?@^_~")) | dot_initial),
+            many0_count!(alt!(alphanumeric1 | call!(is_a("!#$%&*+-./:<=>?@^_~"))))
+        )),
+        match_symbols
+));
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()\";'`,|".contains(c)
+}
+
+fn token(input: &str) -> ReadResult<'_, &str> {
+    take_till1(is_delimiter)(input)
+}
+
+named!(parse_number<&str, LispVal, VerboseError<&str>>, map_opt!(
+        token,
+        |text| number::parse_number(text, 10).map(LispVal::Number)
+));
+
+named!(hex_scalar_value<&str, char, VerboseError<&str>>, map_opt!(
+        call!(hex_digit1),
+        |digits| u32::from_str_radix(digits, 16).ok().and_then(std::char::from_u32)
+));
+
+named!(line_continuation<&str, (), VerboseError<&str>>, do_parse!(
+        space0 >>
+        line_ending >>
+        space0 >>
+        ()
+));
+
+// The part of a string escape after the backslash. A line continuation
+// stands for no character at all.
+named!(string_escape<&str, Option<char>, VerboseError<&str>>, context!("string escape", alt!(
+        value!(Some('\u{7}'), call!(char('a'))) |
+        value!(Some('\u{8}'), call!(char('b'))) |
+        value!(Some('\t'), call!(char('t'))) |
+        value!(Some('\n'), call!(char('n'))) |
+        value!(Some('\r'), call!(char('r'))) |
+        value!(Some('"'), call!(char('"'))) |
+        value!(Some('\\'), call!(char('\\'))) |
+        value!(Some('|'), call!(char('|'))) |
+        map!(delimited!(call!(char('x')), hex_scalar_value, call!(char(';'))), Some) |
+        value!(None, line_continuation)
+)));
+
+named!(string_char<&str, Option<char>, VerboseError<&str>>, alt!(
+        map!(call!(none_of("\\\"")), Some) |
+        preceded!(call!(char('\\')), cut!(string_escape))
 ));
 
-named!(parse_number<&str, LispVal>, do_parse!(
-        number: many1!(digit1) >>
-        (LispVal::Number(number.join("").parse::<u64>().unwrap()))
+named!(symbol_char<&str, Option<char>, VerboseError<&str>>, alt!(
+        map!(call!(none_of("\\|")), Some) |
+        preceded!(call!(char('\\')), cut!(string_escape))
 ));
 
+// `|...|` symbols may contain any character, so that every symbol has a
+// written form.
+named!(parse_bar_symbol<&str, LispVal, VerboseError<&str>>, context!("symbol", do_parse!(
+        call!(char('|')) >>
+        name: cut!(many0!(symbol_char)) >>
+        cut!(call!(char('|'))) >>
+        (LispVal::symbol(&name.into_iter().flatten().collect::<String>()))
+)));
+
 named!(
-    parse_string<&str, LispVal>,
-    do_parse!(
-        char!('\"') >>
-        value: many0!(none_of!("\"")) >> 
-        char!('\"') >>
-        (LispVal::String(String::from_iter(value)))
-    )
+    parse_string<&str, LispVal, VerboseError<&str>>,
+    context!("string", do_parse!(
+        call!(char('\"')) >>
+        value: cut!(many0!(string_char)) >>
+        cut!(call!(char('\"'))) >>
+        (LispVal::string(value.into_iter().flatten().collect()))
+    ))
 );
 
-named!(try_parse_list<&str, LispVal>, do_parse!(
-        char!('(') >>
-        items: alt!(parse_dotted_list | parse_list) >>
-        char!(')') >>
-        (items)
+/// The R7RS character names accepted after `#\\`, shared with the printer.
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+    ("newline", '\n'),
+    ("null", '\u{0}'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+];
+
+fn char_from_name(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        (Some('x'), Some(_)) => u32::from_str_radix(&name[1..], 16)
+            .ok()
+            .and_then(std::char::from_u32),
+        _ => CHAR_NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, c)| *c),
+    }
+}
+
+// Everything after `#\` up to the next delimiter names the character, but the
+// first character is taken unconditionally so that `#\(` and `#\ ` work.
+fn char_name(input: &str) -> ReadResult<'_, &str> {
+    match input.chars().next() {
+        Some(first) => {
+            let rest = &input[first.len_utf8()..];
+            let len = rest.find(is_delimiter).unwrap_or(rest.len());
+            let len = if is_delimiter(first) { 0 } else { len };
+            let end = first.len_utf8() + len;
+            Ok((&input[end..], &input[..end]))
+        }
+        None => Err(nom::Err::Error(nom::error::ParseError::from_error_kind(
+            input,
+            nom::error::ErrorKind::Eof,
+        ))),
+    }
+}
+
+named!(parse_char<&str, LispVal, VerboseError<&str>>, context!("character", do_parse!(
+        call!(tag("#\\")) >>
+        c: cut!(map_opt!(char_name, char_from_name)) >>
+        (LispVal::Char(c))
+)));
+
+// The dot of a dotted list is a token of its own, unlike the one in `.5`.
+fn dot(input: &str) -> ReadResult<'_, char> {
+    let mut chars = input.chars();
+    if chars.next() == Some('.') && chars.next().is_none_or(is_delimiter) {
+        Ok((&input[1..], '.'))
+    } else {
+        Err(nom::Err::Error(nom::error::ParseError::from_char(
+            input, '.',
+        )))
+    }
+}
+
+named!(line_comment<&str, &str, VerboseError<&str>>, recognize!(pair!(
+        call!(char(';')),
+        call!(take_till(|c| c == '\n'))
+)));
+
+// Block comments nest, so they are scanned by hand rather than with a
+// `take_until`.
+fn block_comment(input: &str) -> ReadResult<'_, &str> {
+    let (mut rest, _) = tag("#|")(input)?;
+    let mut depth = 1;
+    while depth > 0 {
+        if rest.starts_with("|#") {
+            depth -= 1;
+            rest = &rest[2..];
+        } else if rest.starts_with("#|") {
+            depth += 1;
+            rest = &rest[2..];
+        } else {
+            match rest.chars().next() {
+                Some(c) => rest = &rest[c.len_utf8()..],
+                None => {
+                    return Err(nom::Err::Failure(VerboseError {
+                        errors: vec![
+                            (rest, VerboseErrorKind::Context("`|#`")),
+                            (input, VerboseErrorKind::Context("block comment")),
+                        ],
+                    }))
+                }
+            }
+        }
+    }
+    Ok((rest, &input[..input.len() - rest.len()]))
+}
+
+named!(datum_comment<&str, &str, VerboseError<&str>>, recognize!(tuple!(
+        call!(tag("#;")),
+        ws,
+        cut!(parse_expr)
+)));
+
+// Intertoken space: whitespace and all three kinds of comment.
+named!(ws<&str, (), VerboseError<&str>>, map!(
+        many0!(alt!(multispace1 | line_comment | block_comment | datum_comment)),
+        |_| ()
 ));
 
-named!(parse_list<&str, LispVal>, do_parse!(
-        items: separated_list!(space1, parse_expr) >>
-        (LispVal::List(items))
+named!(try_parse_list<&str, LispVal, VerboseError<&str>>, context!("list", do_parse!(
+        call!(char('(')) >>
+        ws >>
+        items: parse_items >>
+        tail: cond!(!items.is_empty(), opt!(parse_dotted_tail)) >>
+        cut!(call!(char(')'))) >>
+        (match tail {
+            Some(Some(tail)) => LispVal::dotted_list(items, tail),
+            _ => LispVal::list(items),
+        })
+)));
+
+named!(parse_items<&str, Vec<LispVal>, VerboseError<&str>>, many0!(terminated!(parse_expr, ws)));
+
+named!(parse_list<&str, LispVal, VerboseError<&str>>, map!(parse_items, LispVal::list));
+
+named!(parse_vector<&str, LispVal, VerboseError<&str>>, context!("vector", do_parse!(
+        call!(tag("#(")) >>
+        ws >>
+        items: parse_items >>
+        cut!(call!(char(')'))) >>
+        (LispVal::vector(items))
+)));
+
+fn to_byte(text: &str) -> Option<u8> {
+    match number::parse_number(text, 10)? {
+        Number::Integer(n) if (0..=255).contains(&n) => Some(n as u8),
+        _ => None,
+    }
+}
+
+// Anything other than the closing paren must be a valid byte; checking here
+// rather than after the fact points the error at the offending element.
+named!(byte<&str, u8, VerboseError<&str>>, preceded!(
+        not!(alt!(call!(char(')')) | value!(' ', eof!()))),
+        cut!(context!("byte (an exact integer from 0 to 255)", map_opt!(token, to_byte)))
+));
+
+named!(parse_bytevector<&str, LispVal, VerboseError<&str>>, context!("bytevector", do_parse!(
+        call!(tag("#u8(")) >>
+        ws >>
+        bytes: many0!(terminated!(byte, ws)) >>
+        cut!(call!(char(')'))) >>
+        (LispVal::bytevector(bytes))
+)));
+
+fn quote_form(keyword: &str, expr: LispVal) -> LispVal {
+    LispVal::list(vec![LispVal::symbol(keyword), expr])
+}
+
+named!(parse_quoted<&str, LispVal, VerboseError<&str>>, do_parse!(
+        call!(char('\'')) >>
+        ws >>
+        expr: cut!(parse_expr) >>
+        (quote_form("quote", expr))
 ));
 
-named!(parse_quoted<&str, LispVal>, do_parse!(
-        char!('\'') >>
-        expr: parse_expr >>
-        (LispVal::List(vec![LispVal::Atom("quote".to_owned()), expr]))
+named!(parse_quasiquoted<&str, LispVal, VerboseError<&str>>, do_parse!(
+        call!(char('`')) >>
+        ws >>
+        expr: cut!(parse_expr) >>
+        (quote_form("quasiquote", expr))
 ));
 
-named!(dotted<&str, &str>, do_parse!(space0 >> char!('.') >> space0 >> (".")));
+named!(parse_unquote_spliced<&str, LispVal, VerboseError<&str>>, do_parse!(
+        call!(tag(",@")) >>
+        ws >>
+        expr: cut!(parse_expr) >>
+        (quote_form("unquote-splicing", expr))
+));
 
-named!(parse_dotted_list<&str, LispVal>, do_parse!(
-        exprs: separated_pair!(parse_list, dotted, parse_expr) >>
-        ({
-            let head = match exprs.0 {
-                LispVal::List(v) => v,
-                _ => panic!("List parser returned a non-list value")
-            };
-            LispVal::DottedList(head, Box::new(exprs.1))
-        })
+named!(parse_unquoted<&str, LispVal, VerboseError<&str>>, do_parse!(
+        call!(char(',')) >>
+        ws >>
+        expr: cut!(parse_expr) >>
+        (quote_form("unquote", expr))
 ));
 
-named!(parse_expr<&str, LispVal>, alt!(parse_atom | parse_number | parse_string | parse_quoted | try_parse_list));
+named!(parse_dotted_tail<&str, LispVal, VerboseError<&str>>, do_parse!(
+        dot >>
+        ws >>
+        tail: cut!(parse_expr) >>
+        ws >>
+        (tail)
+));
 
-pub fn parse_lisp_expr(input: &str) -> Result<(&str, LispVal), AppErr> {
-    parse_expr(input)
+named!(parse_expr<&str, LispVal, VerboseError<&str>>, context!("expression",
+        alt!(parse_number | parse_char | parse_vector | parse_bytevector | parse_atom | parse_bar_symbol |
+             parse_string |
+             parse_quoted | parse_quasiquoted | parse_unquote_spliced | parse_unquoted |
+             try_parse_list)
+));
+
+/// Reads one datum from the start of `input`, after any leading whitespace
+/// and comments, returning it along with the unread remainder.
+pub fn parse_lisp_expr(input: &str) -> Result<(&str, LispVal), ParseError> {
+    preceded!(input, ws, parse_expr).map_err(|err| ParseError::from_nom(input, err))
+}
+
+/// Reads every top-level datum in `input`, as found in a source file.
+pub fn parse_program(input: &str) -> Result<Vec<LispVal>, ParseError> {
+    let (rest, exprs) =
+        preceded!(input, ws, parse_items).map_err(|err| ParseError::from_nom(input, err))?;
+    if rest.is_empty() {
+        return Ok(exprs);
+    }
+    // Whatever stopped the reader is not a datum; reading it again yields
+    // the error describing why.
+    match parse_expr(rest) {
+        Err(err) => Err(ParseError::from_nom(input, err)),
+        Ok(_) => unreachable!("parse_items stopped before a readable datum"),
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +550,39 @@ mod tests {
     fn number_parser_test() {
         assert!(parse_number("j5").is_err());
         assert!(parse_number("jlsdf").is_err());
-        assert_eq!(parse_number("23").unwrap(), ("", LispVal::Number(23)));
+        assert_eq!(
+            parse_number("23").unwrap(),
+            ("", LispVal::Number(Number::Integer(23)))
+        );
+        assert_eq!(
+            parse_number("-1/2 x").unwrap().1,
+            LispVal::Number(number::parse_number("-1/2", 10).unwrap())
+        );
+        assert_eq!(
+            parse_number("#x-ff)").unwrap(),
+            (")", LispVal::Number(Number::Integer(-255)))
+        );
+        assert_eq!(
+            parse_number("1e2").unwrap(),
+            ("", LispVal::Number(Number::Real(100.0)))
+        );
+        assert!(parse_number("+").is_err());
+        assert!(parse_number("99999999999999999999999999").is_ok());
+    }
+
+    #[test]
+    fn expr_parser_test() {
+        assert_eq!(
+            parse_expr("(- 5)").unwrap().1,
+            LispVal::list(vec![
+                LispVal::symbol("-"),
+                LispVal::Number(Number::Integer(5))
+            ])
+        );
+        assert_eq!(
+            parse_expr("-5").unwrap().1,
+            LispVal::Number(Number::Integer(-5))
+        );
     }
 
     #[test]
@@ -98,17 +590,113 @@ mod tests {
         let output = parse_string("\"hello\"").unwrap();
         assert_eq!(
             output,
-            ("", LispVal::String(String::from_iter("hello".chars())))
+            ("", LispVal::string(String::from_iter("hello".chars())))
         );
     }
 
     #[test]
-    fn atom_parser_test() {
+    fn string_escape_test() {
+        let read = |input| match parse_string(input) {
+            Ok(("", LispVal::String(s))) => s.take().into_iter().collect::<String>(),
+            other => panic!("unexpected parse {:?}", other),
+        };
+        assert_eq!(read(r#""say \"hi\"""#), "say \"hi\"");
+        assert_eq!(read(r#""a\nb\tc\\d\r\a\b\|""#), "a\nb\tc\\d\r\u{7}\u{8}|");
+        assert_eq!(read(r#""\x41;\x3bb;""#), "A\u{3bb}");
+        assert_eq!(read("\"λ → ☃\""), "λ → ☃");
+        assert_eq!(read("\"one \\  \n    two\""), "one two");
+        assert_eq!(read("\"one \\\r\ntwo\""), "one two");
+        assert_eq!(read("\"multi\nline\""), "multi\nline");
+
+        let err = parse_lisp_expr(r#""bad \q escape""#).unwrap_err();
+        assert_eq!((err.column, err.expected.as_str()), (7, "string escape"));
+        assert!(parse_lisp_expr(r#""\x41""#).is_err());
+        assert!(parse_lisp_expr(r#""\xd800;""#).is_err());
+    }
+
+    #[test]
+    fn char_parser_test() {
+        let read = |input| parse_lisp_expr(input).unwrap();
+        assert_eq!(read("#\\a"), ("", LispVal::Char('a')));
+        assert_eq!(read("#\\A)"), (")", LispVal::Char('A')));
+        assert_eq!(read("#\\space"), ("", LispVal::Char(' ')));
+        assert_eq!(read("#\\newline"), ("", LispVal::Char('\n')));
+        assert_eq!(read("#\\alarm"), ("", LispVal::Char('\u{7}')));
+        assert_eq!(read("#\\x"), ("", LispVal::Char('x')));
+        assert_eq!(read("#\\x3bb"), ("", LispVal::Char('λ')));
+        assert_eq!(read("#\\λ"), ("", LispVal::Char('λ')));
+        assert_eq!(read("#\\("), ("", LispVal::Char('(')));
+        assert_eq!(read("#\\ "), ("", LispVal::Char(' ')));
+        assert_eq!(read("#\\;"), ("", LispVal::Char(';')));
         assert_eq!(
-            parse_atom("$foo").unwrap(),
-            ("", LispVal::Atom(String::from_iter("$foo".chars())))
+            read("(#\\a #\\))").1,
+            LispVal::list(vec![LispVal::Char('a'), LispVal::Char(')')])
         );
+
+        let err = parse_lisp_expr("#\\bogus").unwrap_err();
+        assert_eq!((err.column, err.expected.as_str()), (3, "character"));
+        assert!(parse_lisp_expr("#\\xd800").is_err());
+        assert!(parse_lisp_expr("#\\").unwrap_err().incomplete);
+    }
+
+    #[test]
+    fn vector_parser_test() {
+        assert_eq!(
+            parse_lisp_expr("#(1 \"two\" (3) #(4))").unwrap().1,
+            LispVal::vector(vec![
+                LispVal::Number(Number::Integer(1)),
+                LispVal::string("two".to_owned()),
+                LispVal::list(vec![LispVal::Number(Number::Integer(3))]),
+                LispVal::vector(vec![LispVal::Number(Number::Integer(4))]),
+            ])
+        );
+        assert_eq!(parse_lisp_expr("#( )").unwrap().1, LispVal::vector(vec![]));
+        assert!(parse_lisp_expr("#(1 2").unwrap_err().incomplete);
+        assert!(parse_lisp_expr("#(1 . 2)").is_err());
+    }
+
+    #[test]
+    fn bytevector_parser_test() {
+        assert_eq!(
+            parse_lisp_expr("#u8(0 #xff\n 16)").unwrap().1,
+            LispVal::bytevector(vec![0, 255, 16])
+        );
+        assert_eq!(
+            parse_lisp_expr("#u8()").unwrap().1,
+            LispVal::bytevector(vec![])
+        );
+
+        let err = parse_lisp_expr("#u8(1 256 3)").unwrap_err();
+        assert_eq!(err.column, 7);
+        assert_eq!(err.expected, "byte (an exact integer from 0 to 255)");
+        for bad in &["#u8(-1)", "#u8(1.0)", "#u8(a)", "#u8(\"x\")"] {
+            assert_eq!(parse_lisp_expr(bad).unwrap_err().column, 5);
+        }
+        let err = parse_lisp_expr("#u8(1 2").unwrap_err();
+        assert!(err.incomplete);
+        assert_eq!(err.expected, "`)` before end of input to close bytevector");
+    }
+
+    #[test]
+    fn atom_parser_test() {
+        assert_eq!(parse_atom("$foo").unwrap(), ("", LispVal::symbol("$foo")));
         assert_eq!(parse_atom("#f").unwrap(), ("", LispVal::Boolean(false)));
+        for name in &["...", ".foo", "a.b", "->x", "x->y"] {
+            assert_eq!(parse_lisp_expr(name).unwrap(), ("", LispVal::symbol(name)));
+        }
+        assert_eq!(
+            parse_lisp_expr("|hello world|").unwrap(),
+            ("", LispVal::symbol("hello world"))
+        );
+        assert_eq!(
+            parse_lisp_expr(r"|a\|b\x41;|").unwrap(),
+            ("", LispVal::symbol("a|bA"))
+        );
+        assert_eq!(
+            parse_lisp_expr("a|b|").unwrap(),
+            ("|b|", LispVal::symbol("a"))
+        );
+        assert!(parse_lisp_expr("|open").unwrap_err().incomplete);
     }
 
     #[test]
@@ -117,10 +705,10 @@ mod tests {
             parse_list("$foo 42 53").unwrap(),
             (
                 "",
-                LispVal::List(vec!(
-                    LispVal::Atom("$foo".to_owned()),
-                    LispVal::Number(42),
-                    LispVal::Number(53)
+                LispVal::list(vec!(
+                    LispVal::symbol("$foo"),
+                    LispVal::Number(Number::Integer(42)),
+                    LispVal::Number(Number::Integer(53))
                 ))
             )
         );
@@ -128,15 +716,160 @@ mod tests {
             parse_list("\"foo\" 42 53").unwrap(),
             (
                 "",
-                LispVal::List(vec!(
-                    LispVal::String("foo".to_owned()),
-                    LispVal::Number(42),
-                    LispVal::Number(53)
+                LispVal::list(vec!(
+                    LispVal::string("foo".to_owned()),
+                    LispVal::Number(Number::Integer(42)),
+                    LispVal::Number(Number::Integer(53))
                 ))
             )
         );
     }
 
+    #[test]
+    fn quasiquote_parser_test() {
+        let atom = |name: &str| LispVal::symbol(name);
+        assert_eq!(
+            parse_lisp_expr("`(a ,b ,@c)").unwrap().1,
+            quote_form(
+                "quasiquote",
+                LispVal::list(vec![
+                    atom("a"),
+                    quote_form("unquote", atom("b")),
+                    quote_form("unquote-splicing", atom("c")),
+                ])
+            )
+        );
+        assert_eq!(
+            parse_lisp_expr("`(1 ,(+ 1 1) , @x)").unwrap().1,
+            quote_form(
+                "quasiquote",
+                LispVal::list(vec![
+                    LispVal::Number(Number::Integer(1)),
+                    quote_form(
+                        "unquote",
+                        LispVal::list(vec![
+                            atom("+"),
+                            LispVal::Number(Number::Integer(1)),
+                            LispVal::Number(Number::Integer(1)),
+                        ])
+                    ),
+                    quote_form("unquote", atom("@x")),
+                ])
+            )
+        );
+        assert_eq!(
+            parse_lisp_expr("``,,x").unwrap().1,
+            quote_form(
+                "quasiquote",
+                quote_form(
+                    "quasiquote",
+                    quote_form("unquote", quote_form("unquote", atom("x")))
+                )
+            )
+        );
+        assert_eq!(
+            parse_lisp_expr("(a,b)").unwrap().1,
+            LispVal::list(vec![atom("a"), quote_form("unquote", atom("b"))])
+        );
+        assert!(parse_lisp_expr(",@").unwrap_err().incomplete);
+    }
+
+    #[test]
+    fn parse_error_position_test() {
+        let err = parse_lisp_expr("(a b").unwrap_err();
+        assert_eq!((err.line, err.column, err.offset), (1, 1, 0));
+        assert!(err.incomplete);
+        assert_eq!(err.expected, "`)` before end of input to close list");
+
+        let err = parse_lisp_expr("(define x \"unterminated)").unwrap_err();
+        assert_eq!((err.line, err.column, err.offset), (1, 11, 10));
+        assert!(err.incomplete);
+        assert_eq!(err.snippet, "(define x \"unterminated)\n          ^");
+
+        let err = parse_lisp_expr("(a (b \"x\n c\" ]").unwrap_err();
+        assert_eq!((err.line, err.column, err.offset), (2, 5, 13));
+        assert!(!err.incomplete);
+        assert_eq!(err.expected, "`)`");
+        assert_eq!(
+            err.to_string(),
+            "Parse error at line 2, column 5: expected `)`\n c\" ]\n    ^"
+        );
+    }
+
+    #[test]
+    fn whitespace_and_comment_test() {
+        let expected = LispVal::list(vec![LispVal::symbol("a"), LispVal::symbol("b")]);
+        assert_eq!(parse_lisp_expr("(a\n\tb)").unwrap().1, expected);
+        assert_eq!(parse_lisp_expr("(  a b  )").unwrap().1, expected);
+        assert_eq!(parse_lisp_expr("(a ; one\n b)").unwrap().1, expected);
+        assert_eq!(
+            parse_lisp_expr("(a #| x #| nested |# y |# b)").unwrap().1,
+            expected
+        );
+        assert_eq!(parse_lisp_expr("(a #;(c d) b)").unwrap().1, expected);
+        assert_eq!(parse_lisp_expr("(a #; c b)").unwrap().1, expected);
+        assert_eq!(
+            parse_lisp_expr("; leading\n  (a b) rest").unwrap(),
+            (" rest", expected)
+        );
+        assert_eq!(
+            parse_lisp_expr("(a\n . b )").unwrap().1,
+            LispVal::cons(LispVal::symbol("a"), LispVal::symbol("b"))
+        );
+        assert_eq!(parse_lisp_expr("()").unwrap().1, LispVal::Nil);
+        assert_eq!(parse_lisp_expr("( )").unwrap().1, LispVal::Nil);
+    }
+
+    #[test]
+    fn program_parser_test() {
+        let program =
+            "; a program\n(define x 1)\n\n#| block\n comment |#\n(display x) #;(ignored)\n";
+        assert_eq!(
+            parse_program(program).unwrap(),
+            vec![
+                LispVal::list(vec![
+                    LispVal::symbol("define"),
+                    LispVal::symbol("x"),
+                    LispVal::Number(Number::Integer(1)),
+                ]),
+                LispVal::list(vec![LispVal::symbol("display"), LispVal::symbol("x"),]),
+            ]
+        );
+        assert_eq!(parse_program("").unwrap(), vec![]);
+        assert_eq!(parse_program("  ; only a comment").unwrap(), vec![]);
+
+        let err = parse_program("(a)\n(b))").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert!(!err.incomplete);
+
+        let err = parse_program("(a)\n#| never closed").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert!(err.incomplete);
+        assert_eq!(
+            err.expected,
+            "`|#` before end of input to close block comment"
+        );
+
+        let err = parse_program("(a . )").unwrap_err();
+        assert_eq!(err.column, 6);
+        assert!(parse_program("(. a)").is_err());
+        assert!(parse_program("(a . b c)").is_err());
+    }
+
+    #[test]
+    fn parse_error_unexpected_test() {
+        let err = parse_lisp_expr(")").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        assert_eq!(err.expected, "expression");
+        assert!(!err.incomplete);
+
+        let err = parse_lisp_expr("").unwrap_err();
+        assert!(err.incomplete);
+
+        let err = parse_lisp_expr("'").unwrap_err();
+        assert!(err.incomplete);
+    }
+
     #[test]
     fn quoted_parser_test() {
         let output = parse_quoted("'52").unwrap();
@@ -144,7 +877,10 @@ mod tests {
             output,
             (
                 "",
-                LispVal::List(vec![LispVal::Atom("quote".to_owned()), LispVal::Number(52)])
+                LispVal::list(vec![
+                    LispVal::symbol("quote"),
+                    LispVal::Number(Number::Integer(52))
+                ])
             )
         )
     }
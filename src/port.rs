@@ -0,0 +1,210 @@
+use crate::error::LispError;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+/// A port made by `open-input-string` and the other port procedures, or one
+/// of the standard ports. A port reads or writes either characters or bytes,
+/// never both. Closing a port stops it being read or written, but it is still
+/// a port of the same kind.
+pub struct Port {
+    data: RefCell<Data>,
+    open: Cell<bool>,
+}
+
+enum Data {
+    /// The characters still to read. Standard input is read into it a line
+    /// at a time, as they are needed.
+    TextIn {
+        chars: VecDeque<char>,
+        stdin: bool,
+    },
+    BytesIn(VecDeque<u8>),
+    TextOut(String),
+    BytesOut(Vec<u8>),
+    Stdout,
+    Stderr,
+}
+
+fn io_error(err: io::Error) -> LispError {
+    LispError::Default(err.to_string())
+}
+
+impl Port {
+    fn new(data: Data) -> Rc<Port> {
+        Rc::new(Port {
+            data: RefCell::new(data),
+            open: Cell::new(true),
+        })
+    }
+
+    pub fn input_string(text: &str) -> Rc<Port> {
+        Port::new(Data::TextIn {
+            chars: text.chars().collect(),
+            stdin: false,
+        })
+    }
+
+    pub fn input_bytes(bytes: &[u8]) -> Rc<Port> {
+        Port::new(Data::BytesIn(bytes.iter().copied().collect()))
+    }
+
+    pub fn output_string() -> Rc<Port> {
+        Port::new(Data::TextOut(String::new()))
+    }
+
+    pub fn output_bytes() -> Rc<Port> {
+        Port::new(Data::BytesOut(vec![]))
+    }
+
+    pub fn stdin() -> Rc<Port> {
+        Port::new(Data::TextIn {
+            chars: VecDeque::new(),
+            stdin: true,
+        })
+    }
+
+    pub fn stdout() -> Rc<Port> {
+        Port::new(Data::Stdout)
+    }
+
+    pub fn stderr() -> Rc<Port> {
+        Port::new(Data::Stderr)
+    }
+
+    pub fn is_input(&self) -> bool {
+        matches!(*self.data.borrow(), Data::TextIn { .. } | Data::BytesIn(_))
+    }
+
+    pub fn is_textual(&self) -> bool {
+        !matches!(*self.data.borrow(), Data::BytesIn(_) | Data::BytesOut(_))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open.get()
+    }
+
+    pub fn close(&self) {
+        self.open.set(false);
+    }
+
+    /// Reads from standard input until there is a character to read or the
+    /// input ends.
+    fn fill(chars: &mut VecDeque<char>, stdin: bool) -> Result<(), LispError> {
+        if stdin && chars.is_empty() {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line).map_err(io_error)?;
+            chars.extend(line.chars());
+        }
+        Ok(())
+    }
+
+    /// Takes the next character if `consume` is set, or only looks at it.
+    /// `None` is the end of the input.
+    pub(crate) fn read_char(&self, consume: bool) -> Result<Option<char>, LispError> {
+        match &mut *self.data.borrow_mut() {
+            Data::TextIn { chars, stdin } => {
+                Port::fill(chars, *stdin)?;
+                Ok(if consume {
+                    chars.pop_front()
+                } else {
+                    chars.front().copied()
+                })
+            }
+            _ => unreachable!("not a textual input port"),
+        }
+    }
+
+    /// Reads up to `limit` characters, stopping after `end` if it is given
+    /// and read. `None` is the end of the input, before anything was read.
+    pub(crate) fn read_until(
+        &self,
+        limit: usize,
+        end: Option<char>,
+    ) -> Result<Option<String>, LispError> {
+        let mut text = String::new();
+        let mut read = 0;
+        while read < limit {
+            match self.read_char(true)? {
+                Some(c) => {
+                    read += 1;
+                    text.push(c);
+                    if Some(c) == end {
+                        break;
+                    }
+                }
+                None if read == 0 => return Ok(None),
+                None => break,
+            }
+        }
+        Ok(Some(text))
+    }
+
+    /// Whether a character can be read without waiting for more input.
+    pub(crate) fn char_ready(&self) -> bool {
+        match &*self.data.borrow() {
+            Data::TextIn { chars, stdin } => !stdin || !chars.is_empty(),
+            _ => unreachable!("not a textual input port"),
+        }
+    }
+
+    /// Takes the next byte if `consume` is set, or only looks at it. `None`
+    /// is the end of the input.
+    pub(crate) fn read_u8(&self, consume: bool) -> Option<u8> {
+        match &mut *self.data.borrow_mut() {
+            Data::BytesIn(bytes) if consume => bytes.pop_front(),
+            Data::BytesIn(bytes) => bytes.front().copied(),
+            _ => unreachable!("not a binary input port"),
+        }
+    }
+
+    pub(crate) fn write_str(&self, text: &str) -> Result<(), LispError> {
+        match &mut *self.data.borrow_mut() {
+            Data::TextOut(out) => out.push_str(text),
+            Data::Stdout => io::stdout().write_all(text.as_bytes()).map_err(io_error)?,
+            Data::Stderr => io::stderr().write_all(text.as_bytes()).map_err(io_error)?,
+            _ => unreachable!("not a textual output port"),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_bytes(&self, bytes: &[u8]) {
+        match &mut *self.data.borrow_mut() {
+            Data::BytesOut(out) => out.extend_from_slice(bytes),
+            _ => unreachable!("not a binary output port"),
+        }
+    }
+
+    pub(crate) fn flush(&self) -> Result<(), LispError> {
+        match &*self.data.borrow() {
+            Data::Stdout => io::stdout().flush().map_err(io_error),
+            Data::Stderr => io::stderr().flush().map_err(io_error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Everything written so far to a port made by `open-output-string`.
+    pub fn output_text(&self) -> Option<String> {
+        match &*self.data.borrow() {
+            Data::TextOut(out) => Some(out.clone()),
+            _ => None,
+        }
+    }
+
+    /// Everything written so far to a port made by `open-output-bytevector`.
+    pub fn output_bytevector(&self) -> Option<Vec<u8>> {
+        match &*self.data.borrow() {
+            Data::BytesOut(out) => Some(out.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = if self.is_input() { "input" } else { "output" };
+        write!(f, "<{}-port {:p}>", direction, self)
+    }
+}
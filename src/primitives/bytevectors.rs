@@ -0,0 +1,283 @@
+use super::strings::string_range;
+use super::{out_of_range, predicate, unpack_index, unpack_range, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::{LispVal, PrimitiveFn};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("bytevector?", is_bytevector),
+    ("make-bytevector", make_bytevector),
+    ("bytevector", bytevector),
+    ("bytevector-length", bytevector_length),
+    ("bytevector-u8-ref", bytevector_u8_ref),
+    ("bytevector-u8-set!", bytevector_u8_set),
+    ("bytevector-copy", bytevector_copy),
+    ("bytevector-copy!", bytevector_copy_to),
+    ("bytevector-append", bytevector_append),
+    ("utf8->string", utf8_to_string),
+    ("string->utf8", string_to_utf8),
+];
+
+type Bytes = Rc<RefCell<Vec<u8>>>;
+
+pub(super) fn unpack_bytevector(value: &LispVal) -> Result<&Bytes, LispError> {
+    match value {
+        LispVal::Bytevector(bytes) => Ok(bytes),
+        _ => Err(LispError::TypeMismatch("bytevector", value.clone())),
+    }
+}
+
+pub(super) fn unpack_byte(value: &LispVal) -> Result<u8, LispError> {
+    match value {
+        LispVal::Number(Number::Integer(n)) if (0..=255).contains(n) => Ok(*n as u8),
+        _ => Err(LispError::TypeMismatch("byte", value.clone())),
+    }
+}
+
+/// The bytes of a bytevector between the optional `start` and `end`
+/// arguments that follow it.
+fn bytevector_range(bytevector: &LispVal, range: &[LispVal]) -> Result<Vec<u8>, LispError> {
+    let bytes = unpack_bytevector(bytevector)?.borrow();
+    let (start, end) = unpack_range(range, bytes.len())?;
+    Ok(bytes[start..end].to_vec())
+}
+
+fn is_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Bytevector(_)))
+}
+
+fn make_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [k, fill @ ..] if fill.len() <= 1 => {
+            let fill = fill.first().map(unpack_byte).transpose()?.unwrap_or(0);
+            Ok(LispVal::bytevector(vec![fill; unpack_index(k)?]))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn bytevector(args: &[LispVal]) -> PrimitiveResult {
+    let bytes = args.iter().map(unpack_byte).collect::<Result<_, _>>()?;
+    Ok(LispVal::bytevector(bytes))
+}
+
+fn bytevector_length(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv] => Ok(LispVal::Number(Number::Integer(
+            unpack_bytevector(bv)?.borrow().len() as i64,
+        ))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn bytevector_u8_ref(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv, k] => {
+            let (bytes, k) = (unpack_bytevector(bv)?.borrow(), unpack_index(k)?);
+            match bytes.get(k) {
+                Some(byte) => Ok(LispVal::Number(Number::Integer(*byte as i64))),
+                None => Err(out_of_range(k, bytes.len())),
+            }
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn bytevector_u8_set(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv, k, byte] => {
+            let byte = unpack_byte(byte)?;
+            let (mut bytes, k) = (unpack_bytevector(bv)?.borrow_mut(), unpack_index(k)?);
+            let len = bytes.len();
+            match bytes.get_mut(k) {
+                Some(slot) => *slot = byte,
+                None => return Err(out_of_range(k, len)),
+            }
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn bytevector_copy(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv, range @ ..] if range.len() <= 2 => {
+            Ok(LispVal::bytevector(bytevector_range(bv, range)?))
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+/// `(bytevector-copy! to at from [start [end]])` copies bytes of `from` into
+/// `to` starting at index `at`, like `vector-copy!`.
+fn bytevector_copy_to(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [to, at, from, range @ ..] if range.len() <= 2 => {
+            let (to, at) = (unpack_bytevector(to)?, unpack_index(at)?);
+            let copied = bytevector_range(from, range)?;
+            let mut bytes = to.borrow_mut();
+            if at > bytes.len() || copied.len() > bytes.len() - at {
+                return Err(out_of_range(at + copied.len(), bytes.len()));
+            }
+            bytes[at..at + copied.len()].copy_from_slice(&copied);
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(5, args.to_vec())),
+    }
+}
+
+fn bytevector_append(args: &[LispVal]) -> PrimitiveResult {
+    let mut result = vec![];
+    for arg in args {
+        result.extend_from_slice(&unpack_bytevector(arg)?.borrow());
+    }
+    Ok(LispVal::bytevector(result))
+}
+
+fn utf8_to_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv, range @ ..] if range.len() <= 2 => {
+            match String::from_utf8(bytevector_range(bv, range)?) {
+                Ok(s) => Ok(LispVal::string(s)),
+                Err(_) => Err(LispError::TypeMismatch("UTF-8 bytevector", bv.clone())),
+            }
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn string_to_utf8(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, range @ ..] if range.len() <= 2 => {
+            let chars = string_range(s, range)?;
+            Ok(LispVal::bytevector(
+                chars.into_iter().collect::<String>().into_bytes(),
+            ))
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::bytevectors::*;
+
+    fn bytes(bytes: &[u8]) -> LispVal {
+        LispVal::bytevector(bytes.to_vec())
+    }
+
+    fn text(s: &str) -> LispVal {
+        LispVal::string(s.to_owned())
+    }
+
+    #[test]
+    fn is_bytevector_test() {
+        assert_eq!(
+            is_bytevector(&[bytes(&[])]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            is_bytevector(&[LispVal::vector(vec![])]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn make_bytevector_test() {
+        assert_eq!(make_bytevector(&[int(2), int(7)]).unwrap(), bytes(&[7, 7]));
+        assert_eq!(make_bytevector(&[int(2)]).unwrap(), bytes(&[0, 0]));
+        assert!(make_bytevector(&[int(2), int(256)]).is_err());
+        assert!(make_bytevector(&[int(-1)]).is_err());
+    }
+
+    #[test]
+    fn bytevector_test() {
+        assert_eq!(bytevector(&[int(1), int(255)]).unwrap(), bytes(&[1, 255]));
+        assert_eq!(bytevector(&[]).unwrap(), bytes(&[]));
+        assert!(bytevector(&[int(-1)]).is_err());
+    }
+
+    #[test]
+    fn bytevector_length_test() {
+        assert_eq!(bytevector_length(&[bytes(&[1, 2, 3])]).unwrap(), int(3));
+        assert!(bytevector_length(&[LispVal::vector(vec![])]).is_err());
+    }
+
+    #[test]
+    fn bytevector_u8_ref_test() {
+        let bv = bytes(&[1, 2, 3]);
+        assert_eq!(bytevector_u8_ref(&[bv.clone(), int(1)]).unwrap(), int(2));
+        assert!(bytevector_u8_ref(&[bv, int(3)]).is_err());
+    }
+
+    #[test]
+    fn bytevector_u8_set_test() {
+        let bv = bytes(&[1, 2, 3]);
+        let alias = bv.clone();
+        bytevector_u8_set(&[bv.clone(), int(0), int(9)]).unwrap();
+        assert_eq!(alias, bytes(&[9, 2, 3]));
+        assert!(bytevector_u8_set(&[bv.clone(), int(3), int(9)]).is_err());
+        assert!(bytevector_u8_set(&[bv, int(0), int(300)]).is_err());
+    }
+
+    #[test]
+    fn bytevector_copy_test() {
+        let bv = bytes(&[1, 2, 3, 4, 5]);
+        let copy = bytevector_copy(std::slice::from_ref(&bv)).unwrap();
+        assert_eq!(copy, bv);
+        bytevector_u8_set(&[copy, int(0), int(0)]).unwrap();
+        assert_eq!(bv, bytes(&[1, 2, 3, 4, 5]));
+        assert_eq!(
+            bytevector_copy(&[bv.clone(), int(1), int(3)]).unwrap(),
+            bytes(&[2, 3])
+        );
+        assert!(bytevector_copy(&[bv, int(3), int(1)]).is_err());
+    }
+
+    #[test]
+    fn bytevector_copy_to_test() {
+        let bv = bytes(&[1, 2, 3, 4, 5]);
+        bytevector_copy_to(&[bv.clone(), int(1), bv.clone(), int(0), int(3)]).unwrap();
+        assert_eq!(bv, bytes(&[1, 1, 2, 3, 5]));
+        bytevector_copy_to(&[bv.clone(), int(3), bytes(&[8, 9])]).unwrap();
+        assert_eq!(bv, bytes(&[1, 1, 2, 8, 9]));
+        assert!(bytevector_copy_to(&[bv, int(4), bytes(&[1, 2])]).is_err());
+    }
+
+    #[test]
+    fn bytevector_append_test() {
+        assert_eq!(
+            bytevector_append(&[bytes(&[1]), bytes(&[]), bytes(&[2, 3])]).unwrap(),
+            bytes(&[1, 2, 3])
+        );
+        assert_eq!(bytevector_append(&[]).unwrap(), bytes(&[]));
+        assert!(bytevector_append(&[bytes(&[1]), int(2)]).is_err());
+    }
+
+    #[test]
+    fn utf8_to_string_test() {
+        assert_eq!(
+            utf8_to_string(&[bytes(&[0xce, 0xbb, b'x'])]).unwrap(),
+            text("λx")
+        );
+        assert_eq!(
+            utf8_to_string(&[bytes(&[0xce, 0xbb, b'x']), int(2)]).unwrap(),
+            text("x")
+        );
+        assert!(utf8_to_string(&[bytes(&[0xce])]).is_err());
+    }
+
+    #[test]
+    fn string_to_utf8_test() {
+        assert_eq!(
+            string_to_utf8(&[text("λx")]).unwrap(),
+            bytes(&[0xce, 0xbb, b'x'])
+        );
+        assert_eq!(string_to_utf8(&[text("λx"), int(1)]).unwrap(), bytes(b"x"));
+        assert!(string_to_utf8(&[bytes(&[])]).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use super::{predicate, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::{LispVal, PrimitiveFn};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("char?", is_char),
+    ("char->integer", char_to_integer),
+    ("integer->char", integer_to_char),
+    ("char=?", char_eq),
+    ("char<?", char_lt),
+    ("char>?", char_gt),
+    ("char<=?", char_le),
+    ("char>=?", char_ge),
+    ("char-ci=?", char_ci_eq),
+    ("char-ci<?", char_ci_lt),
+    ("char-ci>?", char_ci_gt),
+    ("char-ci<=?", char_ci_le),
+    ("char-ci>=?", char_ci_ge),
+    ("char-alphabetic?", is_alphabetic),
+    ("char-numeric?", is_numeric),
+    ("char-whitespace?", is_whitespace),
+    ("char-upper-case?", is_upper_case),
+    ("char-lower-case?", is_lower_case),
+    ("digit-value", digit_value),
+    ("char-upcase", char_upcase),
+    ("char-downcase", char_downcase),
+    ("char-foldcase", char_foldcase),
+];
+
+fn unpack_char(value: &LispVal) -> Result<char, LispError> {
+    match value {
+        LispVal::Char(c) => Ok(*c),
+        _ => Err(LispError::TypeMismatch("char", value.clone())),
+    }
+}
+
+// Case conversions that would turn one character into several, such as
+// upcasing `ß`, leave it as it is.
+fn single(mut chars: impl Iterator<Item = char>, c: char) -> char {
+    match (chars.next(), chars.next()) {
+        (Some(converted), None) => converted,
+        _ => c,
+    }
+}
+
+pub(super) fn upcase(c: char) -> char {
+    single(c.to_uppercase(), c)
+}
+
+pub(super) fn downcase(c: char) -> char {
+    single(c.to_lowercase(), c)
+}
+
+fn is_char(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Char(_)))
+}
+
+fn char_to_integer(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value] => Ok(LispVal::Number(Number::Integer(unpack_char(value)? as i64))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn integer_to_char(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value] => match value {
+            LispVal::Number(Number::Integer(n)) => u32::try_from(*n)
+                .ok()
+                .and_then(char::from_u32)
+                .map(LispVal::Char)
+                .ok_or_else(|| LispError::TypeMismatch("Unicode scalar value", value.clone())),
+            _ => Err(LispError::TypeMismatch(
+                "Unicode scalar value",
+                value.clone(),
+            )),
+        },
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn compare_chars(args: &[LispVal], fold: bool, accept: fn(Ordering) -> bool) -> PrimitiveResult {
+    if args.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    let mut chars = args
+        .iter()
+        .map(unpack_char)
+        .collect::<Result<Vec<_>, _>>()?;
+    if fold {
+        chars = chars.into_iter().map(downcase).collect();
+    }
+    Ok(LispVal::Boolean(
+        chars.windows(2).all(|pair| accept(pair[0].cmp(&pair[1]))),
+    ))
+}
+
+fn char_eq(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, false, |o| o == Ordering::Equal)
+}
+
+fn char_lt(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, false, |o| o == Ordering::Less)
+}
+
+fn char_gt(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, false, |o| o == Ordering::Greater)
+}
+
+fn char_le(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, false, |o| o != Ordering::Greater)
+}
+
+fn char_ge(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, false, |o| o != Ordering::Less)
+}
+
+fn char_ci_eq(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, true, |o| o == Ordering::Equal)
+}
+
+fn char_ci_lt(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, true, |o| o == Ordering::Less)
+}
+
+fn char_ci_gt(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, true, |o| o == Ordering::Greater)
+}
+
+fn char_ci_le(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, true, |o| o != Ordering::Greater)
+}
+
+fn char_ci_ge(args: &[LispVal]) -> PrimitiveResult {
+    compare_chars(args, true, |o| o != Ordering::Less)
+}
+
+fn test_char(args: &[LispVal], test: fn(char) -> bool) -> PrimitiveResult {
+    match args {
+        [value] => Ok(LispVal::Boolean(test(unpack_char(value)?))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn is_alphabetic(args: &[LispVal]) -> PrimitiveResult {
+    test_char(args, char::is_alphabetic)
+}
+
+fn is_numeric(args: &[LispVal]) -> PrimitiveResult {
+    test_char(args, char::is_numeric)
+}
+
+fn is_whitespace(args: &[LispVal]) -> PrimitiveResult {
+    test_char(args, char::is_whitespace)
+}
+
+fn is_upper_case(args: &[LispVal]) -> PrimitiveResult {
+    test_char(args, char::is_uppercase)
+}
+
+fn is_lower_case(args: &[LispVal]) -> PrimitiveResult {
+    test_char(args, char::is_lowercase)
+}
+
+/// `(digit-value char)` is the value of a decimal digit, or `#f` for any
+/// other character. Only ASCII digits are recognised.
+fn digit_value(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value] => Ok(match unpack_char(value)?.to_digit(10) {
+            Some(digit) => LispVal::Number(Number::Integer(digit as i64)),
+            None => LispVal::Boolean(false),
+        }),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn convert_char(args: &[LispVal], convert: fn(char) -> char) -> PrimitiveResult {
+    match args {
+        [value] => Ok(LispVal::Char(convert(unpack_char(value)?))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn char_upcase(args: &[LispVal]) -> PrimitiveResult {
+    convert_char(args, upcase)
+}
+
+fn char_downcase(args: &[LispVal]) -> PrimitiveResult {
+    convert_char(args, downcase)
+}
+
+// Simple case folding maps characters to their lower case, as R7RS allows.
+fn char_foldcase(args: &[LispVal]) -> PrimitiveResult {
+    convert_char(args, downcase)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::chars::*;
+
+    fn chars(s: &str) -> Vec<LispVal> {
+        s.chars().map(LispVal::Char).collect()
+    }
+
+    #[test]
+    fn is_char_test() {
+        assert_eq!(is_char(&chars("a")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_char(&[int(97)]).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_to_integer_test() {
+        assert_eq!(char_to_integer(&chars("a")).unwrap(), int(97));
+        assert_eq!(char_to_integer(&chars("λ")).unwrap(), int(0x3bb));
+        assert!(char_to_integer(&[int(97)]).is_err());
+    }
+
+    #[test]
+    fn integer_to_char_test() {
+        assert_eq!(integer_to_char(&[int(0x3bb)]).unwrap(), LispVal::Char('λ'));
+        assert!(integer_to_char(&[int(0xd800)]).is_err());
+        assert!(integer_to_char(&[int(-1)]).is_err());
+    }
+
+    #[test]
+    fn char_eq_test() {
+        assert_eq!(char_eq(&chars("aaa")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_eq(&chars("aA")).unwrap(), LispVal::Boolean(false));
+        assert!(char_eq(&[LispVal::Char('a'), int(1)]).is_err());
+    }
+
+    #[test]
+    fn char_lt_test() {
+        assert_eq!(char_lt(&chars("abc")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_lt(&chars("abb")).unwrap(), LispVal::Boolean(false));
+        assert_eq!(char_lt(&chars("aB")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_gt_test() {
+        assert_eq!(char_gt(&chars("cba")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_gt(&chars("cca")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_le_test() {
+        assert_eq!(char_le(&chars("abb")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_le(&chars("ba")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_ge_test() {
+        assert_eq!(char_ge(&chars("cca")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ge(&chars("ab")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_ci_eq_test() {
+        assert_eq!(char_ci_eq(&chars("aA")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ci_eq(&chars("ab")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_ci_lt_test() {
+        assert_eq!(char_ci_lt(&chars("aB")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ci_lt(&chars("Ab")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ci_lt(&chars("Aa")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_ci_gt_test() {
+        assert_eq!(char_ci_gt(&chars("Ba")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ci_gt(&chars("aB")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_ci_le_test() {
+        assert_eq!(char_ci_le(&chars("AaB")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ci_le(&chars("Ba")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_ci_ge_test() {
+        assert_eq!(char_ci_ge(&chars("bBa")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(char_ci_ge(&chars("aB")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_alphabetic_test() {
+        assert_eq!(is_alphabetic(&chars("λ")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_alphabetic(&chars("1")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_numeric_test() {
+        assert_eq!(is_numeric(&chars("7")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_numeric(&chars("x")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_whitespace_test() {
+        assert_eq!(is_whitespace(&chars("\t")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_whitespace(&chars("_")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_upper_case_test() {
+        assert_eq!(is_upper_case(&chars("A")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_upper_case(&chars("a")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_lower_case_test() {
+        assert_eq!(is_lower_case(&chars("a")).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_lower_case(&chars("1")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn digit_value_test() {
+        assert_eq!(digit_value(&chars("7")).unwrap(), int(7));
+        assert_eq!(digit_value(&chars("a")).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn char_upcase_test() {
+        assert_eq!(char_upcase(&chars("a")).unwrap(), LispVal::Char('A'));
+        assert_eq!(char_upcase(&chars("λ")).unwrap(), LispVal::Char('Λ'));
+        assert_eq!(char_upcase(&chars("ß")).unwrap(), LispVal::Char('ß'));
+        assert_eq!(char_upcase(&chars("1")).unwrap(), LispVal::Char('1'));
+        assert!(char_upcase(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn char_downcase_test() {
+        assert_eq!(char_downcase(&chars("A")).unwrap(), LispVal::Char('a'));
+        assert_eq!(char_downcase(&chars("Λ")).unwrap(), LispVal::Char('λ'));
+    }
+
+    #[test]
+    fn char_foldcase_test() {
+        assert_eq!(char_foldcase(&chars("Σ")).unwrap(), LispVal::Char('σ'));
+        assert_eq!(char_foldcase(&chars("a")).unwrap(), LispVal::Char('a'));
+    }
+}
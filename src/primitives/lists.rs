@@ -0,0 +1,481 @@
+use super::{is_eqv, out_of_range, predicate, unpack_index, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::{LispVal, PrimitiveFn};
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("car", car),
+    ("cdr", cdr),
+    ("cons", cons),
+    ("set-car!", set_car),
+    ("set-cdr!", set_cdr),
+    ("caar", caar),
+    ("cadr", cadr),
+    ("cdar", cdar),
+    ("cddr", cddr),
+    ("pair?", is_pair),
+    ("null?", is_null),
+    ("list?", is_list),
+    ("length", length),
+    ("append", append),
+    ("reverse", reverse),
+    ("list-tail", list_tail),
+    ("list-ref", list_ref),
+    ("list-set!", list_set),
+    ("list-copy", list_copy),
+    ("make-list", make_list),
+    ("memq", memv),
+    ("memv", memv),
+    ("assq", assv),
+    ("assv", assv),
+];
+
+fn car(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Pair(pair)] => Ok(pair.car()),
+        [other] => Err(LispError::TypeMismatch("pair", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn cdr(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Pair(pair)] => Ok(pair.cdr()),
+        [other] => Err(LispError::TypeMismatch("pair", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn cons(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [car, cdr] => Ok(LispVal::cons(car.clone(), cdr.clone())),
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn set_car(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Pair(pair), value] => pair.set_car(value.clone()),
+        [other, _] => return Err(LispError::TypeMismatch("pair", other.clone())),
+        _ => return Err(LispError::NumArgs(2, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn set_cdr(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Pair(pair), value] => pair.set_cdr(value.clone()),
+        [other, _] => return Err(LispError::TypeMismatch("pair", other.clone())),
+        _ => return Err(LispError::NumArgs(2, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+/// Follows a path of cars and cdrs, spelled as in the procedure's name and
+/// so applied from right to left.
+fn cxr(args: &[LispVal], path: &str) -> PrimitiveResult {
+    match args {
+        [value] => path
+            .chars()
+            .rev()
+            .try_fold(value.clone(), |value, step| match (&value, step) {
+                (LispVal::Pair(pair), 'a') => Ok(pair.car()),
+                (LispVal::Pair(pair), _) => Ok(pair.cdr()),
+                _ => Err(LispError::TypeMismatch("pair", value.clone())),
+            }),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn caar(args: &[LispVal]) -> PrimitiveResult {
+    cxr(args, "aa")
+}
+
+fn cadr(args: &[LispVal]) -> PrimitiveResult {
+    cxr(args, "ad")
+}
+
+fn cdar(args: &[LispVal]) -> PrimitiveResult {
+    cxr(args, "da")
+}
+
+fn cddr(args: &[LispVal]) -> PrimitiveResult {
+    cxr(args, "dd")
+}
+
+fn is_pair(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Pair(_)))
+}
+
+fn is_null(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Nil))
+}
+
+fn is_list(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| value.list_to_vec().is_some())
+}
+
+/// The elements of a proper list, which must not be circular.
+pub(super) fn unpack_list(value: &LispVal) -> Result<Vec<LispVal>, LispError> {
+    value
+        .list_to_vec()
+        .ok_or_else(|| LispError::TypeMismatch("list", value.clone()))
+}
+
+fn length(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list] => Ok(LispVal::Number(Number::Integer(
+            unpack_list(list)?.len() as i64
+        ))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+/// `(append list ... obj)` copies every list but the last, which becomes the
+/// tail of the result and need not be a list at all.
+fn append(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [] => Ok(LispVal::Nil),
+        [lists @ .., last] => {
+            let mut result = last.clone();
+            for list in lists.iter().rev() {
+                result = LispVal::dotted_list(unpack_list(list)?, result);
+            }
+            Ok(result)
+        }
+    }
+}
+
+fn reverse(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list] => {
+            let mut items = unpack_list(list)?;
+            items.reverse();
+            Ok(LispVal::list(items))
+        }
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+// The list left after dropping `k` pairs from the front of `list`.
+fn tail(list: &LispVal, k: usize) -> PrimitiveResult {
+    let mut next = list.clone();
+    for i in 0..k {
+        next = match next {
+            LispVal::Pair(pair) => pair.cdr(),
+            _ => return Err(out_of_range(k, i)),
+        };
+    }
+    Ok(next)
+}
+
+fn list_tail(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list, k] => tail(list, unpack_index(k)?),
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn list_ref(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list, k] => {
+            let k = unpack_index(k)?;
+            match tail(list, k)? {
+                LispVal::Pair(pair) => Ok(pair.car()),
+                _ => Err(out_of_range(k, k)),
+            }
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn list_set(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list, k, value] => {
+            let k = unpack_index(k)?;
+            match tail(list, k)? {
+                LispVal::Pair(pair) => pair.set_car(value.clone()),
+                _ => return Err(out_of_range(k, k)),
+            }
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+/// `(list-copy obj)` copies the pairs of a list, keeping whatever ends it;
+/// anything other than a list is returned as it is.
+fn list_copy(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value @ LispVal::Pair(_)] => match value.split_tail() {
+            Some((items, tail)) => Ok(LispVal::dotted_list(items, tail)),
+            None => Err(LispError::TypeMismatch("list", value.clone())),
+        },
+        [value] => Ok(value.clone()),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn make_list(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [k, fill @ ..] if fill.len() <= 1 => {
+            let fill = fill.first().cloned().unwrap_or(LispVal::Unspecified);
+            Ok(LispVal::list(vec![fill; unpack_index(k)?]))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+// `eq?` and `eqv?` are the same procedure, so `memq` and `assq` are the same
+// as `memv` and `assv`. `member` and `assoc` are in the prelude, since they
+// take an optional comparison procedure.
+fn memv(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [obj, list] => {
+            let mut next = list.clone();
+            while let LispVal::Pair(pair) = &next {
+                if is_eqv(obj, &pair.car()) {
+                    return Ok(next);
+                }
+                next = pair.cdr();
+            }
+            Ok(LispVal::Boolean(false))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn assv(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [obj, alist] => {
+            for entry in alist.iter() {
+                match &entry {
+                    LispVal::Pair(pair) if is_eqv(obj, &pair.car()) => return Ok(entry),
+                    LispVal::Pair(_) => (),
+                    _ => return Err(LispError::TypeMismatch("pair", entry)),
+                }
+            }
+            Ok(LispVal::Boolean(false))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::lists::*;
+
+    fn ints(ns: &[i64]) -> LispVal {
+        LispVal::list(ns.iter().map(|&n| int(n)).collect())
+    }
+
+    #[test]
+    fn car_test() {
+        assert_eq!(car(&[LispVal::cons(int(1), int(2))]).unwrap(), int(1));
+        assert_eq!(car(&[ints(&[3, 4])]).unwrap(), int(3));
+        assert!(car(&[LispVal::Nil]).is_err());
+    }
+
+    #[test]
+    fn cdr_test() {
+        assert_eq!(cdr(&[LispVal::cons(int(1), int(2))]).unwrap(), int(2));
+        assert_eq!(cdr(&[ints(&[1])]).unwrap(), LispVal::Nil);
+        assert!(cdr(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn cons_test() {
+        assert_eq!(
+            cons(&[int(1), int(2)]).unwrap(),
+            LispVal::cons(int(1), int(2))
+        );
+        assert_eq!(cons(&[int(1), LispVal::Nil]).unwrap(), ints(&[1]));
+        assert!(cons(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn set_car_test() {
+        let list = ints(&[1, 2]);
+        set_car(&[list.clone(), int(3)]).unwrap();
+        assert_eq!(list, ints(&[3, 2]));
+        assert!(set_car(&[LispVal::Nil, int(1)]).is_err());
+    }
+
+    #[test]
+    fn set_cdr_test() {
+        let list = ints(&[1, 2]);
+        set_cdr(&[list.clone(), int(3)]).unwrap();
+        assert_eq!(list, LispVal::cons(int(1), int(3)));
+        assert!(set_cdr(&[LispVal::Nil, int(1)]).is_err());
+    }
+
+    #[test]
+    fn caar_test() {
+        let nested = LispVal::list(vec![ints(&[1, 2]), int(3), int(4)]);
+        assert_eq!(caar(&[nested]).unwrap(), int(1));
+        assert!(caar(&[ints(&[1])]).is_err());
+    }
+
+    #[test]
+    fn cadr_test() {
+        let nested = LispVal::list(vec![ints(&[1, 2]), int(3), int(4)]);
+        assert_eq!(cadr(&[nested]).unwrap(), int(3));
+        assert!(cadr(&[ints(&[1])]).is_err());
+    }
+
+    #[test]
+    fn cdar_test() {
+        let nested = LispVal::list(vec![ints(&[1, 2]), int(3), int(4)]);
+        assert_eq!(cdar(&[nested]).unwrap(), ints(&[2]));
+        assert!(cdar(&[ints(&[1])]).is_err());
+    }
+
+    #[test]
+    fn cddr_test() {
+        let nested = LispVal::list(vec![ints(&[1, 2]), int(3), int(4)]);
+        assert_eq!(cddr(&[nested]).unwrap(), ints(&[4]));
+        assert!(cddr(&[ints(&[1])]).is_err());
+    }
+
+    #[test]
+    fn is_pair_test() {
+        assert_eq!(is_pair(&[ints(&[1])]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_pair(&[LispVal::Nil]).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_null_test() {
+        assert_eq!(is_null(&[LispVal::Nil]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_null(&[ints(&[1])]).unwrap(), LispVal::Boolean(false));
+    }
+
+    #[test]
+    fn is_list_test() {
+        let yes = LispVal::Boolean(true);
+        let no = LispVal::Boolean(false);
+        assert_eq!(is_list(&[ints(&[1, 2])]).unwrap(), yes);
+        assert_eq!(is_list(&[LispVal::Nil]).unwrap(), yes);
+        assert_eq!(is_list(&[LispVal::cons(int(1), int(2))]).unwrap(), no);
+        let circular = ints(&[1, 2]);
+        if let LispVal::Pair(pair) = &circular {
+            pair.set_cdr(circular.clone());
+        }
+        assert_eq!(is_list(&[circular]).unwrap(), no);
+    }
+
+    #[test]
+    fn length_test() {
+        assert_eq!(length(&[ints(&[1, 2, 3])]).unwrap(), int(3));
+        assert_eq!(length(&[LispVal::Nil]).unwrap(), int(0));
+        assert!(length(&[LispVal::cons(int(1), int(2))]).is_err());
+        let circular = ints(&[1, 2]);
+        if let LispVal::Pair(pair) = &circular {
+            pair.set_cdr(circular.clone());
+        }
+        assert!(length(&[circular]).is_err());
+    }
+
+    #[test]
+    fn append_test() {
+        assert_eq!(append(&[]).unwrap(), LispVal::Nil);
+        assert_eq!(
+            append(&[ints(&[1]), ints(&[2, 3]), LispVal::Nil, ints(&[4])]).unwrap(),
+            ints(&[1, 2, 3, 4])
+        );
+        assert_eq!(
+            append(&[ints(&[1, 2]), int(3)]).unwrap(),
+            LispVal::dotted_list(vec![int(1), int(2)], int(3))
+        );
+        assert_eq!(append(&[int(3)]).unwrap(), int(3));
+        // The last list is shared rather than copied.
+        let last = ints(&[2]);
+        let appended = append(&[ints(&[1]), last.clone()]).unwrap();
+        assert_eq!(
+            crate::primitives::eqv(&[cdr(&[appended]).unwrap(), last]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert!(append(&[int(1), ints(&[2])]).is_err());
+    }
+
+    #[test]
+    fn reverse_test() {
+        assert_eq!(reverse(&[ints(&[1, 2, 3])]).unwrap(), ints(&[3, 2, 1]));
+        assert_eq!(reverse(&[LispVal::Nil]).unwrap(), LispVal::Nil);
+        assert!(reverse(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn list_tail_test() {
+        let list = ints(&[1, 2, 3]);
+        assert_eq!(list_tail(&[list.clone(), int(2)]).unwrap(), ints(&[3]));
+        assert_eq!(list_tail(&[list.clone(), int(3)]).unwrap(), LispVal::Nil);
+        assert!(list_tail(&[list, int(4)]).is_err());
+    }
+
+    #[test]
+    fn list_ref_test() {
+        let list = ints(&[1, 2, 3]);
+        assert_eq!(list_ref(&[list.clone(), int(1)]).unwrap(), int(2));
+        assert!(list_ref(&[list.clone(), int(3)]).is_err());
+        assert!(list_ref(&[list, int(-1)]).is_err());
+    }
+
+    #[test]
+    fn list_set_test() {
+        let list = ints(&[1, 2, 3]);
+        list_set(&[list.clone(), int(1), int(5)]).unwrap();
+        assert_eq!(list, ints(&[1, 5, 3]));
+        assert!(list_set(&[list, int(3), int(5)]).is_err());
+    }
+
+    #[test]
+    fn list_copy_test() {
+        let list = ints(&[1, 2]);
+        let copy = list_copy(std::slice::from_ref(&list)).unwrap();
+        assert_eq!(copy, list);
+        set_car(&[copy.clone(), int(5)]).unwrap();
+        assert_eq!(list, ints(&[1, 2]));
+        let dotted = LispVal::cons(int(1), int(2));
+        assert_eq!(list_copy(std::slice::from_ref(&dotted)).unwrap(), dotted);
+        assert_eq!(list_copy(&[int(1)]).unwrap(), int(1));
+    }
+
+    #[test]
+    fn make_list_test() {
+        assert_eq!(make_list(&[int(2), int(0)]).unwrap(), ints(&[0, 0]));
+        assert_eq!(make_list(&[int(0)]).unwrap(), LispVal::Nil);
+        assert!(make_list(&[int(-1)]).is_err());
+    }
+
+    #[test]
+    fn memv_test() {
+        let list = ints(&[1, 2, 3]);
+        assert_eq!(memv(&[int(2), list.clone()]).unwrap(), ints(&[2, 3]));
+        assert_eq!(memv(&[int(4), list]).unwrap(), LispVal::Boolean(false));
+        // Lists are only `eqv?` to themselves.
+        let nested = LispVal::list(vec![ints(&[1])]);
+        assert_eq!(
+            memv(&[ints(&[1]), nested]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn assv_test() {
+        let alist = LispVal::list(vec![
+            LispVal::cons(LispVal::symbol("a"), int(1)),
+            LispVal::cons(LispVal::symbol("b"), int(2)),
+        ]);
+        assert_eq!(
+            assv(&[LispVal::symbol("b"), alist.clone()]).unwrap(),
+            LispVal::cons(LispVal::symbol("b"), int(2))
+        );
+        assert_eq!(
+            assv(&[LispVal::symbol("c"), alist]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(assv(&[int(1), ints(&[1])]).is_err());
+    }
+}
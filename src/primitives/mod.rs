@@ -0,0 +1,833 @@
+//! The procedures of the standard library that are written in Rust, grouped
+//! by the kind of data they work on, and the prelude that defines the rest in
+//! Scheme.
+
+use crate::error::LispError;
+use crate::eval::Env;
+use crate::gc;
+use crate::interpreter::Engine;
+use crate::number::Number;
+use crate::pair::equal_contents;
+use crate::parser::{parse_program, LispVal, Primitive, PrimitiveFn};
+use crate::symbol::Symbol;
+use std::rc::Rc;
+
+mod bytevectors;
+mod chars;
+mod lists;
+mod numbers;
+mod ports;
+mod records;
+mod strings;
+mod vectors;
+
+type PrimitiveResult = Result<LispVal, LispError>;
+
+const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("eq?", eqv),
+    ("eqv?", eqv),
+    ("equal?", equal),
+    ("not", not),
+    ("boolean?", is_boolean),
+    ("boolean=?", boolean_eq),
+    ("symbol?", is_symbol),
+    ("symbol=?", symbol_eq),
+    ("symbol->string", symbol_to_string),
+    ("string->symbol", string_to_symbol),
+    ("procedure?", is_procedure),
+    ("values", values),
+    ("%values->list", values_to_list),
+    ("error", error),
+    ("error-object?", is_error_object),
+    ("error-object-message", error_object_message),
+    ("error-object-irritants", error_object_irritants),
+    ("read-error?", is_read_error),
+    ("file-error?", is_file_error),
+    ("features", features),
+    ("gc-stats", gc_stats),
+];
+
+const TABLES: &[&[(&str, PrimitiveFn)]] = &[
+    PRIMITIVES,
+    numbers::PRIMITIVES,
+    lists::PRIMITIVES,
+    chars::PRIMITIVES,
+    strings::PRIMITIVES,
+    vectors::PRIMITIVES,
+    bytevectors::PRIMITIVES,
+    ports::PRIMITIVES,
+    records::PRIMITIVES,
+];
+
+const PRELUDE: &str = include_str!("prelude.scm");
+
+/// Builds a fresh top-level environment holding every primitive procedure and
+/// the procedures defined by the prelude, compiled for `engine`.
+pub fn primitive_env(engine: Engine) -> Rc<Env> {
+    let env = Env::new();
+    for (name, func) in TABLES.iter().copied().flatten() {
+        env.define(
+            Symbol::intern(name),
+            LispVal::PrimitiveFunc(Primitive { name, func: *func }),
+        );
+    }
+    for expr in parse_program(PRELUDE).expect("prelude should parse") {
+        engine.eval(&env, &expr).expect("prelude should evaluate");
+    }
+    env
+}
+
+fn unpack_num(value: &LispVal) -> Result<&Number, LispError> {
+    match value {
+        LispVal::Number(n) => Ok(n),
+        _ => Err(LispError::TypeMismatch("number", value.clone())),
+    }
+}
+
+fn unpack_index(value: &LispVal) -> Result<usize, LispError> {
+    match value {
+        LispVal::Number(Number::Integer(n)) if *n >= 0 => Ok(*n as usize),
+        _ => Err(LispError::TypeMismatch(
+            "exact non-negative integer",
+            value.clone(),
+        )),
+    }
+}
+
+fn out_of_range(index: usize, len: usize) -> LispError {
+    LispError::Default(format!("Index {} out of range for length {}", index, len))
+}
+
+/// Reads the optional `start` and `end` arguments that follow a sequence of
+/// `len` elements, which default to the whole sequence.
+fn unpack_range(args: &[LispVal], len: usize) -> Result<(usize, usize), LispError> {
+    let start = args.first().map(unpack_index).transpose()?.unwrap_or(0);
+    let end = args.get(1).map(unpack_index).transpose()?.unwrap_or(len);
+    if end > len {
+        Err(out_of_range(end, len))
+    } else if start > end {
+        Err(out_of_range(start, end))
+    } else {
+        Ok((start, end))
+    }
+}
+
+/// Applies a type predicate to the single argument.
+fn predicate(args: &[LispVal], test: fn(&LispVal) -> bool) -> PrimitiveResult {
+    match args {
+        [value] => Ok(LispVal::Boolean(test(value))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+// Pairs, strings, vectors, bytevectors, records, error objects, multiple
+// values, ports and procedures are the same object only if they are the same cell.
+// Numbers must agree in exactness, and inexact ones bit for bit, so that
+// `0.0` and `-0.0` differ. Everything else is compared by value.
+pub(crate) fn is_eqv(a: &LispVal, b: &LispVal) -> bool {
+    match (a, b) {
+        (LispVal::Pair(a), LispVal::Pair(b)) => Rc::ptr_eq(a, b),
+        (LispVal::String(a), LispVal::String(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Vector(a), LispVal::Vector(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Bytevector(a), LispVal::Bytevector(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Record(a), LispVal::Record(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Error(a), LispVal::Error(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Values(a), LispVal::Values(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Port(a), LispVal::Port(b)) => Rc::ptr_eq(a, b),
+        (LispVal::NativeFunc(a), LispVal::NativeFunc(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Func(a), LispVal::Func(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Continuation(a), LispVal::Continuation(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Closure(a), LispVal::Closure(b)) => Rc::ptr_eq(a, b),
+        (LispVal::VmContinuation(a), LispVal::VmContinuation(b)) => Rc::ptr_eq(a, b),
+        (LispVal::Number(Number::Real(a)), LispVal::Number(Number::Real(b))) => {
+            a.to_bits() == b.to_bits()
+        }
+        (LispVal::Number(Number::Complex(a)), LispVal::Number(Number::Complex(b))) => {
+            a.re.to_bits() == b.re.to_bits() && a.im.to_bits() == b.im.to_bits()
+        }
+        _ => a == b,
+    }
+}
+
+fn eqv(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [a, b] => Ok(LispVal::Boolean(is_eqv(a, b))),
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn is_equal(a: &LispVal, b: &LispVal) -> bool {
+    equal_contents(a, b, |a, b| match (a, b) {
+        (LispVal::String(_), LispVal::String(_)) => a == b,
+        (LispVal::Bytevector(_), LispVal::Bytevector(_)) => a == b,
+        _ => is_eqv(a, b),
+    })
+}
+
+/// `(equal? a b)` compares pairs, vectors, bytevectors and strings by their
+/// contents and everything else with `eqv?`, and terminates on circular data.
+fn equal(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [a, b] => Ok(LispVal::Boolean(is_equal(a, b))),
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn not(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| *value == LispVal::Boolean(false))
+}
+
+fn is_boolean(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Boolean(_)))
+}
+
+fn boolean_eq(args: &[LispVal]) -> PrimitiveResult {
+    let mut bools = vec![];
+    for arg in args {
+        match arg {
+            LispVal::Boolean(b) => bools.push(*b),
+            other => return Err(LispError::TypeMismatch("boolean", other.clone())),
+        }
+    }
+    if bools.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    Ok(LispVal::Boolean(
+        bools.windows(2).all(|pair| pair[0] == pair[1]),
+    ))
+}
+
+fn is_symbol(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Atom(_)))
+}
+
+fn symbol_eq(args: &[LispVal]) -> PrimitiveResult {
+    let mut symbols = vec![];
+    for arg in args {
+        match arg {
+            LispVal::Atom(name) => symbols.push(*name),
+            other => return Err(LispError::TypeMismatch("symbol", other.clone())),
+        }
+    }
+    if symbols.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    Ok(LispVal::Boolean(
+        symbols.windows(2).all(|pair| pair[0] == pair[1]),
+    ))
+}
+
+fn symbol_to_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Atom(name)] => Ok(LispVal::string(name.as_str().to_owned())),
+        [other] => Err(LispError::TypeMismatch("symbol", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn string_to_symbol(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::String(s)] => Ok(LispVal::symbol(&s.borrow().iter().collect::<String>())),
+        [other] => Err(LispError::TypeMismatch("string", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn is_procedure(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| {
+        matches!(
+            value,
+            LispVal::PrimitiveFunc(_)
+                | LispVal::NativeFunc(_)
+                | LispVal::Func(_)
+                | LispVal::Continuation(_)
+                | LispVal::Closure(_)
+                | LispVal::VmContinuation(_)
+        )
+    })
+}
+
+/// `(values obj ...)` returns a single value as itself, and any other number
+/// of them as one object that only `call-with-values` takes apart.
+fn values(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value] => Ok(value.clone()),
+        _ => Ok(LispVal::Values(gc::alloc(args.to_vec()))),
+    }
+}
+
+/// `(%values->list obj)` lists the values `obj` stands for: those it was
+/// made from if it came from `values`, or else just `obj` itself.
+fn values_to_list(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Values(values)] => Ok(LispVal::list(values.to_vec())),
+        [value] => Ok(LispVal::list(vec![value.clone()])),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+/// `(error message irritant ...)` signals an error, which the evaluator
+/// raises as an error object if a handler is installed.
+fn error(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::String(message), irritants @ ..] => Err(LispError::User(
+            message.borrow().iter().collect(),
+            irritants.to_vec(),
+        )),
+        [other, ..] => Err(LispError::TypeMismatch("string", other.clone())),
+        [] => Err(LispError::NumArgs(1, vec![])),
+    }
+}
+
+fn is_error_object(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value] => Ok(LispVal::Boolean(matches!(value, LispVal::Error(_)))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn error_object_message(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Error(err)] => Ok(LispVal::string(err.message())),
+        [other] => Err(LispError::TypeMismatch("error object", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn error_object_irritants(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Error(err)] => Ok(LispVal::list(err.irritants())),
+        [other] => Err(LispError::TypeMismatch("error object", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+/// `(read-error? obj)` is true of the error objects for source text that
+/// could not be read.
+fn is_read_error(args: &[LispVal]) -> PrimitiveResult {
+    predicate(
+        args,
+        |value| matches!(value, LispVal::Error(err) if matches!(**err, LispError::Parser(_))),
+    )
+}
+
+/// `(file-error? obj)` is always false: no procedure opens files, so there
+/// are no file errors to raise.
+fn is_file_error(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |_| false)
+}
+
+/// `(features)` lists the R7RS feature identifiers this implementation
+/// supports.
+fn features(args: &[LispVal]) -> PrimitiveResult {
+    if !args.is_empty() {
+        return Err(LispError::NumArgs(0, args.to_vec()));
+    }
+    Ok([
+        "r7rs",
+        "exact-closed",
+        "ieee-float",
+        "full-unicode",
+        "ratios",
+    ]
+    .iter()
+    .map(|name| LispVal::symbol(name))
+    .collect())
+}
+
+/// `(gc-stats)` returns an association list of the collector's counts:
+/// collections run, objects allocated, objects freed by the collector and
+/// objects still alive.
+fn gc_stats(args: &[LispVal]) -> PrimitiveResult {
+    if !args.is_empty() {
+        return Err(LispError::NumArgs(0, args.to_vec()));
+    }
+    let stats = gc::stats();
+    let entry = |name, count: usize| {
+        LispVal::cons(
+            LispVal::symbol(name),
+            LispVal::Number(Number::Integer(count as i64)),
+        )
+    };
+    Ok(LispVal::list(vec![
+        entry("collections", stats.collections),
+        entry("allocated", stats.allocated),
+        entry("collected", stats.collected),
+        entry("live", stats.live),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::convert::assoc_field;
+    use crate::parser::int;
+    use crate::primitives::*;
+
+    #[test]
+    fn equivalence_test() {
+        let list = LispVal::list(vec![int(1), int(2)]);
+        let copy = LispVal::list(vec![int(1), int(2)]);
+        let vector = LispVal::vector(vec![int(1)]);
+        let bytes = LispVal::bytevector(vec![1]);
+        let text = LispVal::string("a".to_owned());
+        let yes = LispVal::Boolean(true);
+        let no = LispVal::Boolean(false);
+        // Pairs, strings, vectors and bytevectors are `eqv?` only to
+        // themselves.
+        assert_eq!(eqv(&[text.clone(), text.clone()]).unwrap(), yes);
+        assert_eq!(
+            eqv(&[text.clone(), LispVal::string("a".to_owned())]).unwrap(),
+            no
+        );
+        assert_eq!(
+            equal(&[text, LispVal::string("a".to_owned())]).unwrap(),
+            yes
+        );
+        assert_eq!(eqv(&[list.clone(), list.clone()]).unwrap(), yes);
+        assert_eq!(eqv(&[list.clone(), copy.clone()]).unwrap(), no);
+        assert_eq!(eqv(&[vector.clone(), vector.clone()]).unwrap(), yes);
+        assert_eq!(
+            eqv(&[vector.clone(), LispVal::vector(vec![int(1)])]).unwrap(),
+            no
+        );
+        assert_eq!(
+            eqv(&[bytes.clone(), LispVal::bytevector(vec![1])]).unwrap(),
+            no
+        );
+        assert_eq!(eqv(&[int(2), int(2)]).unwrap(), yes);
+        assert_eq!(
+            eqv(&[int(2), LispVal::Number(Number::Real(2.0))]).unwrap(),
+            no
+        );
+        assert_eq!(equal(&[list, copy]).unwrap(), yes);
+        assert_eq!(
+            equal(&[vector, LispVal::vector(vec![int(1)])]).unwrap(),
+            yes
+        );
+        assert_eq!(equal(&[bytes, LispVal::bytevector(vec![2])]).unwrap(), no);
+        assert!(eqv(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn eqv_test() {
+        // Zeros of either sign are `=` but not `eqv?`, here or inside a list.
+        assert_eq!(
+            run(
+                "(list (eqv? 0.0 -0.0) (= 0.0 -0.0) (eqv? 1.5 1.5) (eqv? 1/2 1/2)
+                       (equal? '(0.0) '(-0.0)))"
+            ),
+            "(#f #t #t #t #f)"
+        );
+        // Error objects and multiple values are `eqv?` only to themselves,
+        // however alike.
+        assert_eq!(
+            run("(define (catch x) (guard (e (#t e)) (error \"m\" x)))
+                 (define e (catch 1))
+                 (list (eqv? e e) (eqv? e (catch 1))
+                       (eqv? (values 1 2) (values 1 2)) (equal? (catch 1) (catch 1)))"),
+            "(#t #f #f #f)"
+        );
+        // Not even when their contents are circular.
+        assert_eq!(
+            run("(define (circle) (let ((l (list 1))) (set-cdr! l l) l))
+                 (define (catch x) (guard (e (#t e)) (error \"m\" x)))
+                 (define v (vector 1)) (vector-set! v 0 v)
+                 (define w (vector 1)) (vector-set! w 0 w)
+                 (list (eqv? (catch (circle)) (catch (circle)))
+                       (eqv? (values v 1) (values w 1)))"),
+            "(#f #f)"
+        );
+    }
+
+    #[test]
+    fn circular_equal_test() {
+        // `#0=(1 . #0#)` against `#0=(1 1 . #0#)` and `#0=(1 2 . #0#)`.
+        assert_eq!(
+            run("(define a (list 1)) (set-cdr! a a)
+                 (define b (list 1 1)) (set-cdr! (cdr b) b)
+                 (define c (list 1 2)) (set-cdr! (cdr c) c)
+                 (list (equal? a b) (equal? a c) (equal? (vector a) (vector b))
+                       (if (member b (list c a)) 'found 'missing))"),
+            "(#t #f #t found)"
+        );
+        assert_eq!(
+            run("(define v (vector 1 #f)) (vector-set! v 1 v)
+                 (define w (vector 1 (vector 1 #f))) (vector-set! (vector-ref w 1) 1 w)
+                 (list (equal? v w) (equal? v (vector 1 v)) (equal? v (vector 2 v)))"),
+            "(#t #t #f)"
+        );
+    }
+
+    #[test]
+    fn boolean_test() {
+        let yes = LispVal::Boolean(true);
+        let no = LispVal::Boolean(false);
+        assert_eq!(not(&[LispVal::Boolean(false)]).unwrap(), yes);
+        assert_eq!(not(&[LispVal::Nil]).unwrap(), no);
+        assert_eq!(is_boolean(&[LispVal::Boolean(false)]).unwrap(), yes);
+        assert_eq!(is_boolean(&[int(0)]).unwrap(), no);
+        assert_eq!(boolean_eq(&[no.clone(), no.clone()]).unwrap(), yes);
+        assert_eq!(
+            boolean_eq(&[no.clone(), yes.clone(), no]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(boolean_eq(&[yes, int(1)]).is_err());
+    }
+
+    #[test]
+    fn symbol_test() {
+        let name = LispVal::string("hello world".to_owned());
+        let symbol = string_to_symbol(std::slice::from_ref(&name)).unwrap();
+        assert_eq!(symbol, LispVal::symbol("hello world"));
+        assert_eq!(
+            is_symbol(std::slice::from_ref(&symbol)).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            is_symbol(std::slice::from_ref(&name)).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert_eq!(
+            symbol_to_string(std::slice::from_ref(&symbol)).unwrap(),
+            name
+        );
+        assert_eq!(
+            symbol_eq(&[symbol.clone(), LispVal::symbol("hello world")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            symbol_eq(&[symbol, LispVal::symbol("x")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(symbol_to_string(&[name]).is_err());
+    }
+
+    #[test]
+    fn procedure_test() {
+        let env = primitive_env(Engine::default());
+        let lookup = |name| env.get(Symbol::intern(name)).unwrap();
+        for name in &["car", "map", "call/cc"] {
+            assert_eq!(
+                is_procedure(&[lookup(name)]).unwrap(),
+                LispVal::Boolean(true)
+            );
+        }
+        assert_eq!(
+            is_procedure(&[LispVal::symbol("car")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn error_object_test() {
+        let message = LispVal::string("oops".to_owned());
+        let err = error(&[message.clone(), int(1)]).unwrap_err();
+        assert_eq!(err, LispError::User("oops".to_owned(), vec![int(1)]));
+        assert!(error(&[int(1)]).is_err());
+        let obj = LispVal::error(err);
+        assert_eq!(
+            is_error_object(std::slice::from_ref(&obj)).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(is_error_object(&[int(1)]).unwrap(), LispVal::Boolean(false));
+        assert_eq!(
+            error_object_message(std::slice::from_ref(&obj)).unwrap(),
+            message
+        );
+        assert_eq!(
+            error_object_irritants(&[obj]).unwrap(),
+            LispVal::list(vec![int(1)])
+        );
+        assert!(error_object_message(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn error_kind_test() {
+        let yes = LispVal::Boolean(true);
+        let no = LispVal::Boolean(false);
+        let unreadable = LispVal::error(parse_program("(1 2").unwrap_err().into());
+        let user = LispVal::error(LispError::User("oops".to_owned(), vec![]));
+        assert_eq!(
+            is_read_error(std::slice::from_ref(&unreadable)).unwrap(),
+            yes
+        );
+        assert_eq!(is_read_error(std::slice::from_ref(&user)).unwrap(), no);
+        assert_eq!(is_read_error(&[int(1)]).unwrap(), no);
+        assert_eq!(is_file_error(&[unreadable]).unwrap(), no);
+        assert_eq!(is_file_error(&[user]).unwrap(), no);
+        assert!(is_read_error(&[]).is_err());
+        assert_eq!(
+            run("(guard (e (#t (list (read-error? e) (file-error? e)))) (car 1))"),
+            "(#f #f)"
+        );
+    }
+
+    #[test]
+    fn current_port_test() {
+        // Output goes to the current output port unless a port is given.
+        assert_eq!(
+            run("(define out (open-output-string))
+                 (define other (open-output-string))
+                 (parameterize ((current-output-port out))
+                   (write \"a\") (display \"b\") (newline) (write-char #\\c)
+                   (write-string \"xdey\" (current-output-port) 1 3)
+                   (write 'z other)
+                   (flush-output-port))
+                 (list (get-output-string out) (get-output-string other)
+                       (eq? (current-output-port) out))"),
+            "(\"\\\"a\\\"b\\ncde\" \"z\" #f)"
+        );
+        assert_eq!(
+            run(
+                "(parameterize ((current-input-port (open-input-string \"ab\\ncd\")))
+                   (let* ((c (peek-char)) (c (read-char)) (line (read-line))
+                          (s (read-string 5)))
+                     (list c line s (eof-object? (read-char)) (char-ready?))))"
+            ),
+            "(#\\a \"b\" \"cd\" #t #t)"
+        );
+        assert_eq!(
+            run("(define out (open-output-bytevector))
+                 (write-u8 1 out) (write-bytevector (bytevector 2 3 4) out 1)
+                 (define in (open-input-bytevector (get-output-bytevector out)))
+                 (list (peek-u8 in) (read-u8 in) (u8-ready? in) (read-bytevector 5 in)
+                       (eof-object? (read-u8 in)))"),
+            "(1 1 #t #u8(3 4) #t)"
+        );
+        assert_eq!(
+            run(
+                "(list (textual-port? (current-input-port)) (output-port? (current-error-port))
+                       (eof-object) (eof-object? (eof-object)))"
+            ),
+            "(#t #t #<eof> #t)"
+        );
+    }
+
+    #[test]
+    fn call_with_port_test() {
+        assert_eq!(
+            run("(define in (open-input-string \"xy\"))
+                 (list (call-with-port in read-char) (input-port-open? in))"),
+            "(#\\x #f)"
+        );
+    }
+
+    #[test]
+    fn features_test() {
+        assert_eq!(
+            run("(list (and (memq 'r7rs (features)) #t) (and (memq 'ratios (features)) #t))"),
+            "(#t #t)"
+        );
+        assert!(features(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn parameter_test() {
+        assert_eq!(
+            run("(define radix (make-parameter 10))
+                 (define (show) (radix))
+                 (list (show)
+                       (parameterize ((radix 2)) (show))
+                       (show))"),
+            "(10 2 10)"
+        );
+        // A converter applies to the initial value and to every value given
+        // by `parameterize`, but not when the old value is put back.
+        assert_eq!(
+            run("(define p (make-parameter 1 (lambda (x) (* x 10))))
+                 (list (p) (parameterize ((p 2)) (p)) (p))"),
+            "(10 20 10)"
+        );
+        // Leaving the body by a continuation or an error restores the value.
+        assert_eq!(
+            run("(define p (make-parameter 'outer))
+                 (define k-result
+                   (call/cc (lambda (k) (parameterize ((p 'inner)) (k (p))))))
+                 (define guarded
+                   (guard (e (#t (p)))
+                     (parameterize ((p 'inner)) (raise 'oops))))
+                 (list k-result guarded (p))"),
+            "(inner outer outer)"
+        );
+        assert_eq!(
+            run("(define a (make-parameter 1))
+                 (define b (make-parameter 2))
+                 (parameterize ((a 3) (b (a))) (list (a) (b)))"),
+            "(3 1)"
+        );
+    }
+
+    // Runs a program under both engines, checking they agree on what it
+    // writes.
+    fn run(source: &str) -> String {
+        let results: Vec<String> = [Engine::Bytecode, Engine::TreeWalking]
+            .iter()
+            .map(|engine| {
+                let env = primitive_env(*engine);
+                let mut result = LispVal::Unspecified;
+                for expr in parse_program(source).unwrap() {
+                    result = engine.eval(&env, &expr).unwrap();
+                }
+                result.to_string()
+            })
+            .collect();
+        assert_eq!(results[0], results[1]);
+        results[0].clone()
+    }
+
+    #[test]
+    fn map_test() {
+        assert_eq!(run("(map (lambda (x) (* x x)) '(1 2 3))"), "(1 4 9)");
+        assert_eq!(run("(map + '(1 2 3) '(10 20))"), "(11 22)");
+        assert_eq!(run("(map car '())"), "()");
+        assert_eq!(
+            run("(define acc '())
+                 (for-each (lambda (x y) (set! acc (cons (- x y) acc)))
+                           '(5 6 7) '(1 2))
+                 acc"),
+            "(4 4)"
+        );
+        assert_eq!(run("(vector-map + #(1 2) #(10 20 30))"), "#(11 22)");
+        assert_eq!(
+            run("(define n 0)
+                 (vector-for-each (lambda (x) (set! n (+ n x))) #(1 2 3))
+                 n"),
+            "6"
+        );
+        assert_eq!(run(r#"(string-map char-upcase "abc")"#), r#""ABC""#);
+        assert_eq!(
+            run(r#"(define l '())
+                   (string-for-each (lambda (a b) (set! l (cons (string a b) l)))
+                                    "ab" "xyz")
+                   l"#),
+            r#"("by" "ax")"#
+        );
+    }
+
+    #[test]
+    fn member_test() {
+        assert_eq!(run("(member (list 'a) '(b (a) c))"), "((a) c)");
+        assert_eq!(run("(member 'd '(a b c))"), "#f");
+        assert_eq!(run("(member 2.0 '(1 2 3) =)"), "(2 3)");
+        assert_eq!(run(r#"(assoc "b" '(("a" . 1) ("b" . 2)))"#), r#"("b" . 2)"#);
+        assert_eq!(run("(assoc 2.0 '((1 one) (2 two)) =)"), "(2 two)");
+        assert_eq!(run("(assoc 5 '((1 one)))"), "#f");
+    }
+
+    #[test]
+    fn values_test() {
+        assert_eq!(
+            run("(call-with-values (lambda () (values 1 2)) cons)"),
+            "(1 . 2)"
+        );
+        assert_eq!(run("(call-with-values (lambda () (values)) list)"), "()");
+        assert_eq!(run("(call-with-values (lambda () 5) list)"), "(5)");
+        assert_eq!(run("(+ (values 2) 1)"), "3");
+        assert_eq!(
+            run("(call-with-values (lambda () (floor/ -7 2)) list)"),
+            "(-4 1)"
+        );
+        assert_eq!(
+            run("(call-with-values (lambda () (truncate/ -7 2)) list)"),
+            "(-3 -1)"
+        );
+        assert_eq!(
+            run("(call-with-values (lambda () (exact-integer-sqrt 17)) list)"),
+            "(4 1)"
+        );
+        assert_eq!(run("(apply + 1 2 '(3 4))"), "10");
+        assert_eq!(
+            run("(call-with-values (lambda () (apply values '(1 2 3))) list)"),
+            "(1 2 3)"
+        );
+        // Multiple values are one object that list operations cannot see
+        // into.
+        assert_eq!(
+            run("(list (pair? (values 1 2)) (pair? (values)) (null? (values)))"),
+            "(#f #f #f)"
+        );
+        assert_eq!(run("(values 1 \"two\")"), "#<values 1 \"two\">");
+        assert_eq!(run("(vector (values))"), "#(#<values>)");
+        assert_eq!(
+            run("(guard (e (#t 'not-a-pair)) (car (values 1 2)))"),
+            "not-a-pair"
+        );
+    }
+
+    #[test]
+    fn record_test() {
+        let point = "(define-record-type <point>
+                       (make-point x y)
+                       point?
+                       (x point-x set-point-x!)
+                       (y point-y))";
+        assert_eq!(
+            run(&format!(
+                "{} (define p (make-point 1 2)) (set-point-x! p 3)
+                 (list p (point? p) (point? 'p) (point-x p) (point-y p))",
+                point
+            )),
+            "(#<point x: 3 y: 2> #t #f 3 2)"
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (guard (e (#t (error-object-message e))) (point-x (vector 1 2)))",
+                point
+            )),
+            r#""Invalid type: expected <point>""#
+        );
+        // Each evaluation of a definition makes a distinct type, and a local
+        // definition is only visible in its body.
+        assert_eq!(
+            run(&format!(
+                "{} (define old (make-point 1 2)) {} (list (point? old) (point? (make-point 1 2)))",
+                point, point
+            )),
+            "(#f #t)"
+        );
+        assert_eq!(
+            run("(define (make-cell v)
+                   (define-record-type cell (new-cell v next) cell? (v cell-v) (next cell-next set-cell-next!))
+                   (let ((c (new-cell v '()))) (set-cell-next! c c) c))
+                 (make-cell 1)"),
+            "#0=#<cell v: 1 next: #0#>"
+        );
+        assert_eq!(
+            run(
+                "(define-record-type <node> (make-node) node? (value node-value))
+                 (make-node)"
+            ),
+            "#<node value: #<unspecified>>"
+        );
+    }
+
+    #[test]
+    fn gc_stats_test() {
+        let env = primitive_env(Engine::default());
+        let run = |source: &str| {
+            for expr in parse_program(source).unwrap() {
+                Engine::default().eval(&env, &expr).unwrap();
+            }
+        };
+        let count = |name| match assoc_field(&gc_stats(&[]).unwrap(), name).unwrap() {
+            LispVal::Number(Number::Integer(n)) => n,
+            other => panic!("{}", other),
+        };
+        let collections = count("collections");
+        let collected = count("collected");
+        // A million pairs that each point to themselves, and a million
+        // procedures stored in the frames they close over.
+        run("(define (churn n)
+               (if (> n 0)
+                   (let ((p (cons n n)))
+                     (define (self) self)
+                     (set-cdr! p p)
+                     (churn (- n 1)))))
+             (churn 1000000)");
+        assert!(count("collections") > collections);
+        assert!(count("collected") >= collected + 2_000_000);
+        assert!(count("live") < 500_000);
+        assert!(gc_stats(&[int(1)]).is_err());
+    }
+}
@@ -0,0 +1,676 @@
+use super::{predicate, unpack_num, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::{parse_number, Number};
+use crate::parser::{LispVal, PrimitiveFn};
+use std::cmp::Ordering;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("+", add),
+    ("-", sub),
+    ("*", mul),
+    ("/", div),
+    ("quotient", quotient),
+    ("remainder", remainder),
+    ("modulo", modulo),
+    ("truncate-quotient", quotient),
+    ("truncate-remainder", remainder),
+    ("floor-quotient", floor_quotient),
+    ("floor-remainder", modulo),
+    ("=", num_eq),
+    ("<", num_lt),
+    (">", num_gt),
+    ("<=", num_le),
+    (">=", num_ge),
+    ("number?", is_number),
+    ("complex?", is_number),
+    ("real?", is_real),
+    ("rational?", is_rational),
+    ("integer?", is_integer),
+    ("exact?", is_exact),
+    ("inexact?", is_inexact),
+    ("exact-integer?", is_exact_integer),
+    ("zero?", is_zero),
+    ("positive?", is_positive),
+    ("negative?", is_negative),
+    ("odd?", is_odd),
+    ("even?", is_even),
+    ("max", max),
+    ("min", min),
+    ("abs", abs),
+    ("gcd", gcd),
+    ("lcm", lcm),
+    ("numerator", numerator),
+    ("denominator", denominator),
+    ("floor", floor),
+    ("ceiling", ceiling),
+    ("truncate", truncate),
+    ("round", round),
+    ("rationalize", rationalize),
+    ("square", square),
+    ("exact", exact),
+    ("inexact", inexact),
+    ("expt", expt),
+    ("%exact-integer-sqrt", exact_integer_sqrt),
+    ("number->string", number_to_string),
+    ("string->number", string_to_number),
+];
+
+fn fold_numbers(
+    args: &[LispVal],
+    identity: i64,
+    op: fn(&Number, &Number) -> Number,
+) -> PrimitiveResult {
+    let mut acc = Number::Integer(identity);
+    for arg in args {
+        acc = op(&acc, unpack_num(arg)?);
+    }
+    Ok(LispVal::Number(acc))
+}
+
+fn add(args: &[LispVal]) -> PrimitiveResult {
+    fold_numbers(args, 0, Number::add)
+}
+
+fn sub(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [] => Err(LispError::NumArgs(1, vec![])),
+        [n] => Ok(LispVal::Number(Number::Integer(0).sub(unpack_num(n)?))),
+        [first, rest @ ..] => {
+            let mut acc = unpack_num(first)?.clone();
+            for arg in rest {
+                acc = acc.sub(unpack_num(arg)?);
+            }
+            Ok(LispVal::Number(acc))
+        }
+    }
+}
+
+fn mul(args: &[LispVal]) -> PrimitiveResult {
+    fold_numbers(args, 1, Number::mul)
+}
+
+fn div(args: &[LispVal]) -> PrimitiveResult {
+    let divide = |a: &Number, b: &Number| {
+        a.div(b)
+            .ok_or_else(|| LispError::Default("Division by zero".to_owned()))
+    };
+    match args {
+        [] => Err(LispError::NumArgs(1, vec![])),
+        [n] => Ok(LispVal::Number(divide(
+            &Number::Integer(1),
+            unpack_num(n)?,
+        )?)),
+        [first, rest @ ..] => {
+            let mut acc = unpack_num(first)?.clone();
+            for arg in rest {
+                acc = divide(&acc, unpack_num(arg)?)?;
+            }
+            Ok(LispVal::Number(acc))
+        }
+    }
+}
+
+fn integer_division(
+    args: &[LispVal],
+    op: fn(&Number, &Number) -> Option<Number>,
+) -> PrimitiveResult {
+    match args {
+        [a, b] => {
+            let (a, b) = (unpack_num(a)?, unpack_num(b)?);
+            match op(a, b) {
+                Some(n) => Ok(LispVal::Number(n)),
+                None if b.is_zero() => Err(LispError::Default("Division by zero".to_owned())),
+                None => Err(LispError::TypeMismatch(
+                    "integer",
+                    LispVal::Number(a.clone()),
+                )),
+            }
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn quotient(args: &[LispVal]) -> PrimitiveResult {
+    integer_division(args, Number::quotient)
+}
+
+fn remainder(args: &[LispVal]) -> PrimitiveResult {
+    integer_division(args, Number::remainder)
+}
+
+fn modulo(args: &[LispVal]) -> PrimitiveResult {
+    integer_division(args, Number::modulo)
+}
+
+fn floor_quotient(args: &[LispVal]) -> PrimitiveResult {
+    integer_division(args, Number::floor_quotient)
+}
+
+fn compare_numbers(args: &[LispVal], accept: fn(Ordering) -> bool) -> PrimitiveResult {
+    if args.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    let nums = args.iter().map(unpack_num).collect::<Result<Vec<_>, _>>()?;
+    Ok(LispVal::Boolean(
+        nums.windows(2)
+            .all(|pair| pair[0].num_cmp(pair[1]).is_some_and(accept)),
+    ))
+}
+
+fn num_eq(args: &[LispVal]) -> PrimitiveResult {
+    if args.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    let nums = args.iter().map(unpack_num).collect::<Result<Vec<_>, _>>()?;
+    Ok(LispVal::Boolean(
+        nums.windows(2).all(|pair| pair[0].num_eq(pair[1])),
+    ))
+}
+
+fn num_lt(args: &[LispVal]) -> PrimitiveResult {
+    compare_numbers(args, |o| o == Ordering::Less)
+}
+
+fn num_gt(args: &[LispVal]) -> PrimitiveResult {
+    compare_numbers(args, |o| o == Ordering::Greater)
+}
+
+fn num_le(args: &[LispVal]) -> PrimitiveResult {
+    compare_numbers(args, |o| o != Ordering::Greater)
+}
+
+fn num_ge(args: &[LispVal]) -> PrimitiveResult {
+    compare_numbers(args, |o| o != Ordering::Less)
+}
+
+fn is_number(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Number(_)))
+}
+
+fn is_real(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| match value {
+        LispVal::Number(Number::Complex(_)) => false,
+        LispVal::Number(_) => true,
+        _ => false,
+    })
+}
+
+// Every finite real can be written as a fraction; infinities and NaN cannot.
+fn is_rational(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| match value {
+        LispVal::Number(Number::Real(x)) => x.is_finite(),
+        LispVal::Number(n) => n.is_exact(),
+        _ => false,
+    })
+}
+
+fn is_integer(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| match value {
+        LispVal::Number(n) => n.is_integer(),
+        _ => false,
+    })
+}
+
+fn is_exact_integer(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| {
+        matches!(
+            value,
+            LispVal::Number(Number::Integer(_)) | LispVal::Number(Number::BigInteger(_))
+        )
+    })
+}
+
+/// Tests a property of the single numeric argument, which `test` reports as
+/// `None` when the number is not of the `expected` kind.
+fn test_number(
+    args: &[LispVal],
+    expected: &'static str,
+    test: fn(&Number) -> Option<bool>,
+) -> PrimitiveResult {
+    match args {
+        [value] => match test(unpack_num(value)?) {
+            Some(result) => Ok(LispVal::Boolean(result)),
+            None => Err(LispError::TypeMismatch(expected, value.clone())),
+        },
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn is_exact(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "number", |n| Some(n.is_exact()))
+}
+
+fn is_inexact(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "number", |n| Some(!n.is_exact()))
+}
+
+fn is_zero(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "number", |n| Some(n.is_zero()))
+}
+
+// The sign of a real number; NaN has none, so it is neither positive nor
+// negative.
+fn sign(n: &Number) -> Option<Option<Ordering>> {
+    match n {
+        Number::Complex(_) => None,
+        _ => Some(n.num_cmp(&Number::Integer(0))),
+    }
+}
+
+fn is_positive(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "real number", |n| {
+        sign(n).map(|o| o == Some(Ordering::Greater))
+    })
+}
+
+fn is_negative(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "real number", |n| {
+        sign(n).map(|o| o == Some(Ordering::Less))
+    })
+}
+
+fn is_odd(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "integer", |n| {
+        n.remainder(&Number::Integer(2)).map(|r| !r.is_zero())
+    })
+}
+
+fn is_even(args: &[LispVal]) -> PrimitiveResult {
+    test_number(args, "integer", |n| {
+        n.remainder(&Number::Integer(2)).map(|r| r.is_zero())
+    })
+}
+
+/// The largest or smallest of the arguments, which is inexact if any of them
+/// is.
+fn extremum(args: &[LispVal], keep: Ordering) -> PrimitiveResult {
+    if args.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    let mut best = unpack_num(&args[0])?;
+    let mut exact = true;
+    for (arg, n) in args.iter().zip(args.iter().map(unpack_num)) {
+        let n = n?;
+        if let Number::Complex(_) = n {
+            return Err(LispError::TypeMismatch("real number", arg.clone()));
+        }
+        exact &= n.is_exact();
+        match n.num_cmp(best) {
+            Some(o) if o == keep => best = n,
+            Some(_) => (),
+            // Only NaN is unordered, and it is contagious.
+            None if best.num_cmp(best).is_some() => best = n,
+            None => (),
+        }
+    }
+    Ok(LispVal::Number(if exact {
+        best.clone()
+    } else {
+        best.to_inexact()
+    }))
+}
+
+fn max(args: &[LispVal]) -> PrimitiveResult {
+    extremum(args, Ordering::Greater)
+}
+
+fn min(args: &[LispVal]) -> PrimitiveResult {
+    extremum(args, Ordering::Less)
+}
+
+/// Applies an operation to the single numeric argument, which `op` reports
+/// as `None` when the number is not of the `expected` kind.
+fn unary(
+    args: &[LispVal],
+    expected: &'static str,
+    op: fn(&Number) -> Option<Number>,
+) -> PrimitiveResult {
+    match args {
+        [value] => match op(unpack_num(value)?) {
+            Some(n) => Ok(LispVal::Number(n)),
+            None => Err(LispError::TypeMismatch(expected, value.clone())),
+        },
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn abs(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "real number", |n| match sign(n)? {
+        Some(Ordering::Less) => Some(Number::Integer(0).sub(n)),
+        _ => Some(n.clone()),
+    })
+}
+
+fn fold_integers(
+    args: &[LispVal],
+    identity: i64,
+    op: fn(&Number, &Number) -> Option<Number>,
+) -> PrimitiveResult {
+    let mut acc = Number::Integer(identity);
+    for arg in args {
+        acc = op(&acc, unpack_num(arg)?)
+            .ok_or_else(|| LispError::TypeMismatch("integer", arg.clone()))?;
+    }
+    Ok(LispVal::Number(acc))
+}
+
+fn gcd(args: &[LispVal]) -> PrimitiveResult {
+    fold_integers(args, 0, Number::gcd)
+}
+
+fn lcm(args: &[LispVal]) -> PrimitiveResult {
+    fold_integers(args, 1, Number::lcm)
+}
+
+fn numerator(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "rational number", Number::numerator)
+}
+
+fn denominator(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "rational number", Number::denominator)
+}
+
+fn floor(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "real number", Number::floor)
+}
+
+fn ceiling(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "real number", Number::ceiling)
+}
+
+fn truncate(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "real number", Number::truncate)
+}
+
+fn round(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "real number", Number::round)
+}
+
+fn rationalize(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [x, y] => match unpack_num(x)?.rationalize(unpack_num(y)?) {
+            Some(n) => Ok(LispVal::Number(n)),
+            None => Err(LispError::TypeMismatch(
+                "rational number",
+                LispVal::list(args.to_vec()),
+            )),
+        },
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn square(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "number", |n| Some(n.mul(n)))
+}
+
+fn exact(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "finite real number", Number::to_exact)
+}
+
+fn inexact(args: &[LispVal]) -> PrimitiveResult {
+    unary(args, "number", |n| Some(n.to_inexact()))
+}
+
+fn expt(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [base, power] => match unpack_num(base)?.expt(unpack_num(power)?) {
+            Some(n) => Ok(LispVal::Number(n)),
+            None => Err(LispError::Default("Division by zero".to_owned())),
+        },
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+/// `(%exact-integer-sqrt k)` returns the list `(s r)`, which the prelude's
+/// `exact-integer-sqrt` returns as two values.
+fn exact_integer_sqrt(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value] => match unpack_num(value)?.exact_integer_sqrt() {
+            Some((s, r)) => Ok(LispVal::list(vec![LispVal::Number(s), LispVal::Number(r)])),
+            None => Err(LispError::TypeMismatch(
+                "exact non-negative integer",
+                value.clone(),
+            )),
+        },
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn unpack_radix(args: &[LispVal]) -> Result<u32, LispError> {
+    match args.first() {
+        None => Ok(10),
+        Some(LispVal::Number(Number::Integer(radix @ (2 | 8 | 10 | 16)))) => Ok(*radix as u32),
+        Some(other) => Err(LispError::TypeMismatch(
+            "radix 2, 8, 10 or 16",
+            other.clone(),
+        )),
+    }
+}
+
+fn number_to_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value, radix @ ..] if radix.len() <= 1 => {
+            let radix = unpack_radix(radix)?;
+            match unpack_num(value)?.to_string_radix(radix) {
+                Some(s) => Ok(LispVal::string(s)),
+                None => Err(LispError::TypeMismatch("exact number", value.clone())),
+            }
+        }
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+/// `(string->number string [radix])` reads a number the way the parser
+/// does, prefixes included, and returns `#f` if the string is not one.
+fn string_to_number(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::String(s), radix @ ..] if radix.len() <= 1 => {
+            let text: String = s.borrow().iter().collect();
+            Ok(match parse_number(&text, unpack_radix(radix)?) {
+                Some(n) => LispVal::Number(n),
+                None => LispVal::Boolean(false),
+            })
+        }
+        [other, ..] => Err(LispError::TypeMismatch("string", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::number::*;
+    use crate::parser::int;
+    use crate::primitives::numbers::*;
+
+    fn real(x: f64) -> LispVal {
+        LispVal::Number(Number::Real(x))
+    }
+
+    fn read(text: &str) -> LispVal {
+        LispVal::Number(parse_number(text, 10).unwrap())
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let nums = [int(7), int(2)];
+        assert_eq!(add(&nums).unwrap(), int(9));
+        assert_eq!(sub(&nums).unwrap(), int(5));
+        assert_eq!(sub(&[int(3)]).unwrap(), int(-3));
+        assert_eq!(mul(&[]).unwrap(), int(1));
+        assert_eq!(quotient(&nums).unwrap(), int(3));
+        assert_eq!(remainder(&nums).unwrap(), int(1));
+        assert_eq!(modulo(&[int(-7), int(2)]).unwrap(), int(1));
+        assert!(quotient(&[int(1), int(0)]).is_err());
+        assert!(div(&[int(1), int(0)]).is_err());
+        assert_eq!(
+            div(&[int(6), int(4)]).unwrap(),
+            LispVal::Number(Number::Integer(3).div(&Number::Integer(2)).unwrap())
+        );
+        assert_eq!(num_gt(&nums).unwrap(), LispVal::Boolean(true));
+        assert_eq!(
+            num_eq(&[int(2), LispVal::Number(Number::Real(2.0))]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            num_lt(&[int(1), int(2), int(2)]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn integer_division_test() {
+        let nums = [int(-7), int(2)];
+        assert_eq!(floor_quotient(&nums).unwrap(), int(-4));
+        assert_eq!(modulo(&nums).unwrap(), int(1));
+        assert_eq!(quotient(&nums).unwrap(), int(-3));
+        assert_eq!(remainder(&nums).unwrap(), int(-1));
+        assert!(floor_quotient(&[int(1), int(0)]).is_err());
+        assert!(floor_quotient(&[real(1.5), int(1)]).is_err());
+        assert_eq!(gcd(&[int(32), int(-36)]).unwrap(), int(4));
+        assert_eq!(gcd(&[]).unwrap(), int(0));
+        assert_eq!(lcm(&[int(32), int(-36)]).unwrap(), int(288));
+        assert_eq!(lcm(&[real(32.0), int(-36)]).unwrap(), real(288.0));
+        assert!(gcd(&[real(0.5)]).is_err());
+        assert_eq!(
+            exact_integer_sqrt(&[int(17)]).unwrap(),
+            LispVal::list(vec![int(4), int(1)])
+        );
+        assert!(exact_integer_sqrt(&[int(-1)]).is_err());
+        assert!(exact_integer_sqrt(&[real(4.0)]).is_err());
+    }
+
+    #[test]
+    fn predicate_test() {
+        let yes = LispVal::Boolean(true);
+        let no = LispVal::Boolean(false);
+        let complex = || read("1+2i");
+        let half = || read("1/2");
+        let string = || LispVal::string("1".to_owned());
+        assert_eq!(is_number(&[complex()]).unwrap(), yes);
+        assert_eq!(is_number(&[string()]).unwrap(), no);
+        assert_eq!(is_real(&[complex()]).unwrap(), no);
+        assert_eq!(is_real(&[half()]).unwrap(), yes);
+        assert_eq!(is_rational(&[half()]).unwrap(), yes);
+        assert_eq!(is_rational(&[real(f64::INFINITY)]).unwrap(), no);
+        assert_eq!(is_integer(&[real(3.0)]).unwrap(), yes);
+        assert_eq!(is_integer(&[half()]).unwrap(), no);
+        assert_eq!(is_integer(&[string()]).unwrap(), no);
+        assert_eq!(is_exact_integer(&[real(3.0)]).unwrap(), no);
+        assert_eq!(
+            is_exact_integer(&[read("100000000000000000000")]).unwrap(),
+            yes
+        );
+        assert_eq!(is_exact(&[half()]).unwrap(), yes);
+        assert_eq!(is_inexact(&[complex()]).unwrap(), yes);
+        assert!(is_exact(&[string()]).is_err());
+        assert_eq!(is_zero(&[real(-0.0)]).unwrap(), yes);
+        assert_eq!(is_positive(&[half()]).unwrap(), yes);
+        assert_eq!(is_negative(&[half()]).unwrap(), no);
+        assert_eq!(is_positive(&[real(f64::NAN)]).unwrap(), no);
+        assert!(is_negative(&[complex()]).is_err());
+        assert_eq!(is_odd(&[int(-3)]).unwrap(), yes);
+        assert_eq!(is_even(&[real(4.0)]).unwrap(), yes);
+        assert_eq!(is_even(&[int(0)]).unwrap(), yes);
+        assert!(is_odd(&[real(1.5)]).is_err());
+    }
+
+    #[test]
+    fn extremum_test() {
+        assert_eq!(max(&[int(1), int(3), int(2)]).unwrap(), int(3));
+        assert_eq!(min(&[int(1), read("1/2")]).unwrap(), read("1/2"));
+        // Any inexact argument makes the result inexact.
+        assert_eq!(max(&[int(3), real(2.5)]).unwrap(), real(3.0));
+        assert!(match max(&[int(1), real(f64::NAN)]).unwrap() {
+            LispVal::Number(Number::Real(x)) => x.is_nan(),
+            _ => false,
+        });
+        assert!(max(&[]).is_err());
+        assert!(min(&[int(1), read("1+i")]).is_err());
+    }
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(abs(&[int(-7)]).unwrap(), int(7));
+        assert_eq!(abs(&[real(-7.5)]).unwrap(), real(7.5));
+        assert_eq!(abs(&[int(i64::MIN)]).unwrap(), read("9223372036854775808"));
+        assert!(abs(&[read("1+i")]).is_err());
+        assert_eq!(floor(&[real(-4.3)]).unwrap(), real(-5.0));
+        assert_eq!(ceiling(&[real(-4.3)]).unwrap(), real(-4.0));
+        assert_eq!(truncate(&[real(-4.3)]).unwrap(), real(-4.0));
+        assert_eq!(round(&[real(-4.3)]).unwrap(), real(-4.0));
+        assert_eq!(floor(&[read("7/2")]).unwrap(), int(3));
+        assert_eq!(round(&[read("7/2")]).unwrap(), int(4));
+        assert_eq!(round(&[real(3.5)]).unwrap(), real(4.0));
+        assert_eq!(round(&[int(7)]).unwrap(), int(7));
+        assert_eq!(numerator(&[read("6/4")]).unwrap(), int(3));
+        assert_eq!(denominator(&[read("6/4")]).unwrap(), int(2));
+        assert_eq!(denominator(&[int(0)]).unwrap(), int(1));
+        assert_eq!(
+            rationalize(&[read("3/10"), read("1/10")]).unwrap(),
+            read("1/3")
+        );
+        assert_eq!(
+            rationalize(&[real(0.3), read("1/10")]).unwrap(),
+            real(1.0 / 3.0)
+        );
+    }
+
+    #[test]
+    fn exactness_test() {
+        assert_eq!(exact(&[real(2.5)]).unwrap(), read("5/2"));
+        assert_eq!(exact(&[int(2)]).unwrap(), int(2));
+        assert!(exact(&[real(f64::INFINITY)]).is_err());
+        assert_eq!(inexact(&[read("1/4")]).unwrap(), real(0.25));
+        assert_eq!(square(&[int(42)]).unwrap(), int(1764));
+        assert_eq!(square(&[real(2.0)]).unwrap(), real(4.0));
+        assert_eq!(expt(&[int(2), int(10)]).unwrap(), int(1024));
+        assert_eq!(expt(&[int(2), int(-1)]).unwrap(), read("1/2"));
+        assert_eq!(expt(&[real(2.0), int(3)]).unwrap(), real(8.0));
+        assert_eq!(expt(&[int(9), read("1/2")]).unwrap(), real(3.0));
+        assert!(expt(&[int(0), int(-1)]).is_err());
+    }
+
+    #[test]
+    fn number_string_test() {
+        let string = |s: &str| LispVal::string(s.to_owned());
+        assert_eq!(number_to_string(&[int(255)]).unwrap(), string("255"));
+        assert_eq!(
+            number_to_string(&[int(255), int(16)]).unwrap(),
+            string("ff")
+        );
+        assert_eq!(
+            number_to_string(&[read("-1/3"), int(2)]).unwrap(),
+            string("-1/11")
+        );
+        assert_eq!(number_to_string(&[real(1.5)]).unwrap(), string("1.5"));
+        assert!(number_to_string(&[real(1.5), int(2)]).is_err());
+        assert!(number_to_string(&[int(1), int(3)]).is_err());
+        assert_eq!(string_to_number(&[string("100")]).unwrap(), int(100));
+        assert_eq!(
+            string_to_number(&[string("100"), int(16)]).unwrap(),
+            int(256)
+        );
+        assert_eq!(string_to_number(&[string("#x1F")]).unwrap(), int(31));
+        assert_eq!(string_to_number(&[string("1e2")]).unwrap(), real(100.0));
+        assert_eq!(
+            string_to_number(&[string("abc")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(string_to_number(&[int(1)]).is_err());
+        // Whatever `number->string` writes, `string->number` reads back.
+        for text in &[
+            "-17",
+            "1/3",
+            "2.5",
+            "1.0-2.0i",
+            "123456789012345678901234567890",
+        ] {
+            let n = read(text);
+            assert_eq!(
+                string_to_number(&[number_to_string(std::slice::from_ref(&n)).unwrap()]).unwrap(),
+                n
+            );
+        }
+    }
+}
@@ -0,0 +1,803 @@
+//! Ports. R7RS makes the port argument of most of these procedures optional,
+//! defaulting to the current port; the prelude defines them that way, around
+//! the `%`-prefixed versions here that always take the port.
+
+use super::bytevectors::{unpack_byte, unpack_bytevector};
+use super::strings::{string_range, unpack_string};
+use super::{predicate, unpack_index, unpack_range, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::{LispVal, PrimitiveFn};
+use crate::port::Port;
+use std::rc::Rc;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("port?", is_port),
+    ("input-port?", is_input_port),
+    ("output-port?", is_output_port),
+    ("textual-port?", is_textual_port),
+    ("binary-port?", is_binary_port),
+    ("input-port-open?", is_input_port_open),
+    ("output-port-open?", is_output_port_open),
+    ("close-port", close_port),
+    ("close-input-port", close_input_port),
+    ("close-output-port", close_output_port),
+    ("open-input-string", open_input_string),
+    ("open-output-string", open_output_string),
+    ("get-output-string", get_output_string),
+    ("open-input-bytevector", open_input_bytevector),
+    ("open-output-bytevector", open_output_bytevector),
+    ("get-output-bytevector", get_output_bytevector),
+    ("eof-object", eof_object),
+    ("eof-object?", is_eof_object),
+    ("%standard-input-port", standard_input_port),
+    ("%standard-output-port", standard_output_port),
+    ("%standard-error-port", standard_error_port),
+    ("%read-char", read_char),
+    ("%peek-char", peek_char),
+    ("%read-line", read_line),
+    ("%read-string", read_string),
+    ("%char-ready?", is_char_ready),
+    ("%read-u8", read_u8),
+    ("%peek-u8", peek_u8),
+    ("%u8-ready?", is_u8_ready),
+    ("%read-bytevector", read_bytevector),
+    ("%read-bytevector!", read_bytevector_into),
+    ("%write", write),
+    ("%display", display),
+    ("%newline", newline),
+    ("%write-char", write_char),
+    ("%write-string", write_string),
+    ("%write-u8", write_u8),
+    ("%write-bytevector", write_bytevector),
+    ("%flush-output-port", flush_output_port),
+];
+
+fn unpack_port(value: &LispVal) -> Result<&Rc<Port>, LispError> {
+    match value {
+        LispVal::Port(port) => Ok(port),
+        _ => Err(LispError::TypeMismatch("port", value.clone())),
+    }
+}
+
+/// The port `value`, if it is an open port of the kind `expected` names.
+fn unpack_open<'a>(
+    value: &'a LispVal,
+    input: bool,
+    textual: bool,
+    expected: &'static str,
+) -> Result<&'a Rc<Port>, LispError> {
+    match value {
+        LispVal::Port(port) if port.is_input() == input && port.is_textual() == textual => {
+            if port.is_open() {
+                Ok(port)
+            } else {
+                Err(LispError::Default(format!("Port is closed: {}", value)))
+            }
+        }
+        _ => Err(LispError::TypeMismatch(expected, value.clone())),
+    }
+}
+
+fn text_in(value: &LispVal) -> Result<&Rc<Port>, LispError> {
+    unpack_open(value, true, true, "textual input port")
+}
+
+fn bytes_in(value: &LispVal) -> Result<&Rc<Port>, LispError> {
+    unpack_open(value, true, false, "binary input port")
+}
+
+fn text_out(value: &LispVal) -> Result<&Rc<Port>, LispError> {
+    unpack_open(value, false, true, "textual output port")
+}
+
+fn bytes_out(value: &LispVal) -> Result<&Rc<Port>, LispError> {
+    unpack_open(value, false, false, "binary output port")
+}
+
+fn or_eof<T>(value: Option<T>, convert: impl FnOnce(T) -> LispVal) -> LispVal {
+    value.map(convert).unwrap_or(LispVal::Eof)
+}
+
+fn byte(b: u8) -> LispVal {
+    LispVal::Number(Number::Integer(b as i64))
+}
+
+fn is_port(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Port(_)))
+}
+
+fn is_input_port(args: &[LispVal]) -> PrimitiveResult {
+    predicate(
+        args,
+        |value| matches!(value, LispVal::Port(port) if port.is_input()),
+    )
+}
+
+fn is_output_port(args: &[LispVal]) -> PrimitiveResult {
+    predicate(
+        args,
+        |value| matches!(value, LispVal::Port(port) if !port.is_input()),
+    )
+}
+
+fn is_textual_port(args: &[LispVal]) -> PrimitiveResult {
+    predicate(
+        args,
+        |value| matches!(value, LispVal::Port(port) if port.is_textual()),
+    )
+}
+
+fn is_binary_port(args: &[LispVal]) -> PrimitiveResult {
+    predicate(
+        args,
+        |value| matches!(value, LispVal::Port(port) if !port.is_textual()),
+    )
+}
+
+fn is_port_open(args: &[LispVal], input: bool, expected: &'static str) -> PrimitiveResult {
+    match args {
+        [LispVal::Port(port)] if port.is_input() == input => Ok(LispVal::Boolean(port.is_open())),
+        [other] => Err(LispError::TypeMismatch(expected, other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn is_input_port_open(args: &[LispVal]) -> PrimitiveResult {
+    is_port_open(args, true, "input port")
+}
+
+fn is_output_port_open(args: &[LispVal]) -> PrimitiveResult {
+    is_port_open(args, false, "output port")
+}
+
+fn close_port(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => unpack_port(port)?.close(),
+        _ => return Err(LispError::NumArgs(1, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn close_input_port(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Port(port)] if port.is_input() => port.close(),
+        [other] => return Err(LispError::TypeMismatch("input port", other.clone())),
+        _ => return Err(LispError::NumArgs(1, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn close_output_port(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Port(port)] if !port.is_input() => port.close(),
+        [other] => return Err(LispError::TypeMismatch("output port", other.clone())),
+        _ => return Err(LispError::NumArgs(1, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn open_input_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s] => {
+            let text: String = unpack_string(s)?.iter().collect();
+            Ok(LispVal::Port(Port::input_string(&text)))
+        }
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn open_output_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [] => Ok(LispVal::Port(Port::output_string())),
+        _ => Err(LispError::NumArgs(0, args.to_vec())),
+    }
+}
+
+fn get_output_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Port(port)] if port.output_text().is_some() => {
+            Ok(LispVal::string(port.output_text().unwrap()))
+        }
+        [other] => Err(LispError::TypeMismatch("string output port", other.clone())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn open_input_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv] => Ok(LispVal::Port(Port::input_bytes(
+            &unpack_bytevector(bv)?.borrow(),
+        ))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn open_output_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [] => Ok(LispVal::Port(Port::output_bytes())),
+        _ => Err(LispError::NumArgs(0, args.to_vec())),
+    }
+}
+
+fn get_output_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Port(port)] if port.output_bytevector().is_some() => {
+            Ok(LispVal::bytevector(port.output_bytevector().unwrap()))
+        }
+        [other] => Err(LispError::TypeMismatch(
+            "bytevector output port",
+            other.clone(),
+        )),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn eof_object(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [] => Ok(LispVal::Eof),
+        _ => Err(LispError::NumArgs(0, args.to_vec())),
+    }
+}
+
+fn is_eof_object(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Eof))
+}
+
+fn standard_port(args: &[LispVal], make: fn() -> Rc<Port>) -> PrimitiveResult {
+    match args {
+        [] => Ok(LispVal::Port(make())),
+        _ => Err(LispError::NumArgs(0, args.to_vec())),
+    }
+}
+
+fn standard_input_port(args: &[LispVal]) -> PrimitiveResult {
+    standard_port(args, Port::stdin)
+}
+
+fn standard_output_port(args: &[LispVal]) -> PrimitiveResult {
+    standard_port(args, Port::stdout)
+}
+
+fn standard_error_port(args: &[LispVal]) -> PrimitiveResult {
+    standard_port(args, Port::stderr)
+}
+
+fn read_char(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => Ok(or_eof(text_in(port)?.read_char(true)?, LispVal::Char)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn peek_char(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => Ok(or_eof(text_in(port)?.read_char(false)?, LispVal::Char)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+/// `(read-line port)` reads up to the end of the line, which is left out of
+/// the string returned, whether it is a linefeed or a carriage return and
+/// linefeed.
+fn read_line(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => {
+            let line = text_in(port)?.read_until(usize::MAX, Some('\n'))?;
+            Ok(or_eof(line, |mut line| {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                LispVal::string(line)
+            }))
+        }
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn read_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [k, port] => {
+            let (k, port) = (unpack_index(k)?, text_in(port)?);
+            if k == 0 {
+                return Ok(LispVal::string(String::new()));
+            }
+            Ok(or_eof(port.read_until(k, None)?, LispVal::string))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn is_char_ready(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => Ok(LispVal::Boolean(text_in(port)?.char_ready())),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn read_u8(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => Ok(or_eof(bytes_in(port)?.read_u8(true), byte)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn peek_u8(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => Ok(or_eof(bytes_in(port)?.read_u8(false), byte)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+// Bytevector ports never wait for input.
+fn is_u8_ready(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => bytes_in(port).map(|_| LispVal::Boolean(true)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+/// Reads up to `k` bytes from `port`, or none at all at the end of the input.
+fn read_bytes(port: &Port, k: usize) -> Vec<u8> {
+    let mut bytes = vec![];
+    while bytes.len() < k {
+        match port.read_u8(true) {
+            Some(b) => bytes.push(b),
+            None => break,
+        }
+    }
+    bytes
+}
+
+fn read_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [k, port] => {
+            let (k, port) = (unpack_index(k)?, bytes_in(port)?);
+            let bytes = read_bytes(port, k);
+            if bytes.is_empty() && k > 0 {
+                Ok(LispVal::Eof)
+            } else {
+                Ok(LispVal::bytevector(bytes))
+            }
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+/// `(read-bytevector! bv port start end)` reads into `bv` between the
+/// optional `start` and `end`, returning how many bytes it read.
+fn read_bytevector_into(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv, port, range @ ..] if range.len() <= 2 => {
+            let (bv, port) = (unpack_bytevector(bv)?, bytes_in(port)?);
+            let (start, end) = unpack_range(range, bv.borrow().len())?;
+            let bytes = read_bytes(port, end - start);
+            if bytes.is_empty() && end > start {
+                return Ok(LispVal::Eof);
+            }
+            bv.borrow_mut()[start..start + bytes.len()].copy_from_slice(&bytes);
+            Ok(LispVal::Number(Number::Integer(bytes.len() as i64)))
+        }
+        _ => Err(LispError::NumArgs(4, args.to_vec())),
+    }
+}
+
+fn write(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value, port] => text_out(port)?.write_str(&value.to_string())?,
+        _ => return Err(LispError::NumArgs(2, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn display(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [value, port] => text_out(port)?.write_str(&value.display().to_string())?,
+        _ => return Err(LispError::NumArgs(2, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn newline(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [port] => text_out(port)?.write_str("\n")?,
+        _ => return Err(LispError::NumArgs(1, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn write_char(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Char(c), port] => text_out(port)?.write_str(c.encode_utf8(&mut [0; 4]))?,
+        [other, _] => return Err(LispError::TypeMismatch("char", other.clone())),
+        _ => return Err(LispError::NumArgs(2, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+/// `(write-string s port start end)` writes the characters of `s` between
+/// the optional `start` and `end`.
+fn write_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, port, range @ ..] if range.len() <= 2 => {
+            let text: String = string_range(s, range)?.into_iter().collect();
+            text_out(port)?.write_str(&text)?;
+        }
+        _ => return Err(LispError::NumArgs(4, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn write_u8(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [b, port] => bytes_out(port)?.write_bytes(&[unpack_byte(b)?]),
+        _ => return Err(LispError::NumArgs(2, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn write_bytevector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [bv, port, range @ ..] if range.len() <= 2 => {
+            let bytes = unpack_bytevector(bv)?.borrow();
+            let (start, end) = unpack_range(range, bytes.len())?;
+            bytes_out(port)?.write_bytes(&bytes[start..end]);
+        }
+        _ => return Err(LispError::NumArgs(4, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+fn flush_output_port(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Port(port)] if !port.is_input() => {
+            if port.is_open() {
+                port.flush()?;
+            }
+        }
+        [other] => return Err(LispError::TypeMismatch("output port", other.clone())),
+        _ => return Err(LispError::NumArgs(1, args.to_vec())),
+    }
+    Ok(LispVal::Unspecified)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::ports::*;
+
+    fn string(s: &str) -> LispVal {
+        LispVal::string(s.to_owned())
+    }
+
+    fn input(s: &str) -> LispVal {
+        open_input_string(&[string(s)]).unwrap()
+    }
+
+    fn bytes_input(bytes: &[u8]) -> LispVal {
+        open_input_bytevector(&[LispVal::bytevector(bytes.to_vec())]).unwrap()
+    }
+
+    fn output() -> LispVal {
+        open_output_string(&[]).unwrap()
+    }
+
+    fn bytes_output() -> LispVal {
+        open_output_bytevector(&[]).unwrap()
+    }
+
+    fn written(port: &LispVal) -> String {
+        get_output_string(std::slice::from_ref(port))
+            .unwrap()
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn port_predicate_test() {
+        assert_eq!(is_port(&[input("")]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_port(&[string("")]).unwrap(), LispVal::Boolean(false));
+        assert_eq!(is_input_port(&[input("")]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_input_port(&[output()]).unwrap(), LispVal::Boolean(false));
+        assert_eq!(is_output_port(&[output()]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(
+            is_output_port(&[input("")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert_eq!(
+            is_textual_port(&[output()]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            is_textual_port(&[bytes_output()]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert_eq!(
+            is_binary_port(&[bytes_input(&[])]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            is_binary_port(&[input("")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn close_port_test() {
+        let port = input("abc");
+        assert_eq!(
+            is_input_port_open(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Boolean(true)
+        );
+        close_port(std::slice::from_ref(&port)).unwrap();
+        assert_eq!(
+            is_input_port_open(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(read_char(&[port]).is_err());
+        assert!(close_port(&[string("")]).is_err());
+    }
+
+    #[test]
+    fn close_input_port_test() {
+        let port = input("");
+        close_input_port(std::slice::from_ref(&port)).unwrap();
+        assert_eq!(
+            is_input_port_open(&[port]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(close_input_port(&[output()]).is_err());
+    }
+
+    #[test]
+    fn close_output_port_test() {
+        let port = output();
+        close_output_port(std::slice::from_ref(&port)).unwrap();
+        assert_eq!(
+            is_output_port_open(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(write_char(&[LispVal::Char('a'), port]).is_err());
+        assert!(close_output_port(&[input("")]).is_err());
+        assert!(is_output_port_open(&[input("")]).is_err());
+    }
+
+    #[test]
+    fn string_port_test() {
+        let port = output();
+        write_string(&[string("hello"), port.clone()]).unwrap();
+        assert_eq!(written(&port), "hello");
+        assert!(get_output_string(&[input("")]).is_err());
+        assert_eq!(read_char(&[input("λ")]).unwrap(), LispVal::Char('λ'));
+        assert!(open_input_string(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn bytevector_port_test() {
+        let port = bytes_output();
+        write_u8(&[int(7), port.clone()]).unwrap();
+        assert_eq!(
+            get_output_bytevector(&[port]).unwrap(),
+            LispVal::bytevector(vec![7])
+        );
+        assert!(get_output_bytevector(&[output()]).is_err());
+        assert_eq!(read_u8(&[bytes_input(&[9])]).unwrap(), int(9));
+        assert!(open_input_bytevector(&[string("")]).is_err());
+    }
+
+    #[test]
+    fn eof_object_test() {
+        assert_eq!(eof_object(&[]).unwrap(), LispVal::Eof);
+        assert_eq!(
+            is_eof_object(&[LispVal::Eof]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(is_eof_object(&[int(0)]).unwrap(), LispVal::Boolean(false));
+        assert!(eof_object(&[int(0)]).is_err());
+    }
+
+    #[test]
+    fn standard_port_test() {
+        let stdout = standard_output_port(&[]).unwrap();
+        assert_eq!(is_output_port(&[stdout]).unwrap(), LispVal::Boolean(true));
+        let stdin = standard_input_port(&[]).unwrap();
+        assert_eq!(is_textual_port(&[stdin]).unwrap(), LispVal::Boolean(true));
+        let stderr = standard_error_port(&[]).unwrap();
+        assert_eq!(flush_output_port(&[stderr]).unwrap(), LispVal::Unspecified);
+    }
+
+    #[test]
+    fn read_char_test() {
+        let port = input("ab");
+        assert_eq!(
+            read_char(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Char('a')
+        );
+        assert_eq!(
+            read_char(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Char('b')
+        );
+        assert_eq!(read_char(&[port]).unwrap(), LispVal::Eof);
+        assert!(read_char(&[bytes_input(&[1])]).is_err());
+    }
+
+    #[test]
+    fn peek_char_test() {
+        let port = input("a");
+        assert_eq!(
+            peek_char(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Char('a')
+        );
+        assert_eq!(
+            peek_char(std::slice::from_ref(&port)).unwrap(),
+            LispVal::Char('a')
+        );
+        read_char(std::slice::from_ref(&port)).unwrap();
+        assert_eq!(peek_char(&[port]).unwrap(), LispVal::Eof);
+    }
+
+    #[test]
+    fn read_line_test() {
+        let port = input("one\r\ntwo\n\nthree");
+        for line in &["one", "two", "", "three"] {
+            assert_eq!(
+                read_line(std::slice::from_ref(&port)).unwrap(),
+                string(line)
+            );
+        }
+        assert_eq!(read_line(&[port]).unwrap(), LispVal::Eof);
+    }
+
+    #[test]
+    fn read_string_test() {
+        let port = input("abcde");
+        assert_eq!(read_string(&[int(2), port.clone()]).unwrap(), string("ab"));
+        assert_eq!(read_string(&[int(0), port.clone()]).unwrap(), string(""));
+        assert_eq!(read_string(&[int(9), port.clone()]).unwrap(), string("cde"));
+        assert_eq!(read_string(&[int(1), port]).unwrap(), LispVal::Eof);
+        assert!(read_string(&[int(-1), input("")]).is_err());
+    }
+
+    #[test]
+    fn char_ready_test() {
+        assert_eq!(is_char_ready(&[input("")]).unwrap(), LispVal::Boolean(true));
+        assert!(is_char_ready(&[output()]).is_err());
+    }
+
+    #[test]
+    fn read_u8_test() {
+        let port = bytes_input(&[1, 2]);
+        assert_eq!(read_u8(std::slice::from_ref(&port)).unwrap(), int(1));
+        assert_eq!(read_u8(std::slice::from_ref(&port)).unwrap(), int(2));
+        assert_eq!(read_u8(&[port]).unwrap(), LispVal::Eof);
+        assert!(read_u8(&[input("a")]).is_err());
+    }
+
+    #[test]
+    fn peek_u8_test() {
+        let port = bytes_input(&[1]);
+        assert_eq!(peek_u8(std::slice::from_ref(&port)).unwrap(), int(1));
+        assert_eq!(read_u8(std::slice::from_ref(&port)).unwrap(), int(1));
+        assert_eq!(peek_u8(&[port]).unwrap(), LispVal::Eof);
+    }
+
+    #[test]
+    fn u8_ready_test() {
+        assert_eq!(
+            is_u8_ready(&[bytes_input(&[])]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert!(is_u8_ready(&[input("")]).is_err());
+    }
+
+    #[test]
+    fn read_bytevector_test() {
+        let port = bytes_input(&[1, 2, 3]);
+        assert_eq!(
+            read_bytevector(&[int(2), port.clone()]).unwrap(),
+            LispVal::bytevector(vec![1, 2])
+        );
+        assert_eq!(
+            read_bytevector(&[int(5), port.clone()]).unwrap(),
+            LispVal::bytevector(vec![3])
+        );
+        assert_eq!(read_bytevector(&[int(1), port]).unwrap(), LispVal::Eof);
+    }
+
+    #[test]
+    fn read_bytevector_into_test() {
+        let port = bytes_input(&[1, 2, 3]);
+        let bv = LispVal::bytevector(vec![0; 4]);
+        assert_eq!(
+            read_bytevector_into(&[bv.clone(), port.clone(), int(1), int(3)]).unwrap(),
+            int(2)
+        );
+        assert_eq!(bv, LispVal::bytevector(vec![0, 1, 2, 0]));
+        assert_eq!(
+            read_bytevector_into(&[bv.clone(), port.clone()]).unwrap(),
+            int(1)
+        );
+        assert_eq!(bv, LispVal::bytevector(vec![3, 1, 2, 0]));
+        assert_eq!(read_bytevector_into(&[bv, port]).unwrap(), LispVal::Eof);
+    }
+
+    #[test]
+    fn write_test() {
+        let port = output();
+        write(&[string("a\"b"), port.clone()]).unwrap();
+        assert_eq!(written(&port), "\"a\\\"b\"");
+        assert!(write(&[int(1), input("")]).is_err());
+        assert!(write(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn display_test() {
+        let port = output();
+        display(&[string("a\"b"), port.clone()]).unwrap();
+        display(&[LispVal::Char('c'), port.clone()]).unwrap();
+        assert_eq!(written(&port), "a\"bc");
+    }
+
+    #[test]
+    fn newline_test() {
+        let port = output();
+        newline(std::slice::from_ref(&port)).unwrap();
+        assert_eq!(written(&port), "\n");
+        assert!(newline(&[]).is_err());
+    }
+
+    #[test]
+    fn write_char_test() {
+        let port = output();
+        write_char(&[LispVal::Char('λ'), port.clone()]).unwrap();
+        assert_eq!(written(&port), "λ");
+        assert!(write_char(&[string("a"), port]).is_err());
+    }
+
+    #[test]
+    fn write_string_test() {
+        let port = output();
+        write_string(&[string("hello"), port.clone(), int(1), int(3)]).unwrap();
+        write_string(&[string("λx"), port.clone(), int(1)]).unwrap();
+        assert_eq!(written(&port), "elx");
+        assert!(write_string(&[string("a"), port.clone(), int(2)]).is_err());
+        assert!(write_string(&[LispVal::Char('a'), port]).is_err());
+    }
+
+    #[test]
+    fn write_u8_test() {
+        let port = bytes_output();
+        write_u8(&[int(255), port.clone()]).unwrap();
+        assert_eq!(
+            get_output_bytevector(std::slice::from_ref(&port)).unwrap(),
+            LispVal::bytevector(vec![255])
+        );
+        assert!(write_u8(&[int(256), port]).is_err());
+        assert!(write_u8(&[int(1), output()]).is_err());
+    }
+
+    #[test]
+    fn write_bytevector_test() {
+        let port = bytes_output();
+        let bv = LispVal::bytevector(vec![1, 2, 3]);
+        write_bytevector(&[bv.clone(), port.clone()]).unwrap();
+        write_bytevector(&[bv, port.clone(), int(1), int(2)]).unwrap();
+        assert_eq!(
+            get_output_bytevector(&[port]).unwrap(),
+            LispVal::bytevector(vec![1, 2, 3, 2])
+        );
+    }
+
+    #[test]
+    fn flush_output_port_test() {
+        assert_eq!(
+            flush_output_port(&[output()]).unwrap(),
+            LispVal::Unspecified
+        );
+        assert!(flush_output_port(&[input("")]).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+;;; Procedures defined in Scheme on top of the evaluator's built-in operators.
+;;; Every top-level environment evaluates this file before anything else.
+
+(define (list . items) items)
+
+(define (call-with-current-continuation proc) (%call/cc proc))
+
+(define call/cc call-with-current-continuation)
+
+(define (dynamic-wind before thunk after) (%dynamic-wind before thunk after))
+
+(define (apply proc arg . args) (%apply proc (cons arg args)))
+
+;; `Interpreter::set_command_line` redefines `%command-line`, so that each
+;; interpreter has its own arguments.
+(define %command-line '())
+
+(define (command-line) %command-line)
+
+;; The remaining derived binding forms, after R7RS section 7.3. `letrec`
+;; evaluates its inits in order like `letrec*`, which every correct `letrec`
+;; allows.
+(define-syntax let*
+  (syntax-rules ()
+    ((_ () body ...)
+     (let () body ...))
+    ((_ ((name value) binding ...) body ...)
+     (let ((name value)) (let* (binding ...) body ...)))))
+
+(define-syntax letrec*
+  (syntax-rules ()
+    ((_ ((name init) ...) body ...)
+     (let () (define name init) ... (let () body ...)))))
+
+(define-syntax letrec
+  (syntax-rules ()
+    ((_ bindings body ...) (letrec* bindings body ...))))
+
+(define-syntax do
+  (syntax-rules ()
+    ((_ ((var init step ...) ...) (test result ...) command ...)
+     (let loop ((var init) ...)
+       (if test
+           (begin (if #f #f) result ...)
+           (begin command ... (loop (do "step" var step ...) ...)))))
+    ((_ "step" var) var)
+    ((_ "step" var step) step)))
+
+;; Multiple values travel as one object made by `values`, which
+;; `call-with-values` spreads over the consumer's parameters. A single value
+;; is passed as itself.
+(define (call-with-values producer consumer)
+  (apply consumer (%values->list (producer))))
+
+;; `let-values` collects the values of each init in a list held by a fresh
+;; variable, so that no init sees the others' formals, and then binds the
+;; formals of each in turn.
+(define-syntax let-values
+  (syntax-rules ()
+    ((_ (binding ...) body ...)
+     (%let-values (binding ...) () body ...))))
+
+(define-syntax %let-values
+  (syntax-rules ()
+    ((_ ((formals init) binding ...) (collected ...) body ...)
+     (call-with-values (lambda () init)
+       (lambda vals
+         (%let-values (binding ...) (collected ... (formals vals)) body ...))))
+    ((_ () () body ...)
+     (let () body ...))
+    ((_ () ((formals vals) collected ...) body ...)
+     (apply (lambda formals (%let-values () (collected ...) body ...)) vals))))
+
+(define-syntax let*-values
+  (syntax-rules ()
+    ((_ () body ...)
+     (let () body ...))
+    ((_ (binding rest ...) body ...)
+     (let-values (binding) (let*-values (rest ...) body ...)))))
+
+;; `define-values` defines each variable first, so that it also works in a
+;; body, and then assigns the values to them.
+(define-syntax define-values
+  (syntax-rules ()
+    ((_ formals expr)
+     (%define-values formals () () expr))))
+
+(define-syntax %define-values
+  (syntax-rules ()
+    ((_ () (var ...) (tmp ...) expr)
+     (begin
+       (define var #f) ...
+       (call-with-values (lambda () expr)
+         (lambda (tmp ...) (set! var tmp) ... (if #f #f)))))
+    ((_ (name . rest) (var ...) (tmp ...) expr)
+     (%define-values rest (var ... name) (tmp ... new) expr))
+    ((_ name (var ...) (tmp ...) expr)
+     (begin
+       (define var #f) ...
+       (define name #f)
+       (call-with-values (lambda () expr)
+         (lambda (tmp ... . new) (set! var tmp) ... (set! name new)))))))
+
+(define (floor/ n d) (values (floor-quotient n d) (floor-remainder n d)))
+
+(define (truncate/ n d) (values (truncate-quotient n d) (truncate-remainder n d)))
+
+(define (exact-integer-sqrt k) (apply values (%exact-integer-sqrt k)))
+
+;; The one-list case of `map`, which the general case uses to step through
+;; all its lists at once.
+(define (%map1 proc l)
+  (let loop ((l l) (acc '()))
+    (if (pair? l)
+        (loop (cdr l) (cons (proc (car l)) acc))
+        (reverse acc))))
+
+;; `map` and `for-each` stop at the end of the shortest list.
+(define (map proc l . ls)
+  (if (null? ls)
+      (%map1 proc l)
+      (let loop ((ls (cons l ls)) (acc '()))
+        (if (memq #f (%map1 pair? ls))
+            (reverse acc)
+            (loop (%map1 cdr ls) (cons (apply proc (%map1 car ls)) acc))))))
+
+(define (for-each proc l . ls)
+  (if (null? ls)
+      (let loop ((l l))
+        (when (pair? l)
+          (proc (car l))
+          (loop (cdr l))))
+      (let loop ((ls (cons l ls)))
+        (unless (memq #f (%map1 pair? ls))
+          (apply proc (%map1 car ls))
+          (loop (%map1 cdr ls))))))
+
+(define (vector-map proc v . vs)
+  (list->vector (apply map proc (vector->list v) (map vector->list vs))))
+
+(define (vector-for-each proc v . vs)
+  (apply for-each proc (vector->list v) (map vector->list vs)))
+
+(define (string-map proc s . ss)
+  (list->string (apply map proc (string->list s) (map string->list ss))))
+
+(define (string-for-each proc s . ss)
+  (apply for-each proc (string->list s) (map string->list ss)))
+
+(define (member x l . compare)
+  (let ((same? (if (pair? compare) (car compare) equal?)))
+    (let loop ((l l))
+      (cond ((not (pair? l)) #f)
+            ((same? x (car l)) l)
+            (else (loop (cdr l)))))))
+
+(define (assoc x alist . compare)
+  (let ((same? (if (pair? compare) (car compare) equal?)))
+    (let loop ((alist alist))
+      (cond ((not (pair? alist)) #f)
+            ((same? x (car (car alist))) (car alist))
+            (else (loop (cdr alist)))))))
+
+(define (with-exception-handler handler thunk)
+  (%with-exception-handler handler thunk))
+
+(define (raise obj) (%raise obj))
+
+(define (raise-continuable obj) (%raise-continuable obj))
+
+;; `(define-record-type <type> (constructor field ...) predicate
+;;   (field accessor [modifier]) ...)` defines a new record type and the
+;; procedures for it.
+(define-syntax define-record-type
+  (syntax-rules ()
+    ((_ type (constructor arg ...) predicate (field . procedures) ...)
+     (begin
+       (define type (%make-record-type 'type '(field ...)))
+       (define constructor (%record-constructor type 'constructor '(arg ...)))
+       (define predicate (%record-predicate type 'predicate))
+       (%define-record-field type field . procedures) ...))))
+
+(define-syntax %define-record-field
+  (syntax-rules ()
+    ((_ type field accessor)
+     (define accessor (%record-accessor type 'accessor 'field)))
+    ((_ type field accessor modifier)
+     (begin
+       (define accessor (%record-accessor type 'accessor 'field))
+       (define modifier (%record-modifier type 'modifier 'field))))))
+
+;; A parameter is a procedure returning its value. Passed the key that only
+;; `%parameterize` holds, it returns its converter and a procedure setting
+;; the value instead. `parameterize` swaps the converted values in for the
+;; extent of its body, and swaps them back out whenever control leaves it.
+(define-values (make-parameter %parameterize)
+  (let ((key (list 'parameter)))
+    (define (make-parameter value . converter)
+      (let* ((convert (if (pair? converter) (car converter) (lambda (x) x)))
+             (value (convert value)))
+        (lambda args
+          (cond ((null? args) value)
+                ((eq? (car args) key)
+                 (cons convert (lambda (new) (set! value new))))
+                (else (error "Too many arguments to a parameter" args))))))
+    (define (parameterize params vals body)
+      (let* ((access (map (lambda (param) (param key)) params))
+             (vals (map (lambda (a value) ((car a) value)) access vals)))
+        (define (swap!)
+          (let ((old (map (lambda (param) (param)) params)))
+            (for-each (lambda (a value) ((cdr a) value)) access vals)
+            (set! vals old)))
+        (dynamic-wind swap! body swap!)))
+    (values make-parameter parameterize)))
+
+(define-syntax parameterize
+  (syntax-rules ()
+    ((_ ((param value) ...) body ...)
+     (%parameterize (list param ...) (list value ...) (lambda () body ...)))))
+
+;; `(guard (var clause ...) body ...)` runs body, and if it raises, binds var
+;; to the raised object and picks a clause as `cond` would. The clauses run
+;; in the dynamic environment of the `guard`; when none applies, the object
+;; is raised again from where it was first raised.
+(define-syntax guard
+  (syntax-rules ()
+    ((_ (var clause ...) body ...)
+     ((call/cc
+        (lambda (guard-k)
+          (with-exception-handler
+            (lambda (condition)
+              ((call/cc
+                 (lambda (handler-k)
+                   (guard-k
+                     (lambda ()
+                       (let ((var condition))
+                         (%guard-clauses
+                           (handler-k (lambda () (raise-continuable condition)))
+                           clause ...))))))))
+            (lambda ()
+              (let ((result (begin body ...)))
+                (guard-k (lambda () result)))))))))))
+
+(define-syntax %guard-clauses
+  (syntax-rules (else =>)
+    ((_ reraise (else result ...))
+     (begin result ...))
+    ((_ reraise (test => receiver) clause ...)
+     (let ((value test))
+       (if value (receiver value) (%guard-clauses reraise clause ...))))
+    ((_ reraise (test) clause ...)
+     (let ((value test))
+       (if value value (%guard-clauses reraise clause ...))))
+    ((_ reraise (test result ...) clause ...)
+     (if test (begin result ...) (%guard-clauses reraise clause ...)))
+    ((_ reraise)
+     reraise)))
+
+;; The current ports are parameters, so `parameterize` can send output to a
+;; string port.
+(define current-input-port (make-parameter (%standard-input-port)))
+(define current-output-port (make-parameter (%standard-output-port)))
+(define current-error-port (make-parameter (%standard-error-port)))
+
+;; The port procedures written in Rust always take the port, which comes
+;; after any other required arguments. Here it is optional, defaulting to the
+;; current port.
+(define (%port-args current args)
+  (if (pair? args) args (list (current))))
+
+(define (read-char . args) (apply %read-char (%port-args current-input-port args)))
+(define (peek-char . args) (apply %peek-char (%port-args current-input-port args)))
+(define (read-line . args) (apply %read-line (%port-args current-input-port args)))
+(define (char-ready? . args) (apply %char-ready? (%port-args current-input-port args)))
+(define (read-string k . args)
+  (apply %read-string k (%port-args current-input-port args)))
+(define (read-u8 . args) (apply %read-u8 (%port-args current-input-port args)))
+(define (peek-u8 . args) (apply %peek-u8 (%port-args current-input-port args)))
+(define (u8-ready? . args) (apply %u8-ready? (%port-args current-input-port args)))
+(define (read-bytevector k . args)
+  (apply %read-bytevector k (%port-args current-input-port args)))
+(define (read-bytevector! bv . args)
+  (apply %read-bytevector! bv (%port-args current-input-port args)))
+(define (write obj . args) (apply %write obj (%port-args current-output-port args)))
+(define (display obj . args) (apply %display obj (%port-args current-output-port args)))
+(define (newline . args) (apply %newline (%port-args current-output-port args)))
+(define (write-char c . args) (apply %write-char c (%port-args current-output-port args)))
+(define (write-string s . args)
+  (apply %write-string s (%port-args current-output-port args)))
+(define (write-u8 b . args) (apply %write-u8 b (%port-args current-output-port args)))
+(define (write-bytevector bv . args)
+  (apply %write-bytevector bv (%port-args current-output-port args)))
+(define (flush-output-port . args)
+  (apply %flush-output-port (%port-args current-output-port args)))
+
+(define (call-with-port port proc)
+  (call-with-values (lambda () (proc port))
+    (lambda results
+      (close-port port)
+      (apply values results))))
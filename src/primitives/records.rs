@@ -0,0 +1,206 @@
+//! The procedures behind `define-record-type`, which the prelude defines as
+//! a macro. Each makes the constructor, predicate, accessor or modifier of a
+//! record type as a procedure of its own, with the positions of the fields it
+//! uses worked out once.
+
+use super::lists::unpack_list;
+use super::PrimitiveResult;
+use crate::error::LispError;
+use crate::interpreter::NativeFunc;
+use crate::parser::{LispVal, PrimitiveFn};
+use crate::record::RecordType;
+use crate::symbol::Symbol;
+use std::rc::Rc;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("%make-record-type", make_record_type),
+    ("%record-constructor", record_constructor),
+    ("%record-predicate", record_predicate),
+    ("%record-accessor", record_accessor),
+    ("%record-modifier", record_modifier),
+];
+
+fn unpack_symbol(value: &LispVal) -> Result<Symbol, LispError> {
+    match value {
+        LispVal::Atom(name) => Ok(*name),
+        _ => Err(LispError::TypeMismatch("symbol", value.clone())),
+    }
+}
+
+fn unpack_record_type(value: &LispVal) -> Result<&Rc<RecordType>, LispError> {
+    match value {
+        LispVal::RecordType(record_type) => Ok(record_type),
+        _ => Err(LispError::TypeMismatch("record type", value.clone())),
+    }
+}
+
+fn unpack_field(record_type: &RecordType, field: &LispVal) -> Result<usize, LispError> {
+    let name = unpack_symbol(field)?;
+    record_type
+        .field_index(name)
+        .ok_or_else(|| LispError::Default(format!("{} has no field {}", record_type.name(), name)))
+}
+
+fn native(
+    name: Symbol,
+    arity: usize,
+    func: impl Fn(&[LispVal]) -> PrimitiveResult + 'static,
+) -> LispVal {
+    LispVal::NativeFunc(Rc::new(NativeFunc::new(
+        name.as_str(),
+        arity,
+        Box::new(func),
+    )))
+}
+
+/// `(%make-record-type name fields)`.
+fn make_record_type(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [name, fields] => {
+            let fields = unpack_list(fields)?
+                .iter()
+                .map(unpack_symbol)
+                .collect::<Result<Vec<_>, _>>()?;
+            for (i, field) in fields.iter().enumerate() {
+                if fields[..i].contains(field) {
+                    return Err(LispError::Default(format!("Duplicate field {}", field)));
+                }
+            }
+            Ok(LispVal::RecordType(RecordType::new(
+                unpack_symbol(name)?,
+                fields,
+            )))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+/// `(%record-constructor type name fields)` makes a procedure taking the
+/// values of `fields`, in that order. Any other fields start out unspecified.
+fn record_constructor(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [record_type, name, fields] => {
+            let record_type = unpack_record_type(record_type)?.clone();
+            let indices = unpack_list(fields)?
+                .iter()
+                .map(|field| unpack_field(&record_type, field))
+                .collect::<Result<Vec<_>, _>>()?;
+            let arity = indices.len();
+            Ok(native(unpack_symbol(name)?, arity, move |args| {
+                let mut values = vec![LispVal::Unspecified; record_type.fields().len()];
+                for (index, value) in indices.iter().zip(args) {
+                    values[*index] = value.clone();
+                }
+                record_type.make(values)
+            }))
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn record_predicate(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [record_type, name] => {
+            let record_type = unpack_record_type(record_type)?.clone();
+            Ok(native(unpack_symbol(name)?, 1, move |args| {
+                Ok(LispVal::Boolean(record_type.is_instance(&args[0])))
+            }))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn record_accessor(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [record_type, name, field] => {
+            let record_type = unpack_record_type(record_type)?.clone();
+            let index = unpack_field(&record_type, field)?;
+            Ok(native(unpack_symbol(name)?, 1, move |args| {
+                Ok(record_type.unpack(&args[0])?.get(index))
+            }))
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn record_modifier(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [record_type, name, field] => {
+            let record_type = unpack_record_type(record_type)?.clone();
+            let index = unpack_field(&record_type, field)?;
+            Ok(native(unpack_symbol(name)?, 2, move |args| {
+                record_type.unpack(&args[0])?.set(index, args[1].clone());
+                Ok(LispVal::Unspecified)
+            }))
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::records::*;
+
+    fn symbols(names: &[&str]) -> LispVal {
+        LispVal::list(names.iter().map(|name| LispVal::symbol(name)).collect())
+    }
+
+    fn call(procedure: &LispVal, args: Vec<LispVal>) -> PrimitiveResult {
+        match procedure {
+            LispVal::NativeFunc(native) => native.call(args),
+            _ => panic!("{} is not a native procedure", procedure),
+        }
+    }
+
+    #[test]
+    fn record_procedure_test() {
+        let point = make_record_type(&[LispVal::symbol("<point>"), symbols(&["x", "y"])]).unwrap();
+        let make = record_constructor(&[
+            point.clone(),
+            LispVal::symbol("make-point"),
+            symbols(&["y"]),
+        ])
+        .unwrap();
+        let is_point = record_predicate(&[point.clone(), LispVal::symbol("point?")]).unwrap();
+        let x = LispVal::symbol("x");
+        let get_x =
+            record_accessor(&[point.clone(), LispVal::symbol("point-x"), x.clone()]).unwrap();
+        let set_x = record_modifier(&[point.clone(), LispVal::symbol("set-point-x!"), x]).unwrap();
+
+        let p = call(&make, vec![int(2)]).unwrap();
+        assert_eq!(p.to_string(), "#<point x: #<unspecified> y: 2>");
+        assert_eq!(
+            call(&is_point, vec![p.clone()]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            call(&is_point, vec![int(1)]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        call(&set_x, vec![p.clone(), int(1)]).unwrap();
+        assert_eq!(call(&get_x, vec![p]).unwrap(), int(1));
+        assert_eq!(
+            call(&get_x, vec![int(1)]).unwrap_err(),
+            LispError::TypeMismatch("<point>", int(1))
+        );
+        assert!(call(&make, vec![]).is_err());
+        assert_eq!(make.to_string(), "#<primitive make-point>");
+    }
+
+    #[test]
+    fn record_type_error_test() {
+        let point = make_record_type(&[LispVal::symbol("<point>"), symbols(&["x"])]).unwrap();
+        assert!(make_record_type(&[LispVal::symbol("<bad>"), symbols(&["x", "x"])]).is_err());
+        assert!(
+            make_record_type(&[LispVal::symbol("<bad>"), LispVal::list(vec![int(1)])]).is_err()
+        );
+        assert!(
+            record_accessor(&[point.clone(), LispVal::symbol("get"), LispVal::symbol("z")])
+                .is_err()
+        );
+        assert!(record_predicate(&[int(1), LispVal::symbol("point?")]).is_err());
+        assert_eq!(point.to_string(), "#<record-type point>");
+    }
+}
@@ -0,0 +1,648 @@
+//! Strings are shared and mutable like vectors: `string-set!`, `string-fill!`
+//! and `string-copy!` change a string in place, and the change is seen
+//! through every copy of the value. A string holds its characters rather
+//! than UTF-8, so indices count characters and `string-ref` and `string-set!`
+//! take constant time.
+
+use super::chars::{downcase, upcase};
+use super::lists::unpack_list;
+use super::{out_of_range, predicate, unpack_index, unpack_range, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::{LispVal, PrimitiveFn};
+use std::cell::{Ref, RefCell};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("string?", is_string),
+    ("make-string", make_string),
+    ("string", string),
+    ("string-length", string_length),
+    ("string-ref", string_ref),
+    ("substring", substring),
+    ("string-append", string_append),
+    ("string-copy", string_copy),
+    ("string-set!", string_set),
+    ("string-fill!", string_fill),
+    ("string-copy!", string_copy_into),
+    ("string->list", string_to_list),
+    ("list->string", list_to_string),
+    ("string->vector", string_to_vector),
+    ("vector->string", vector_to_string),
+    ("string-upcase", string_upcase),
+    ("string-downcase", string_downcase),
+    ("string-foldcase", string_downcase),
+    ("string=?", string_eq),
+    ("string<?", string_lt),
+    ("string>?", string_gt),
+    ("string<=?", string_le),
+    ("string>=?", string_ge),
+    ("string-ci=?", string_ci_eq),
+    ("string-ci<?", string_ci_lt),
+    ("string-ci>?", string_ci_gt),
+    ("string-ci<=?", string_ci_le),
+    ("string-ci>=?", string_ci_ge),
+];
+
+pub(super) fn unpack_string(value: &LispVal) -> Result<Ref<'_, [char]>, LispError> {
+    Ok(Ref::map(unpack_cell(value)?.borrow(), Vec::as_slice))
+}
+
+fn unpack_cell(value: &LispVal) -> Result<&Rc<RefCell<Vec<char>>>, LispError> {
+    match value {
+        LispVal::String(s) => Ok(s),
+        _ => Err(LispError::TypeMismatch("string", value.clone())),
+    }
+}
+
+fn unpack_char(value: &LispVal) -> Result<char, LispError> {
+    match value {
+        LispVal::Char(c) => Ok(*c),
+        _ => Err(LispError::TypeMismatch("char", value.clone())),
+    }
+}
+
+fn unpack_chars(values: &[LispVal]) -> Result<String, LispError> {
+    values.iter().map(unpack_char).collect()
+}
+
+/// The characters of a string between the optional `start` and `end`
+/// arguments that follow it.
+pub(super) fn string_range(s: &LispVal, range: &[LispVal]) -> Result<Vec<char>, LispError> {
+    let chars = unpack_string(s)?;
+    let (start, end) = unpack_range(range, chars.len())?;
+    Ok(chars[start..end].to_vec())
+}
+
+fn is_string(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::String(_)))
+}
+
+fn make_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [k] => Ok(LispVal::string(" ".repeat(unpack_index(k)?))),
+        [k, fill] => {
+            let fill = unpack_char(fill)?.to_string();
+            Ok(LispVal::string(fill.repeat(unpack_index(k)?)))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn string(args: &[LispVal]) -> PrimitiveResult {
+    Ok(LispVal::string(unpack_chars(args)?))
+}
+
+fn string_length(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s] => Ok(LispVal::Number(Number::Integer(
+            unpack_string(s)?.len() as i64
+        ))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn string_ref(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, k] => {
+            let (s, k) = (unpack_string(s)?, unpack_index(k)?);
+            match s.get(k) {
+                Some(c) => Ok(LispVal::Char(*c)),
+                None => Err(out_of_range(k, s.len())),
+            }
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn substring(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, start, end] => {
+            let chars = string_range(s, &[start.clone(), end.clone()])?;
+            Ok(LispVal::string(chars.into_iter().collect()))
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn string_append(args: &[LispVal]) -> PrimitiveResult {
+    let mut result = String::new();
+    for arg in args {
+        result.extend(unpack_string(arg)?.iter());
+    }
+    Ok(LispVal::string(result))
+}
+
+fn string_copy(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, range @ ..] if range.len() <= 2 => Ok(LispVal::string(
+            string_range(s, range)?.into_iter().collect::<String>(),
+        )),
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn string_set(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, k, c] => {
+            let (cell, k, c) = (unpack_cell(s)?, unpack_index(k)?, unpack_char(c)?);
+            let mut chars = cell.borrow_mut();
+            let len = chars.len();
+            match chars.get_mut(k) {
+                Some(slot) => *slot = c,
+                None => return Err(out_of_range(k, len)),
+            }
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+/// `(string-fill! string char start end)` sets every character between the
+/// optional `start` and `end` to `char`.
+fn string_fill(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, c, range @ ..] if range.len() <= 2 => {
+            let (cell, c) = (unpack_cell(s)?, unpack_char(c)?);
+            let mut chars = cell.borrow_mut();
+            let (start, end) = unpack_range(range, chars.len())?;
+            for slot in &mut chars[start..end] {
+                *slot = c;
+            }
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(4, args.to_vec())),
+    }
+}
+
+/// `(string-copy! to at from start end)` copies the characters of `from`
+/// between the optional `start` and `end` into `to`, starting at index `at`.
+/// The source is read in full first, so `to` and `from` may be the same
+/// string.
+fn string_copy_into(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [to, at, from, range @ ..] if range.len() <= 2 => {
+            let (cell, at) = (unpack_cell(to)?, unpack_index(at)?);
+            let source = string_range(from, range)?;
+            let mut chars = cell.borrow_mut();
+            let end = at.saturating_add(source.len());
+            if end > chars.len() {
+                return Err(out_of_range(end, chars.len()));
+            }
+            chars[at..end].copy_from_slice(&source);
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(5, args.to_vec())),
+    }
+}
+
+fn string_to_list(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, range @ ..] if range.len() <= 2 => Ok(string_range(s, range)?
+            .into_iter()
+            .map(LispVal::Char)
+            .collect()),
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn list_to_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list] => Ok(LispVal::string(unpack_chars(&unpack_list(list)?)?)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn string_to_vector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [s, range @ ..] if range.len() <= 2 => Ok(LispVal::vector(
+            string_range(s, range)?
+                .into_iter()
+                .map(LispVal::Char)
+                .collect(),
+        )),
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn vector_to_string(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [LispVal::Vector(items), range @ ..] if range.len() <= 2 => {
+            let items = items.borrow();
+            let (start, end) = unpack_range(range, items.len())?;
+            Ok(LispVal::string(unpack_chars(&items[start..end])?))
+        }
+        [other, ..] if args.len() <= 3 => Err(LispError::TypeMismatch("vector", other.clone())),
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn convert_string(args: &[LispVal], convert: fn(char) -> char) -> PrimitiveResult {
+    match args {
+        [s] => Ok(LispVal::string(
+            unpack_string(s)?.iter().copied().map(convert).collect(),
+        )),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+// Characters are converted one at a time, like `char-upcase`, so the string
+// keeps its length.
+fn string_upcase(args: &[LispVal]) -> PrimitiveResult {
+    convert_string(args, upcase)
+}
+
+fn string_downcase(args: &[LispVal]) -> PrimitiveResult {
+    convert_string(args, downcase)
+}
+
+fn compare_strings(args: &[LispVal], fold: bool, accept: fn(Ordering) -> bool) -> PrimitiveResult {
+    if args.is_empty() {
+        return Err(LispError::NumArgs(1, vec![]));
+    }
+    let mut strings = args
+        .iter()
+        .map(|s| unpack_string(s).map(|s| s.to_vec()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if fold {
+        strings = strings
+            .iter()
+            .map(|s| s.iter().copied().map(downcase).collect())
+            .collect();
+    }
+    Ok(LispVal::Boolean(
+        strings.windows(2).all(|pair| accept(pair[0].cmp(&pair[1]))),
+    ))
+}
+
+fn string_eq(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, false, |o| o == Ordering::Equal)
+}
+
+fn string_lt(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, false, |o| o == Ordering::Less)
+}
+
+fn string_gt(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, false, |o| o == Ordering::Greater)
+}
+
+fn string_le(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, false, |o| o != Ordering::Greater)
+}
+
+fn string_ge(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, false, |o| o != Ordering::Less)
+}
+
+fn string_ci_eq(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, true, |o| o == Ordering::Equal)
+}
+
+fn string_ci_lt(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, true, |o| o == Ordering::Less)
+}
+
+fn string_ci_gt(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, true, |o| o == Ordering::Greater)
+}
+
+fn string_ci_le(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, true, |o| o != Ordering::Greater)
+}
+
+fn string_ci_ge(args: &[LispVal]) -> PrimitiveResult {
+    compare_strings(args, true, |o| o != Ordering::Less)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::strings::*;
+
+    fn text(s: &str) -> LispVal {
+        LispVal::string(s.to_owned())
+    }
+
+    fn chars(s: &str) -> Vec<LispVal> {
+        s.chars().map(LispVal::Char).collect()
+    }
+
+    #[test]
+    fn is_string_test() {
+        assert_eq!(is_string(&[text("")]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(is_string(&chars("a")).unwrap(), LispVal::Boolean(false));
+        assert!(is_string(&[]).is_err());
+    }
+
+    #[test]
+    fn make_string_test() {
+        assert_eq!(make_string(&[int(3)]).unwrap(), text("   "));
+        assert_eq!(
+            make_string(&[int(2), LispVal::Char('λ')]).unwrap(),
+            text("λλ")
+        );
+        assert!(make_string(&[int(2), text("a")]).is_err());
+        assert!(make_string(&[int(-1)]).is_err());
+    }
+
+    #[test]
+    fn string_test() {
+        assert_eq!(string(&chars("abc")).unwrap(), text("abc"));
+        assert_eq!(string(&[]).unwrap(), text(""));
+        assert!(string(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn string_length_test() {
+        assert_eq!(string_length(&[text("λx.y")]).unwrap(), int(4));
+        assert_eq!(string_length(&[text("")]).unwrap(), int(0));
+        assert!(string_length(&chars("a")).is_err());
+    }
+
+    #[test]
+    fn string_ref_test() {
+        let s = text("λx.y");
+        assert_eq!(
+            string_ref(&[s.clone(), int(0)]).unwrap(),
+            LispVal::Char('λ')
+        );
+        assert_eq!(
+            string_ref(&[s.clone(), int(1)]).unwrap(),
+            LispVal::Char('x')
+        );
+        assert!(string_ref(&[s, int(4)]).is_err());
+    }
+
+    #[test]
+    fn substring_test() {
+        let s = text("λx.y");
+        assert_eq!(substring(&[s.clone(), int(1), int(3)]).unwrap(), text("x."));
+        assert_eq!(substring(&[s.clone(), int(4), int(4)]).unwrap(), text(""));
+        assert!(substring(&[s.clone(), int(3), int(1)]).is_err());
+        assert!(substring(&[s.clone(), int(0), int(5)]).is_err());
+        assert!(substring(&[s, int(0)]).is_err());
+    }
+
+    #[test]
+    fn string_append_test() {
+        assert_eq!(
+            string_append(&[text("ab"), text(""), text("c")]).unwrap(),
+            text("abc")
+        );
+        assert_eq!(string_append(&[]).unwrap(), text(""));
+        assert!(string_append(&[text("a"), LispVal::Char('b')]).is_err());
+    }
+
+    #[test]
+    fn string_copy_test() {
+        let s = text("λx.y");
+        let copy = string_copy(std::slice::from_ref(&s)).unwrap();
+        assert_eq!(copy, s);
+        // The copy is a new string.
+        string_set(&[copy, int(0), LispVal::Char('a')]).unwrap();
+        assert_eq!(s, text("λx.y"));
+        assert_eq!(string_copy(&[s.clone(), int(2)]).unwrap(), text(".y"));
+        assert_eq!(
+            string_copy(&[s.clone(), int(1), int(2)]).unwrap(),
+            text("x")
+        );
+        assert!(string_copy(&[s, int(5)]).is_err());
+    }
+
+    #[test]
+    fn string_set_test() {
+        let s = text("abcd");
+        let shared = s.clone();
+        assert_eq!(
+            string_set(&[s.clone(), int(1), LispVal::Char('λ')]).unwrap(),
+            LispVal::Unspecified
+        );
+        assert_eq!(shared, text("aλcd"));
+        assert!(string_set(&[s.clone(), int(4), LispVal::Char('x')]).is_err());
+        assert!(string_set(&[s, int(0), text("x")]).is_err());
+        assert!(string_set(&[text("x"), int(0)]).is_err());
+    }
+
+    #[test]
+    fn string_fill_test() {
+        let s = text("abcd");
+        string_fill(&[s.clone(), LispVal::Char('z'), int(2)]).unwrap();
+        assert_eq!(s, text("abzz"));
+        string_fill(&[s.clone(), LispVal::Char('λ'), int(1), int(2)]).unwrap();
+        assert_eq!(s, text("aλzz"));
+        string_fill(&[s.clone(), LispVal::Char('y')]).unwrap();
+        assert_eq!(s, text("yyyy"));
+        assert!(string_fill(&[s.clone(), LispVal::Char('y'), int(3), int(1)]).is_err());
+        assert!(string_fill(&[s, int(1)]).is_err());
+    }
+
+    #[test]
+    fn string_copy_into_test() {
+        let s = text("abcde");
+        string_copy_into(&[s.clone(), int(1), text("XY")]).unwrap();
+        assert_eq!(s, text("aXYde"));
+        string_copy_into(&[s.clone(), int(0), text("123"), int(1), int(2)]).unwrap();
+        assert_eq!(s, text("2XYde"));
+        // Copying a string onto itself works with overlapping ranges.
+        string_copy_into(&[s.clone(), int(1), s.clone(), int(0), int(3)]).unwrap();
+        assert_eq!(s, text("22XYe"));
+        assert!(string_copy_into(&[s.clone(), int(4), text("ab")]).is_err());
+        assert!(string_copy_into(&[s.clone(), int(6), text("")]).is_err());
+        assert!(string_copy_into(&[s.clone(), int(0), int(1)]).is_err());
+        assert_eq!(s, text("22XYe"));
+    }
+
+    #[test]
+    fn string_to_list_test() {
+        let s = text("abc");
+        assert_eq!(
+            string_to_list(std::slice::from_ref(&s)).unwrap(),
+            LispVal::list(chars("abc"))
+        );
+        assert_eq!(
+            string_to_list(&[s.clone(), int(1)]).unwrap(),
+            LispVal::list(chars("bc"))
+        );
+        assert_eq!(
+            string_to_list(&[s.clone(), int(1), int(1)]).unwrap(),
+            LispVal::Nil
+        );
+        assert!(string_to_list(&[s, int(4)]).is_err());
+    }
+
+    #[test]
+    fn list_to_string_test() {
+        assert_eq!(
+            list_to_string(&[LispVal::list(chars("abc"))]).unwrap(),
+            text("abc")
+        );
+        assert_eq!(list_to_string(&[LispVal::Nil]).unwrap(), text(""));
+        assert!(list_to_string(&[LispVal::list(vec![int(1)])]).is_err());
+    }
+
+    #[test]
+    fn string_to_vector_test() {
+        let s = text("abc");
+        assert_eq!(
+            string_to_vector(std::slice::from_ref(&s)).unwrap(),
+            LispVal::vector(chars("abc"))
+        );
+        assert_eq!(
+            string_to_vector(&[s.clone(), int(0), int(2)]).unwrap(),
+            LispVal::vector(chars("ab"))
+        );
+        assert!(string_to_vector(&[s, int(2), int(1)]).is_err());
+    }
+
+    #[test]
+    fn vector_to_string_test() {
+        let vector = LispVal::vector(chars("ab"));
+        assert_eq!(
+            vector_to_string(std::slice::from_ref(&vector)).unwrap(),
+            text("ab")
+        );
+        assert_eq!(vector_to_string(&[vector, int(1)]).unwrap(), text("b"));
+        assert!(vector_to_string(&[LispVal::vector(vec![int(1)])]).is_err());
+        assert!(vector_to_string(&[text("ab")]).is_err());
+    }
+
+    #[test]
+    fn string_upcase_test() {
+        assert_eq!(
+            string_upcase(&[text("Straße λ")]).unwrap(),
+            text("STRAßE Λ")
+        );
+        assert!(string_upcase(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn string_downcase_test() {
+        assert_eq!(string_downcase(&[text("ABC Λ")]).unwrap(), text("abc λ"));
+        assert!(string_downcase(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn string_eq_test() {
+        assert_eq!(
+            string_eq(&[text("a"), text("a"), text("a")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_eq(&[text("a"), text("A")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+        assert!(string_eq(&[text("a"), LispVal::Char('a')]).is_err());
+        assert!(string_eq(&[]).is_err());
+    }
+
+    #[test]
+    fn string_lt_test() {
+        assert_eq!(
+            string_lt(&[text("ab"), text("abc"), text("b")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_lt(&[text("a"), text("a")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_gt_test() {
+        assert_eq!(
+            string_gt(&[text("b"), text("ab")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_gt(&[text("B"), text("a")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_le_test() {
+        assert_eq!(
+            string_le(&[text("a"), text("a"), text("b")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_le(&[text("b"), text("a")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_ge_test() {
+        assert_eq!(
+            string_ge(&[text("b"), text("b"), text("a")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_ge(&[text("a"), text("b")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_ci_eq_test() {
+        assert_eq!(
+            string_ci_eq(&[text("Abc"), text("aBC")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_ci_eq(&[text("a"), text("b")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_ci_lt_test() {
+        assert_eq!(
+            string_ci_lt(&[text("a"), text("B")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_ci_lt(&[text("A"), text("a")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_ci_gt_test() {
+        assert_eq!(
+            string_ci_gt(&[text("b"), text("A")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_ci_gt(&[text("a"), text("B")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_ci_le_test() {
+        assert_eq!(
+            string_ci_le(&[text("A"), text("a")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_ci_le(&[text("b"), text("A")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn string_ci_ge_test() {
+        assert_eq!(
+            string_ci_ge(&[text("B"), text("a")]).unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            string_ci_ge(&[text("a"), text("B")]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use super::lists::unpack_list;
+use super::{out_of_range, predicate, unpack_index, unpack_range, PrimitiveResult};
+use crate::error::LispError;
+use crate::number::Number;
+use crate::parser::{LispVal, PrimitiveFn};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub(super) const PRIMITIVES: &[(&str, PrimitiveFn)] = &[
+    ("vector?", is_vector),
+    ("make-vector", make_vector),
+    ("vector", vector),
+    ("vector-length", vector_length),
+    ("vector-ref", vector_ref),
+    ("vector-set!", vector_set),
+    ("vector->list", vector_to_list),
+    ("list->vector", list_to_vector),
+    ("vector-fill!", vector_fill),
+    ("vector-copy", vector_copy),
+    ("vector-copy!", vector_copy_to),
+    ("vector-append", vector_append),
+];
+
+type Items = Rc<RefCell<Vec<LispVal>>>;
+
+fn unpack_vector(value: &LispVal) -> Result<&Items, LispError> {
+    match value {
+        LispVal::Vector(items) => Ok(items),
+        _ => Err(LispError::TypeMismatch("vector", value.clone())),
+    }
+}
+
+/// The elements of a vector between the optional `start` and `end`
+/// arguments that follow it.
+fn vector_range(vector: &LispVal, range: &[LispVal]) -> Result<Vec<LispVal>, LispError> {
+    let items = unpack_vector(vector)?.borrow();
+    let (start, end) = unpack_range(range, items.len())?;
+    Ok(items[start..end].to_vec())
+}
+
+fn is_vector(args: &[LispVal]) -> PrimitiveResult {
+    predicate(args, |value| matches!(value, LispVal::Vector(_)))
+}
+
+fn make_vector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [k, fill @ ..] if fill.len() <= 1 => {
+            let fill = fill.first().cloned().unwrap_or(LispVal::Unspecified);
+            Ok(LispVal::vector(vec![fill; unpack_index(k)?]))
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn vector(args: &[LispVal]) -> PrimitiveResult {
+    Ok(LispVal::vector(args.to_vec()))
+}
+
+fn vector_length(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [v] => Ok(LispVal::Number(Number::Integer(
+            unpack_vector(v)?.borrow().len() as i64,
+        ))),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn vector_ref(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [v, k] => {
+            let (items, k) = (unpack_vector(v)?.borrow(), unpack_index(k)?);
+            match items.get(k) {
+                Some(item) => Ok(item.clone()),
+                None => Err(out_of_range(k, items.len())),
+            }
+        }
+        _ => Err(LispError::NumArgs(2, args.to_vec())),
+    }
+}
+
+fn vector_set(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [v, k, value] => {
+            let (mut items, k) = (unpack_vector(v)?.borrow_mut(), unpack_index(k)?);
+            let len = items.len();
+            match items.get_mut(k) {
+                Some(item) => *item = value.clone(),
+                None => return Err(out_of_range(k, len)),
+            }
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn vector_to_list(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [v, range @ ..] if range.len() <= 2 => Ok(LispVal::list(vector_range(v, range)?)),
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+fn list_to_vector(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [list] => Ok(LispVal::vector(unpack_list(list)?)),
+        _ => Err(LispError::NumArgs(1, args.to_vec())),
+    }
+}
+
+fn vector_fill(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [v, fill, range @ ..] if range.len() <= 2 => {
+            let mut items = unpack_vector(v)?.borrow_mut();
+            let (start, end) = unpack_range(range, items.len())?;
+            for item in &mut items[start..end] {
+                *item = fill.clone();
+            }
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(4, args.to_vec())),
+    }
+}
+
+fn vector_copy(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [v, range @ ..] if range.len() <= 2 => Ok(LispVal::vector(vector_range(v, range)?)),
+        _ => Err(LispError::NumArgs(3, args.to_vec())),
+    }
+}
+
+/// `(vector-copy! to at from [start [end]])` copies elements of `from` into
+/// `to` starting at index `at`. The two may be the same vector, and the
+/// ranges may overlap.
+fn vector_copy_to(args: &[LispVal]) -> PrimitiveResult {
+    match args {
+        [to, at, from, range @ ..] if range.len() <= 2 => {
+            let (to, at) = (unpack_vector(to)?, unpack_index(at)?);
+            let copied = vector_range(from, range)?;
+            let mut items = to.borrow_mut();
+            if at > items.len() || copied.len() > items.len() - at {
+                return Err(out_of_range(at + copied.len(), items.len()));
+            }
+            items[at..at + copied.len()].clone_from_slice(&copied);
+            Ok(LispVal::Unspecified)
+        }
+        _ => Err(LispError::NumArgs(5, args.to_vec())),
+    }
+}
+
+fn vector_append(args: &[LispVal]) -> PrimitiveResult {
+    let mut result = vec![];
+    for arg in args {
+        result.extend(unpack_vector(arg)?.borrow().iter().cloned());
+    }
+    Ok(LispVal::vector(result))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::primitives::vectors::*;
+
+    fn ints(ns: &[i64]) -> LispVal {
+        LispVal::vector(ns.iter().map(|&n| int(n)).collect())
+    }
+
+    #[test]
+    fn is_vector_test() {
+        assert_eq!(is_vector(&[ints(&[])]).unwrap(), LispVal::Boolean(true));
+        assert_eq!(
+            is_vector(&[LispVal::list(vec![int(1)])]).unwrap(),
+            LispVal::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn make_vector_test() {
+        assert_eq!(make_vector(&[int(2), int(7)]).unwrap(), ints(&[7, 7]));
+        assert_eq!(
+            make_vector(&[int(1)]).unwrap(),
+            LispVal::vector(vec![LispVal::Unspecified])
+        );
+        assert!(make_vector(&[int(-1)]).is_err());
+    }
+
+    #[test]
+    fn vector_test() {
+        assert_eq!(vector(&[int(1), int(2)]).unwrap(), ints(&[1, 2]));
+        assert_eq!(vector(&[]).unwrap(), ints(&[]));
+    }
+
+    #[test]
+    fn vector_length_test() {
+        assert_eq!(vector_length(&[ints(&[1, 2, 3])]).unwrap(), int(3));
+        assert_eq!(vector_length(&[ints(&[])]).unwrap(), int(0));
+        assert!(vector_length(&[int(1)]).is_err());
+    }
+
+    #[test]
+    fn vector_ref_test() {
+        let v = ints(&[1, 2, 3]);
+        assert_eq!(vector_ref(&[v.clone(), int(2)]).unwrap(), int(3));
+        assert!(vector_ref(&[v.clone(), int(3)]).is_err());
+        assert!(vector_ref(&[v, int(-1)]).is_err());
+    }
+
+    #[test]
+    fn vector_set_test() {
+        let v = ints(&[1, 2, 3]);
+        // Every copy of a vector shares its elements.
+        let alias = v.clone();
+        vector_set(&[v.clone(), int(0), int(5)]).unwrap();
+        assert_eq!(alias, ints(&[5, 2, 3]));
+        assert!(vector_set(&[v, int(3), int(5)]).is_err());
+    }
+
+    #[test]
+    fn vector_to_list_test() {
+        let v = ints(&[1, 2, 3]);
+        assert_eq!(
+            vector_to_list(std::slice::from_ref(&v)).unwrap(),
+            LispVal::list(vec![int(1), int(2), int(3)])
+        );
+        assert_eq!(
+            vector_to_list(&[v.clone(), int(1), int(2)]).unwrap(),
+            LispVal::list(vec![int(2)])
+        );
+        assert!(vector_to_list(&[v, int(2), int(1)]).is_err());
+    }
+
+    #[test]
+    fn list_to_vector_test() {
+        assert_eq!(
+            list_to_vector(&[LispVal::list(vec![int(1), int(2), int(3)])]).unwrap(),
+            ints(&[1, 2, 3])
+        );
+        assert_eq!(list_to_vector(&[LispVal::Nil]).unwrap(), ints(&[]));
+        assert!(list_to_vector(&[ints(&[1])]).is_err());
+    }
+
+    #[test]
+    fn vector_fill_test() {
+        let v = ints(&[1, 2, 3, 4]);
+        vector_fill(&[v.clone(), int(0), int(1), int(3)]).unwrap();
+        assert_eq!(v, ints(&[1, 0, 0, 4]));
+        vector_fill(&[v.clone(), int(9)]).unwrap();
+        assert_eq!(v, ints(&[9, 9, 9, 9]));
+        assert!(vector_fill(&[v, int(9), int(5)]).is_err());
+    }
+
+    #[test]
+    fn vector_copy_test() {
+        let v = ints(&[1, 2, 3, 4, 5]);
+        let copy = vector_copy(std::slice::from_ref(&v)).unwrap();
+        assert_eq!(copy, v);
+        vector_set(&[copy, int(0), int(0)]).unwrap();
+        assert_eq!(vector_ref(&[v.clone(), int(0)]).unwrap(), int(1));
+        assert_eq!(vector_copy(&[v.clone(), int(3)]).unwrap(), ints(&[4, 5]));
+        assert_eq!(
+            vector_copy(&[v.clone(), int(1), int(2)]).unwrap(),
+            ints(&[2])
+        );
+        assert!(vector_copy(&[v, int(6)]).is_err());
+    }
+
+    #[test]
+    fn vector_copy_to_test() {
+        let v = ints(&[1, 2, 3, 4, 5]);
+        // Overlapping ranges of the same vector.
+        vector_copy_to(&[v.clone(), int(1), v.clone(), int(0), int(3)]).unwrap();
+        assert_eq!(v, ints(&[1, 1, 2, 3, 5]));
+        vector_copy_to(&[v.clone(), int(3), ints(&[8, 9])]).unwrap();
+        assert_eq!(v, ints(&[1, 1, 2, 8, 9]));
+        assert!(vector_copy_to(&[v.clone(), int(4), ints(&[8, 9])]).is_err());
+        assert!(vector_copy_to(&[v, int(6), ints(&[])]).is_err());
+    }
+
+    #[test]
+    fn vector_append_test() {
+        assert_eq!(
+            vector_append(&[ints(&[1]), ints(&[]), ints(&[2, 3])]).unwrap(),
+            ints(&[1, 2, 3])
+        );
+        assert_eq!(vector_append(&[]).unwrap(), ints(&[]));
+        assert!(vector_append(&[ints(&[1]), int(2)]).is_err());
+    }
+}
@@ -0,0 +1,571 @@
+use crate::number::parse_number;
+use crate::pair::Pair;
+use crate::parser::{LispVal, CHAR_NAMES};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+// `write` produces the external representation, which reads back as an
+// equal value; `display` is for humans and prints text without quoting.
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Write,
+    Display,
+}
+
+fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\t' => out.push_str("\\t"),
+        '\r' => out.push_str("\\r"),
+        '\u{7}' => out.push_str("\\a"),
+        '\u{8}' => out.push_str("\\b"),
+        c if c.is_control() => out.push_str(&format!("\\x{:x};", c as u32)),
+        c => out.push(c),
+    }
+}
+
+/// Renders a string as a Scheme string literal, escaping whatever the
+/// reader would otherwise misread.
+pub fn write_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            c => push_escaped(&mut out, c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders a character as a `#\\` literal, using its R7RS name where it has
+/// one so that whitespace and control characters stay visible.
+pub fn write_char(c: char) -> String {
+    match CHAR_NAMES.iter().find(|(_, named)| *named == c) {
+        Some((name, _)) => format!("#\\{}", name),
+        None if c.is_control() => format!("#\\x{:x}", c as u32),
+        None => format!("#\\{}", c),
+    }
+}
+
+/// Whether the reader reads `name` back as the symbol it names. This follows
+/// the reader's rules for identifiers instead of reading the name, which
+/// would intern it.
+fn is_plain_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    let initial = match chars.next() {
+        Some(c) => c,
+        None => return false,
+    };
+    // A lone `.` is the dot of a dotted list.
+    let plain_initial = initial.is_ascii_alphabetic()
+        || "!#$%&*+-/:<=>?@^_~".contains(initial)
+        || (initial == '.' && name.len() > 1);
+    plain_initial
+        && chars.all(|c| c.is_ascii_alphanumeric() || "!#$%&*+-./:<=>?@^_~".contains(c))
+        && name != "#t"
+        && name != "#f"
+        && parse_number(name, 10).is_none()
+}
+
+/// Renders a symbol, falling back to `|...|` syntax for names that would
+/// otherwise read back as something else, such as `|hello world|` or `|42|`.
+pub fn write_symbol(name: &str) -> String {
+    if is_plain_symbol(name) {
+        return name.to_owned();
+    }
+    let mut out = String::with_capacity(name.len() + 2);
+    out.push('|');
+    for c in name.chars() {
+        match c {
+            '|' => out.push_str("\\|"),
+            c => push_escaped(&mut out, c),
+        }
+    }
+    out.push('|');
+    out
+}
+
+fn quote_prefix(keyword: &str) -> Option<&'static str> {
+    match keyword {
+        "quote" => Some("'"),
+        "quasiquote" => Some("`"),
+        "unquote" => Some(","),
+        "unquote-splicing" => Some(",@"),
+        _ => None,
+    }
+}
+
+/// The address of a value that can hold other values, by which the printer
+/// recognises it when it is reached again, along with the values it holds.
+fn children(val: &LispVal) -> Option<(*const (), Vec<LispVal>)> {
+    match val {
+        LispVal::Pair(pair) => Some((Rc::as_ptr(pair) as *const (), vec![pair.car(), pair.cdr()])),
+        LispVal::Vector(items) => Some((Rc::as_ptr(items) as *const (), items.borrow().clone())),
+        LispVal::Record(record) => Some((Rc::as_ptr(record) as *const (), record.values())),
+        LispVal::Values(values) => Some((Rc::as_ptr(values) as *const (), values.to_vec())),
+        LispVal::Error(err) => Some((Rc::as_ptr(err) as *const (), err.irritants())),
+        _ => None,
+    }
+}
+
+enum Visit {
+    Enter(LispVal),
+    Leave(*const ()),
+}
+
+/// Finds the pairs, vectors and other objects that are reached again while
+/// walking the structure below them, i.e. the places where a cycle closes.
+/// The walk keeps the objects still to visit on an explicit stack, so deeply
+/// nested and long lists take no extra native stack.
+fn find_cycles(val: &LispVal) -> HashMap<*const (), Option<usize>> {
+    let mut cycles = HashMap::new();
+    let mut path = HashSet::new();
+    let mut done = HashSet::new();
+    let mut pending = vec![Visit::Enter(val.clone())];
+    while let Some(visit) = pending.pop() {
+        let (ptr, children) = match visit {
+            Visit::Enter(val) => match children(&val) {
+                Some(found) => found,
+                None => continue,
+            },
+            Visit::Leave(ptr) => {
+                path.remove(&ptr);
+                continue;
+            }
+        };
+        if path.contains(&ptr) {
+            cycles.insert(ptr, None);
+        } else if done.insert(ptr) {
+            path.insert(ptr);
+            pending.push(Visit::Leave(ptr));
+            pending.extend(children.into_iter().rev().map(Visit::Enter));
+        }
+    }
+    cycles
+}
+
+/// What is left to print, innermost last.
+enum Task {
+    Val(LispVal),
+    /// The rest of a list after an element: more elements, a dotted tail or
+    /// just the closing parenthesis.
+    Tail(LispVal),
+    Text(String),
+}
+
+/// Formats one value. Pairs, vectors and other objects where a cycle closes
+/// get datum labels, so that a circular list prints as `#0=(1 2 . #0#)`
+/// rather than forever. The parts still to print are kept on a stack rather
+/// than recursed into, so no nesting is too deep to print.
+struct Printer<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    style: Style,
+    // The label of each such object, once it has been printed.
+    cycles: HashMap<*const (), Option<usize>>,
+    labels: usize,
+    tasks: Vec<Task>,
+}
+
+impl Printer<'_, '_> {
+    fn print(f: &mut fmt::Formatter, val: &LispVal, style: Style) -> fmt::Result {
+        let mut printer = Printer {
+            f,
+            style,
+            cycles: find_cycles(val),
+            labels: 0,
+            tasks: vec![Task::Val(val.clone())],
+        };
+        while let Some(task) = printer.tasks.pop() {
+            match task {
+                Task::Val(val) => printer.val(&val)?,
+                Task::Tail(next) => printer.tail(next)?,
+                Task::Text(text) => write!(printer.f, "{}", text)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Schedules `items`, separated by spaces, with `close` after them.
+    fn items(&mut self, items: Vec<LispVal>, close: &str) {
+        self.tasks.push(Task::Text(close.to_owned()));
+        for (i, item) in items.into_iter().enumerate().rev() {
+            self.tasks.push(Task::Val(item));
+            if i > 0 {
+                self.tasks.push(Task::Text(" ".to_owned()));
+            }
+        }
+    }
+
+    /// Writes the label of an object where a cycle closes: `#n=` the first
+    /// time and `#n#` thereafter. Returns whether it still needs printing.
+    fn label<T: ?Sized>(&mut self, rc: &Rc<T>) -> Result<bool, fmt::Error> {
+        match self.cycles.get_mut(&(Rc::as_ptr(rc) as *const ())) {
+            Some(Some(label)) => {
+                write!(self.f, "#{}#", label)?;
+                Ok(false)
+            }
+            Some(label) => {
+                *label = Some(self.labels);
+                write!(self.f, "#{}=", self.labels)?;
+                self.labels += 1;
+                Ok(true)
+            }
+            None => Ok(true),
+        }
+    }
+
+    fn pair(&mut self, pair: &Rc<Pair>) -> fmt::Result {
+        if !self.label(pair)? {
+            return Ok(());
+        }
+        if let (LispVal::Atom(keyword), LispVal::Pair(rest)) = (pair.car(), pair.cdr()) {
+            let prefix = quote_prefix(keyword.as_str());
+            if let (Some(prefix), LispVal::Nil) = (prefix, rest.cdr()) {
+                if !self.cycles.contains_key(&(Rc::as_ptr(&rest) as *const ())) {
+                    write!(self.f, "{}", prefix)?;
+                    self.tasks.push(Task::Val(rest.car()));
+                    return Ok(());
+                }
+            }
+        }
+        write!(self.f, "(")?;
+        self.tasks.push(Task::Tail(pair.cdr()));
+        self.tasks.push(Task::Val(pair.car()));
+        Ok(())
+    }
+
+    fn tail(&mut self, next: LispVal) -> fmt::Result {
+        match next {
+            LispVal::Nil => write!(self.f, ")"),
+            LispVal::Pair(pair) if !self.cycles.contains_key(&(Rc::as_ptr(&pair) as *const ())) => {
+                write!(self.f, " ")?;
+                self.tasks.push(Task::Tail(pair.cdr()));
+                self.tasks.push(Task::Val(pair.car()));
+                Ok(())
+            }
+            tail => {
+                write!(self.f, " . ")?;
+                self.tasks.push(Task::Text(")".to_owned()));
+                self.tasks.push(Task::Val(tail));
+                Ok(())
+            }
+        }
+    }
+
+    fn val(&mut self, val: &LispVal) -> fmt::Result {
+        let style = self.style;
+        let f = &mut *self.f;
+        match val {
+            LispVal::Atom(name) if style == Style::Write => {
+                write!(f, "{}", write_symbol(name.as_str()))
+            }
+            LispVal::Atom(name) => write!(f, "{}", name),
+            LispVal::Pair(pair) => self.pair(pair),
+            LispVal::Nil => write!(f, "()"),
+            LispVal::Number(n) => write!(f, "{}", n),
+            LispVal::String(s) => {
+                let text: String = s.borrow().iter().collect();
+                match style {
+                    Style::Write => write!(f, "{}", write_string(&text)),
+                    Style::Display => write!(f, "{}", text),
+                }
+            }
+            LispVal::Char(c) if style == Style::Write => write!(f, "{}", write_char(*c)),
+            LispVal::Char(c) => write!(f, "{}", c),
+            LispVal::Vector(items) => {
+                if self.label(items)? {
+                    write!(self.f, "#(")?;
+                    self.items(items.borrow().clone(), ")");
+                }
+                Ok(())
+            }
+            LispVal::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                for (i, byte) in bytes.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", byte)?;
+                }
+                write!(f, ")")
+            }
+            LispVal::Record(record) => {
+                if self.label(record)? {
+                    let record_type = record.record_type();
+                    write!(self.f, "#<{}", record_type.short_name())?;
+                    self.tasks.push(Task::Text(">".to_owned()));
+                    let fields = record_type.fields().iter().zip(record.values());
+                    for (field, value) in fields.rev() {
+                        self.tasks.push(Task::Val(value));
+                        self.tasks.push(Task::Text(format!(" {}: ", field)));
+                    }
+                }
+                Ok(())
+            }
+            LispVal::RecordType(record_type) => {
+                write!(f, "#<record-type {}>", record_type.short_name())
+            }
+            LispVal::Boolean(true) => write!(f, "#t"),
+            LispVal::Boolean(false) => write!(f, "#f"),
+            LispVal::PrimitiveFunc(primitive) => write!(f, "#<primitive {}>", primitive.name),
+            LispVal::NativeFunc(native) => write!(f, "#<primitive {}>", native.name),
+            LispVal::Func(_) | LispVal::Closure(_) => write!(f, "#<procedure>"),
+            LispVal::Continuation(_) | LispVal::VmContinuation(_) => write!(f, "#<continuation>"),
+            LispVal::Macro(_) => write!(f, "#<syntax>"),
+            LispVal::Error(err) => {
+                if self.label(err)? {
+                    write!(self.f, "#<error {}", write_string(&err.message()))?;
+                    self.tasks.push(Task::Text(">".to_owned()));
+                    for irritant in err.irritants().into_iter().rev() {
+                        self.tasks.push(Task::Val(irritant));
+                        self.tasks.push(Task::Text(" ".to_owned()));
+                    }
+                }
+                Ok(())
+            }
+            LispVal::Values(values) => {
+                if self.label(values)? {
+                    write!(self.f, "#<values")?;
+                    self.tasks.push(Task::Text(">".to_owned()));
+                    for value in values.iter().rev() {
+                        self.tasks.push(Task::Val(value.clone()));
+                        self.tasks.push(Task::Text(" ".to_owned()));
+                    }
+                }
+                Ok(())
+            }
+            LispVal::Port(port) if port.is_input() => write!(f, "#<input-port>"),
+            LispVal::Port(_) => write!(f, "#<output-port>"),
+            LispVal::Eof => write!(f, "#<eof>"),
+            LispVal::Unspecified => write!(f, "#<unspecified>"),
+        }
+    }
+}
+
+/// Formats a value the way `write` does: anything built from data (rather
+/// than procedures) reads back through `parse_lisp_expr` as an equal value.
+impl fmt::Display for LispVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::print(f, self, Style::Write)
+    }
+}
+
+/// A value formatted the way `display` does, as returned by
+/// [`LispVal::display`].
+pub struct Displayed<'a>(&'a LispVal);
+
+impl fmt::Display for Displayed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::print(f, self.0, Style::Display)
+    }
+}
+
+impl LispVal {
+    /// Formats strings, characters and symbols as their raw text, at any depth.
+    pub fn display(&self) -> Displayed<'_> {
+        Displayed(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::error::LispError;
+    use crate::number::Number;
+    use crate::parser::parse_lisp_expr;
+    use crate::printer::*;
+    use crate::symbol::Symbol;
+
+    fn read(text: &str) -> LispVal {
+        let (rest, value) = parse_lisp_expr(text).unwrap();
+        assert_eq!(rest, "");
+        value
+    }
+
+    #[test]
+    fn write_string_test() {
+        assert_eq!(write_string("plain"), "\"plain\"");
+        assert_eq!(write_string("say \"hi\""), r#""say \"hi\"""#);
+        assert_eq!(write_string("a\\b\nc\td"), r#""a\\b\nc\td""#);
+        assert_eq!(write_string("\u{0}\u{7f}λ"), "\"\\x0;\\x7f;λ\"");
+    }
+
+    #[test]
+    fn string_round_trip_test() {
+        for s in &[
+            "",
+            "tab\there",
+            "quote \" and \\",
+            "\r\n\u{7}\u{8}\u{1b}",
+            "λ ☃ |",
+        ] {
+            assert_eq!(read(&write_string(s)), LispVal::string(s.to_string()));
+        }
+    }
+
+    #[test]
+    fn write_char_test() {
+        assert_eq!(write_char('a'), "#\\a");
+        assert_eq!(write_char(' '), "#\\space");
+        assert_eq!(write_char('\n'), "#\\newline");
+        assert_eq!(write_char('\u{1}'), "#\\x1");
+        assert_eq!(write_char('λ'), "#\\λ");
+        for c in &['a', '(', ' ', '\u{0}', '\u{7f}', '\u{85}', 'x', 'λ'] {
+            assert_eq!(read(&write_char(*c)), LispVal::Char(*c));
+        }
+    }
+
+    #[test]
+    fn write_symbol_test() {
+        assert_eq!(write_symbol("foo"), "foo");
+        assert_eq!(write_symbol("set-car!"), "set-car!");
+        assert_eq!(write_symbol("hello world"), "|hello world|");
+        assert_eq!(write_symbol("42"), "|42|");
+        assert_eq!(write_symbol("#t"), "|#t|");
+        assert_eq!(write_symbol(""), "||");
+        assert_eq!(write_symbol("a|b\\c"), "|a\\|b\\\\c|");
+        for name in &[
+            "foo",
+            "hello world",
+            "42",
+            "#t",
+            "",
+            "a|b\\c",
+            "(",
+            "a\nb",
+            ".",
+            "...",
+            ".5",
+            "+",
+            "-i",
+            "1+",
+            "#foo",
+            "λ",
+            "a.b",
+        ] {
+            assert_eq!(read(&write_symbol(name)), LispVal::symbol(name));
+        }
+        // Writing a name does not intern it.
+        let before = Symbol::count();
+        for n in 0..100 {
+            write_symbol(&format!("never-read-{}", n));
+        }
+        assert_eq!(Symbol::count(), before);
+    }
+
+    #[test]
+    fn write_test() {
+        for text in &[
+            "(a \"b\\n\" (1 . 2.5) #t)",
+            "#(1 #(2) (3 . 4) #\\x)",
+            "#u8(0 127 255)",
+            "#()",
+            "#u8()",
+            "()",
+            "'a",
+            "`(a ,b ,@c)",
+            "(quote)",
+            "(quote a b)",
+            "(1/3 -2.5e-10 +inf.0 1.0+2.0i)",
+        ] {
+            assert_eq!(read(text).to_string(), *text);
+        }
+    }
+
+    #[test]
+    fn display_test() {
+        let value = read(r#"("a \"string\"" #\x #\space |odd symbol| 1.5 #("v"))"#);
+        assert_eq!(
+            value.display().to_string(),
+            r#"(a "string" x   odd symbol 1.5 #(v))"#
+        );
+        assert_eq!(
+            value.to_string(),
+            r#"("a \"string\"" #\x #\space |odd symbol| 1.5 #("v"))"#
+        );
+    }
+
+    #[test]
+    fn circular_write_test() {
+        let list = read("(1 2 3)");
+        let second = match &list {
+            LispVal::Pair(pair) => pair.cdr(),
+            _ => unreachable!(),
+        };
+        match &second {
+            LispVal::Pair(pair) => pair.set_cdr(second.clone()),
+            _ => unreachable!(),
+        }
+        assert_eq!(list.to_string(), "(1 . #0=(2 . #0#))");
+        match &list {
+            LispVal::Pair(pair) => pair.set_car(list.clone()),
+            _ => unreachable!(),
+        }
+        assert_eq!(list.to_string(), "#0=(#0# . #1=(2 . #1#))");
+        // Shared structure that does not loop is printed in full.
+        let shared = read("(a)");
+        let both = LispVal::list(vec![shared.clone(), shared]);
+        assert_eq!(both.display().to_string(), "((a) (a))");
+        // A vector that contains itself.
+        let vector = read("#(1 2)");
+        if let LispVal::Vector(items) = &vector {
+            items.borrow_mut()[1] = vector.clone();
+        }
+        assert_eq!(vector.to_string(), "#0=#(1 #0#)");
+        // An error object that is one of its own irritants.
+        let irritant = read("(1)");
+        let error = LispVal::error(LispError::User("oops".to_owned(), vec![irritant.clone()]));
+        if let LispVal::Pair(pair) = &irritant {
+            pair.set_car(error.clone());
+        }
+        assert_eq!(error.to_string(), "#0=#<error \"oops\" (#0#)>");
+    }
+
+    #[test]
+    fn deep_nesting_test() {
+        // `((((...))))`, nested through the car a hundred thousand times,
+        // then made circular at the bottom.
+        let depth = 100_000;
+        let innermost = LispVal::list(vec![LispVal::Nil]);
+        let nested = (1..depth).fold(innermost.clone(), |inner, _| LispVal::list(vec![inner]));
+        let expected = format!("{}(){}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(nested.to_string(), expected);
+        if let LispVal::Pair(pair) = &innermost {
+            pair.set_car(nested.clone());
+        }
+        let expected = format!("#0=({}#0#{}", "(".repeat(depth - 1), ")".repeat(depth));
+        assert_eq!(nested.to_string(), expected);
+    }
+
+    #[test]
+    fn write_round_trip_test() {
+        let atom = |name: &str| LispVal::symbol(name);
+        let values = vec![
+            LispVal::list(vec![
+                atom("quote"),
+                LispVal::list(vec![atom("unquote"), atom("x")]),
+            ]),
+            LispVal::dotted_list(
+                vec![
+                    LispVal::string("a\u{0}".to_owned()),
+                    LispVal::Char('\u{7f}'),
+                ],
+                LispVal::vector(vec![LispVal::bytevector(vec![1, 2])]),
+            ),
+            LispVal::list(vec![atom("quasiquote"), atom("unquote-splicing")]),
+            LispVal::list(vec![atom(""), atom("x y"), atom("1+"), atom("-")]),
+            LispVal::Number(Number::Real(0.1)),
+            LispVal::Number(Number::Real(-1e-300)),
+            LispVal::Number(Number::Real(123456789.125)),
+            LispVal::Number(Number::Integer(i64::MIN)),
+            LispVal::Boolean(false),
+        ];
+        for value in values {
+            assert_eq!(read(&value.to_string()), value);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::error::LispError;
+use crate::gc::{self, Trace, Tracer};
+use crate::parser::LispVal;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A record type made by `define-record-type`. Every evaluation of the
+/// definition makes a new type, and records belong only to the type that
+/// made them.
+///
+/// Host code gets hold of a type by evaluating its name, and can then make
+/// records of it or read the fields of ones made in Scheme:
+///
+/// ```
+/// use scheme::{Interpreter, IntoLisp, LispVal};
+///
+/// let interp = Interpreter::new();
+/// interp
+///     .eval_str("(define-record-type <point> (make-point x y) point? (x point-x) (y point-y))")
+///     .unwrap();
+/// let point = match interp.eval_str("<point>").unwrap() {
+///     LispVal::RecordType(point) => point,
+///     _ => unreachable!(),
+/// };
+/// let origin = point.make(vec![0.into_lisp(), 0.into_lisp()]).unwrap();
+/// interp.define_global("origin", origin);
+/// assert_eq!(interp.eval_str("(point-y origin)").unwrap(), 0.into_lisp());
+///
+/// let p = interp.eval_str("(make-point 1 2)").unwrap();
+/// assert_eq!(point.unpack(&p).unwrap().field("y"), Some(2.into_lisp()));
+/// assert_eq!(p.to_string(), "#<point x: 1 y: 2>");
+/// ```
+pub struct RecordType {
+    name: Symbol,
+    fields: Vec<Symbol>,
+}
+
+impl RecordType {
+    pub fn new(name: Symbol, fields: Vec<Symbol>) -> Rc<RecordType> {
+        Rc::new(RecordType { name, fields })
+    }
+
+    /// The name the type was defined with, such as `<point>`.
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn fields(&self) -> &[Symbol] {
+        &self.fields
+    }
+
+    /// The position of the field called `name`.
+    pub fn field_index(&self, name: Symbol) -> Option<usize> {
+        self.fields.iter().position(|field| *field == name)
+    }
+
+    /// Makes a record of this type from the values of all its fields, in
+    /// the order they were defined.
+    pub fn make(self: &Rc<Self>, values: Vec<LispVal>) -> Result<LispVal, LispError> {
+        if values.len() != self.fields.len() {
+            return Err(LispError::NumArgs(self.fields.len(), values));
+        }
+        Ok(LispVal::Record(gc::alloc(Record {
+            record_type: self.clone(),
+            values: RefCell::new(values),
+        })))
+    }
+
+    /// Whether `value` is a record of this type.
+    pub fn is_instance(self: &Rc<Self>, value: &LispVal) -> bool {
+        matches!(value, LispVal::Record(record) if Rc::ptr_eq(&record.record_type, self))
+    }
+
+    /// The record `value` as one of this type, or an error naming the type.
+    pub fn unpack<'a>(self: &Rc<Self>, value: &'a LispVal) -> Result<&'a Rc<Record>, LispError> {
+        match value {
+            LispVal::Record(record) if Rc::ptr_eq(&record.record_type, self) => Ok(record),
+            _ => Err(LispError::TypeMismatch(self.name.as_str(), value.clone())),
+        }
+    }
+
+    /// The name records are written with: the type's name without the angle
+    /// brackets that conventionally surround it.
+    pub(crate) fn short_name(&self) -> &'static str {
+        let name = self.name.as_str();
+        match name.strip_prefix('<').and_then(|n| n.strip_suffix('>')) {
+            Some(short) if !short.is_empty() => short,
+            _ => name,
+        }
+    }
+}
+
+// Record types compare by identity, since two definitions with the same
+// fields still make different types.
+impl PartialEq for RecordType {
+    fn eq(&self, other: &RecordType) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<record-type {}>", self.name)
+    }
+}
+
+/// An instance of a record type, whose fields can be changed by the type's
+/// modifiers. Like a pair, a record is shared by every copy of the value.
+pub struct Record {
+    record_type: Rc<RecordType>,
+    values: RefCell<Vec<LispVal>>,
+}
+
+impl Record {
+    pub fn record_type(&self) -> &Rc<RecordType> {
+        &self.record_type
+    }
+
+    /// The value of the field at `index`.
+    pub fn get(&self, index: usize) -> LispVal {
+        self.values.borrow()[index].clone()
+    }
+
+    pub fn set(&self, index: usize, value: LispVal) {
+        self.values.borrow_mut()[index] = value;
+    }
+
+    /// The value of the field called `name`, if the record has one.
+    pub fn field(&self, name: &str) -> Option<LispVal> {
+        let index = self.record_type.field_index(Symbol::intern(name))?;
+        Some(self.get(index))
+    }
+
+    /// Sets the field called `name`, failing if the record has no such field.
+    pub fn set_field(&self, name: &str, value: LispVal) -> Result<(), LispError> {
+        match self.record_type.field_index(Symbol::intern(name)) {
+            Some(index) => {
+                self.set(index, value);
+                Ok(())
+            }
+            None => Err(LispError::Default(format!(
+                "{} has no field {}",
+                self.record_type.name, name
+            ))),
+        }
+    }
+
+    /// The value of every field, in the order they were defined.
+    pub fn values(&self) -> Vec<LispVal> {
+        self.values.borrow().clone()
+    }
+}
+
+impl Trace for Record {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self.values.borrow().iter() {
+            tracer.value(value);
+        }
+    }
+
+    fn clear(&self) {
+        drop(self.values.take());
+    }
+}
+
+// Records compare by identity, as `equal?` does in most Schemes.
+impl PartialEq for Record {
+    fn eq(&self, other: &Record) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<record {} {:p}>", self.record_type.name, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::int;
+    use crate::record::*;
+
+    #[test]
+    fn record_test() {
+        let fields = vec![Symbol::intern("x"), Symbol::intern("y")];
+        let point = RecordType::new(Symbol::intern("<point>"), fields);
+        assert_eq!(point.short_name(), "point");
+        let p = point.make(vec![int(1), int(2)]).unwrap();
+        assert!(point.is_instance(&p));
+        let record = point.unpack(&p).unwrap();
+        assert_eq!(record.field("y"), Some(int(2)));
+        assert_eq!(record.field("z"), None);
+        record.set_field("x", int(5)).unwrap();
+        assert!(record.set_field("z", int(5)).is_err());
+        assert_eq!(record.values(), vec![int(5), int(2)]);
+        assert_eq!(p.to_string(), "#<point x: 5 y: 2>");
+        assert_eq!(
+            point.make(vec![int(1)]),
+            Err(LispError::NumArgs(2, vec![int(1)]))
+        );
+
+        // Another type with the same name and fields is a different type.
+        let other = RecordType::new(Symbol::intern("<point>"), point.fields().to_vec());
+        assert!(!other.is_instance(&p));
+        assert_eq!(
+            other.unpack(&p).unwrap_err(),
+            LispError::TypeMismatch("<point>", p.clone())
+        );
+        assert_eq!(
+            RecordType::new(Symbol::intern("empty"), vec![]).short_name(),
+            "empty"
+        );
+    }
+}
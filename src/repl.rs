@@ -0,0 +1,150 @@
+//! An interactive read-eval-print loop over an [`Interpreter`].
+
+use crate::interpreter::Interpreter;
+use crate::parser::{parse_program, LispVal};
+use crate::script::load_file;
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+const HELP: &str = "\
+Enter Scheme expressions to evaluate them. An expression may span several
+lines; the prompt changes to `...` until its parentheses are balanced.
+
+Meta-commands:
+  ,help         Show this message
+  ,load <file>  Evaluate every expression in <file>
+  ,quit         Leave the REPL
+";
+
+enum Command {
+    Continue,
+    Quit,
+}
+
+fn run_command<W: Write>(interp: &Interpreter, line: &str, output: &mut W) -> io::Result<Command> {
+    let mut words = line.splitn(2, char::is_whitespace);
+    let name = words.next().unwrap_or("");
+    let arg = words.next().map(str::trim).unwrap_or("");
+    match (name, arg) {
+        (",quit", "") | (",q", "") => return Ok(Command::Quit),
+        (",help", "") | (",h", "") => write!(output, "{}", HELP)?,
+        (",load", "") => writeln!(output, "Usage: ,load <file>")?,
+        (",load", path) => match load_file(interp, path) {
+            Ok(_) => writeln!(output, "Loaded {}", path)?,
+            Err(err) => writeln!(output, "{}", err)?,
+        },
+        _ => writeln!(output, "Unknown command {}; try ,help", line)?,
+    }
+    Ok(Command::Continue)
+}
+
+/// Runs a read-eval-print loop until `,quit` or the end of `input`.
+///
+/// Lines are buffered until they hold complete datums, so an expression can
+/// be spread over several lines. Errors are reported and the loop carries on.
+pub fn run<R: BufRead, W: Write>(interp: &Interpreter, input: R, output: &mut W) -> io::Result<()> {
+    let mut pending = String::new();
+    let mut lines = input.lines();
+    loop {
+        let prompt = if pending.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        write!(output, "{}", prompt)?;
+        output.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if pending.is_empty() && line.trim_start().starts_with(',') {
+            match run_command(interp, line.trim(), output)? {
+                Command::Quit => return Ok(()),
+                Command::Continue => continue,
+            }
+        }
+        pending.push_str(&line);
+        pending.push('\n');
+        let program = match parse_program(&pending) {
+            Ok(program) => program,
+            Err(err) if err.incomplete => continue,
+            Err(err) => {
+                writeln!(output, "{}", err)?;
+                pending.clear();
+                continue;
+            }
+        };
+        pending.clear();
+        for expr in program {
+            match interp.eval(&expr) {
+                Ok(LispVal::Unspecified) => (),
+                Ok(value) => writeln!(output, "{}", value)?,
+                Err(err) => {
+                    writeln!(output, "Error: {}", err)?;
+                    break;
+                }
+            }
+        }
+    }
+    writeln!(output)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::repl::*;
+    use std::fs;
+
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        run(&Interpreter::new(), input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn evaluate_test() {
+        assert_eq!(session("(+ 1 2)\n"), "> 3\n> \n");
+        assert_eq!(
+            session("(define x 5) x '(a \"b\")\n"),
+            "> 5\n(a \"b\")\n> \n"
+        );
+        assert_eq!(session("\n; nothing\n"), "> > > \n");
+    }
+
+    #[test]
+    fn multiline_test() {
+        assert_eq!(
+            session("(define (f n)\n  (* n 2)) ; done\n(f\n\n 21)\n"),
+            "> ... > ... ... 42\n> \n"
+        );
+        assert_eq!(
+            session("\"open\nstring\"\n"),
+            "> ... \"open\\nstring\"\n> \n"
+        );
+    }
+
+    #[test]
+    fn error_recovery_test() {
+        let output = session("(car 1)\n(1 . )\n(+ 1 1)\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("> Error: "));
+        assert!(lines[1].starts_with("> Parse error at line 1"));
+        assert!(lines.contains(&"> 2"));
+    }
+
+    #[test]
+    fn command_test() {
+        assert_eq!(session(",quit\n(+ 1 2)\n"), "> ");
+        assert!(session(",help\n").contains(",load <file>"));
+        assert!(session(",frobnicate\n").contains("Unknown command ,frobnicate"));
+        assert!(session(",load /no/such/file.scm\n").contains("/no/such/file.scm: "));
+
+        let path = std::env::temp_dir().join(format!("repl-load-{}.scm", std::process::id()));
+        fs::write(&path, "(define (double n) (* n 2))\n").unwrap();
+        let output = session(&format!(",load {}\n(double 4)\n", path.display()));
+        fs::remove_file(&path).unwrap();
+        assert!(output.ends_with("> 8\n> \n"));
+    }
+}
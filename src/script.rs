@@ -0,0 +1,64 @@
+//! Running whole programs for the command-line front end, with errors
+//! rendered as the messages shown to the user.
+
+use crate::error::LispError;
+use crate::interpreter::Interpreter;
+use crate::parser::LispVal;
+use std::fs;
+
+/// Evaluates every top-level form in `source` in order, returning the value of
+/// the last one. Errors are rendered as the messages shown to the user.
+pub fn run_source(interp: &Interpreter, source: &str) -> Result<LispVal, String> {
+    interp.eval_str(source).map_err(|err| match err {
+        LispError::Parser(err) => err.to_string(),
+        err => format!("Error: {}", err),
+    })
+}
+
+/// Evaluates every top-level form in the file at `path`, stopping at the first
+/// error.
+pub fn load_file(interp: &Interpreter, path: &str) -> Result<LispVal, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    run_source(interp, &source).map_err(|err| format!("{}: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::number::Number;
+    use crate::script::*;
+
+    #[test]
+    fn run_source_test() {
+        let interp = Interpreter::new();
+        assert_eq!(
+            run_source(&interp, "(define x 4) ; four\n(* x x)").unwrap(),
+            LispVal::Number(Number::Integer(16))
+        );
+        assert_eq!(run_source(&interp, "").unwrap(), LispVal::Unspecified);
+        assert!(run_source(&interp, "(car '())")
+            .unwrap_err()
+            .starts_with("Error: "));
+        assert!(run_source(&interp, "(+ 1")
+            .unwrap_err()
+            .starts_with("Parse error at line 1"));
+    }
+
+    #[test]
+    fn load_file_test() {
+        let interp = Interpreter::new();
+        let path = std::env::temp_dir().join(format!("script-load-{}.scm", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "(define y 2)\n(+ y 1)\n(car y)\n").unwrap();
+        let err = load_file(&interp, path).unwrap_err();
+        fs::remove_file(path).unwrap();
+        assert!(err.starts_with(&format!("{}: Error: ", path)));
+        assert_eq!(
+            run_source(&interp, "y").unwrap(),
+            LispVal::Number(Number::Integer(2))
+        );
+        assert!(load_file(&interp, "/no/such/file.scm")
+            .unwrap_err()
+            .starts_with("/no/such/file.scm: "));
+    }
+}
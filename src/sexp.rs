@@ -0,0 +1,798 @@
+//! Serde support for the S-expression syntax the reader accepts, so it can
+//! be used as a data format for Rust types. Enabled by the `serde` feature.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Server {
+//!     host: String,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! let server = Server { host: "example.org".to_owned(), ports: vec![80, 443] };
+//! let text = scheme::sexp::to_string(&server).unwrap();
+//! assert_eq!(text, r#"((host . "example.org") (ports 80 443))"#);
+//! assert_eq!(scheme::sexp::from_str::<Server>(&text).unwrap(), server);
+//! ```
+//!
+//! Rust values map onto Scheme data as follows, matching
+//! `#[derive(IntoLisp, FromLisp)]`:
+//!
+//! | Rust                                | Scheme                             |
+//! |-------------------------------------|------------------------------------|
+//! | `bool`, integers, floats, `char`    | booleans, numbers, characters      |
+//! | `String`, `&str`                    | strings                            |
+//! | byte buffers                        | bytevectors, `#u8(1 2)`            |
+//! | `None` / `Some(x)`                  | `#f` / `x`                         |
+//! | `()`, unit structs                  | `()`                               |
+//! | newtype structs                     | the wrapped value                  |
+//! | sequences, tuples, tuple structs    | lists, `(1 2 3)`                   |
+//! | maps                                | association lists, `((k . v) ...)` |
+//! | structs                             | association lists keyed by symbols |
+//! | unit variants                       | symbols, `red`                     |
+//! | other variants                      | lists headed by a symbol           |
+//!
+//! A newtype variant is `(name value)`, a tuple variant `(name 1 2)` and a
+//! struct variant `(name (field . value) ...)`. Field and variant names are
+//! used as serde reports them, so `#[serde(rename_all = "kebab-case")]` gives
+//! the usual Scheme spelling. Vectors are read as sequences too.
+//!
+//! Because `None` is `#f`, an `Option<bool>` holding `Some(false)` reads back
+//! as `None`.
+
+use crate::number::Number;
+use crate::parser::{parse_program, LispVal, ParseError};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use std::fmt;
+use std::vec;
+
+/// Something that went wrong converting between Rust and Scheme data.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The text given to [`from_str`] is not a single datum.
+    Parse(ParseError),
+    /// The data does not fit the Rust type, or a serde impl failed.
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(err) => write!(f, "{}", err),
+            Error::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error::Message(message.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error::Message(message.to_string())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Error {
+        Error::Parse(err)
+    }
+}
+
+fn mismatch(expected: &str, found: &LispVal) -> Error {
+    Error::Message(format!(
+        "Invalid type: expected {}, found {}",
+        expected, found
+    ))
+}
+
+/// Converts `value` into a Scheme datum.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<LispVal, Error> {
+    value.serialize(Serializer)
+}
+
+/// Writes `value` as S-expression text.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    Ok(to_value(value)?.to_string())
+}
+
+/// Converts a Scheme datum into a `T`.
+pub fn from_value<T: DeserializeOwned>(value: &LispVal) -> Result<T, Error> {
+    T::deserialize(Deserializer {
+        value: value.clone(),
+    })
+}
+
+/// Reads a `T` from text holding exactly one datum.
+pub fn from_str<T: DeserializeOwned>(text: &str) -> Result<T, Error> {
+    match parse_program(text)?.as_slice() {
+        [value] => from_value(value),
+        data => Err(Error::Message(format!(
+            "Expected one datum, found {}",
+            data.len()
+        ))),
+    }
+}
+
+/// A serde serializer producing a `LispVal`.
+pub struct Serializer;
+
+fn integer<T: Into<BigInt>>(n: T) -> LispVal {
+    LispVal::Number(Number::from(n.into()))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = LispVal;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<LispVal, Error> {
+        Ok(LispVal::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<LispVal, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<LispVal, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<LispVal, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<LispVal, Error> {
+        Ok(LispVal::Number(Number::Integer(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<LispVal, Error> {
+        Ok(integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<LispVal, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<LispVal, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<LispVal, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<LispVal, Error> {
+        Ok(integer(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<LispVal, Error> {
+        Ok(integer(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<LispVal, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<LispVal, Error> {
+        Ok(LispVal::Number(Number::Real(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<LispVal, Error> {
+        Ok(LispVal::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<LispVal, Error> {
+        Ok(LispVal::string(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<LispVal, Error> {
+        Ok(LispVal::bytevector(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<LispVal, Error> {
+        Ok(LispVal::Boolean(false))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<LispVal, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<LispVal, Error> {
+        Ok(LispVal::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<LispVal, Error> {
+        Ok(LispVal::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<LispVal, Error> {
+        Ok(LispVal::symbol(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<LispVal, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<LispVal, Error> {
+        Ok(LispVal::list(vec![
+            LispVal::symbol(variant),
+            value.serialize(self)?,
+        ]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        let mut items = Vec::with_capacity(len + 1);
+        items.push(LispVal::symbol(variant));
+        Ok(SeqSerializer { items })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer {
+            entries: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer, Error> {
+        let mut entries = Vec::with_capacity(len + 1);
+        entries.push(LispVal::symbol(variant));
+        Ok(StructSerializer { entries })
+    }
+}
+
+/// Collects the items of a sequence, tuple or tuple variant into a list.
+pub struct SeqSerializer {
+    items: Vec<LispVal>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        Ok(LispVal::list(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Collects the entries of a map into an association list.
+pub struct MapSerializer {
+    entries: Vec<LispVal>,
+    key: Option<LispVal>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(to_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value is called after serialize_key");
+        self.entries.push(LispVal::cons(key, to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        Ok(LispVal::list(self.entries))
+    }
+}
+
+/// Collects the fields of a struct or struct variant into an association
+/// list keyed by symbols.
+pub struct StructSerializer {
+    entries: Vec<LispVal>,
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.entries
+            .push(LispVal::cons(LispVal::symbol(key), to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        Ok(LispVal::list(self.entries))
+    }
+}
+
+impl ser::SerializeStructVariant for StructSerializer {
+    type Ok = LispVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<LispVal, Error> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+/// A serde deserializer reading from a `LispVal`.
+pub struct Deserializer {
+    value: LispVal,
+}
+
+impl Deserializer {
+    pub fn new(value: LispVal) -> Deserializer {
+        Deserializer { value }
+    }
+
+    /// The items of a list or vector.
+    fn items(&self, expected: &str) -> Result<Vec<LispVal>, Error> {
+        match &self.value {
+            LispVal::Vector(items) => Ok(items.borrow().clone()),
+            value => value.list_to_vec().ok_or_else(|| mismatch(expected, value)),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &self.value {
+            LispVal::Boolean(b) => visitor.visit_bool(*b),
+            LispVal::Number(Number::Integer(n)) => visitor.visit_i64(*n),
+            LispVal::Number(Number::BigInteger(n)) => {
+                if let Some(n) = n.to_u64() {
+                    visitor.visit_u64(n)
+                } else if let Some(n) = n.to_i128() {
+                    visitor.visit_i128(n)
+                } else if let Some(n) = n.to_u128() {
+                    visitor.visit_u128(n)
+                } else {
+                    Err(mismatch("integer of at most 128 bits", &self.value))
+                }
+            }
+            LispVal::Number(n) => match n.to_f64() {
+                Some(x) => visitor.visit_f64(x),
+                None => Err(mismatch("real number", &self.value)),
+            },
+            LispVal::Char(c) => visitor.visit_char(*c),
+            LispVal::String(s) => visitor.visit_string(s.borrow().iter().collect()),
+            LispVal::Atom(name) => visitor.visit_str(name.as_str()),
+            LispVal::Bytevector(bytes) => visitor.visit_bytes(&bytes.borrow()),
+            LispVal::Nil => visitor.visit_unit(),
+            LispVal::Pair(_) | LispVal::Vector(_) => self.deserialize_seq(visitor),
+            value => Err(mismatch("data", value)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            LispVal::Boolean(false) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            LispVal::Nil => visitor.visit_unit(),
+            ref value => Err(mismatch("()", value)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let items = self.items("list")?;
+        visitor.visit_seq(SeqAccess {
+            items: items.into_iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries = self.items("association list")?;
+        visitor.visit_map(MapAccess {
+            entries: entries.into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match &self.value {
+            LispVal::Atom(name) => {
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(name.as_str()))
+            }
+            LispVal::Pair(pair) => match pair.car() {
+                LispVal::Atom(name) => visitor.visit_enum(EnumAccess {
+                    variant: name.as_str(),
+                    rest: pair.cdr(),
+                }),
+                _ => Err(mismatch("variant", &self.value)),
+            },
+            value => Err(mismatch("variant", value)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for LispVal {
+    type Deserializer = Deserializer;
+
+    fn into_deserializer(self) -> Deserializer {
+        Deserializer::new(self)
+    }
+}
+
+struct SeqAccess {
+    items: vec::IntoIter<LispVal>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.items.next() {
+            Some(item) => seed.deserialize(Deserializer::new(item)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess {
+    entries: vec::IntoIter<LispVal>,
+    value: Option<LispVal>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some(LispVal::Pair(pair)) => {
+                self.value = Some(pair.cdr());
+                seed.deserialize(Deserializer::new(pair.car())).map(Some)
+            }
+            Some(entry) => Err(mismatch("pair", &entry)),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed is called after next_key_seed");
+        seed.deserialize(Deserializer::new(value))
+    }
+}
+
+/// A variant written as a list: its name, then `rest`.
+struct EnumAccess {
+    variant: &'static str,
+    rest: LispVal,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer), Error> {
+        let variant =
+            seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.variant))?;
+        Ok((variant, Deserializer::new(self.rest)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserializer::deserialize_unit(self, de::IgnoredAny).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        match self.items("list of one value")?.as_slice() {
+            [value] => seed.deserialize(Deserializer::new(value.clone())),
+            _ => Err(mismatch("list of one value", &self.value)),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::sexp::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Config {
+        name: String,
+        max_size: u64,
+        ratio: f64,
+        tags: Vec<String>,
+        owner: Option<String>,
+        limits: BTreeMap<String, i32>,
+        mode: Mode,
+        shapes: Vec<Shape>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    enum Mode {
+        ReadOnly,
+        ReadWrite,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    enum Shape {
+        Circle(f64),
+        Line(i32, i32),
+        Rect { width: u32, height: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper(char);
+
+    #[test]
+    fn round_trip_test() {
+        let mut limits = BTreeMap::new();
+        limits.insert("cpu".to_owned(), 2);
+        let config = Config {
+            name: "svc".to_owned(),
+            max_size: u64::MAX,
+            ratio: 0.5,
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            owner: None,
+            limits,
+            mode: Mode::ReadOnly,
+            shapes: vec![
+                Shape::Circle(1.5),
+                Shape::Line(1, 2),
+                Shape::Rect {
+                    width: 3,
+                    height: 4,
+                },
+            ],
+        };
+        let text = to_string(&config).unwrap();
+        assert_eq!(
+            text,
+            "((name . \"svc\") (max-size . 18446744073709551615) (ratio . 0.5) \
+             (tags \"a\" \"b\") (owner . #f) (limits (\"cpu\" . 2)) (mode . read-only) \
+             (shapes (circle 1.5) (line 1 2) (rect (width . 3) (height . 4))))"
+        );
+        assert_eq!(from_str::<Config>(&text).unwrap(), config);
+    }
+
+    #[test]
+    fn from_str_test() {
+        assert_eq!(from_str::<Vec<u8>>("#(1 2) ; bytes").unwrap(), vec![1, 2]);
+        assert_eq!(
+            from_str::<(i32, String)>("(1 \"x\")").unwrap(),
+            (1, "x".to_owned())
+        );
+        assert_eq!(from_str::<Wrapper>("#\\a").unwrap(), Wrapper('a'));
+        assert_eq!(from_str::<()>("()").unwrap(), ());
+        assert_eq!(from_str::<f32>("1/2").unwrap(), 0.5);
+        assert_eq!(from_str::<Option<i32>>("7").unwrap(), Some(7));
+        assert_eq!(from_str::<Mode>("read-write").unwrap(), Mode::ReadWrite);
+        assert_eq!(
+            from_str::<Shape>("(rect (height . 1) (width . 2))").unwrap(),
+            Shape::Rect {
+                width: 2,
+                height: 1
+            }
+        );
+        assert!(from_str::<u8>("300").is_err());
+        assert!(from_str::<Vec<i32>>("(1 . 2)").is_err());
+        assert!(from_str::<Shape>("(circle 1 2)").is_err());
+        assert!(from_str::<Mode>("(read-only 1)").is_err());
+        assert!(matches!(from_str::<i32>("(1"), Err(Error::Parse(_))));
+        assert_eq!(
+            from_str::<i32>("1 2").unwrap_err().to_string(),
+            "Expected one datum, found 2"
+        );
+    }
+}
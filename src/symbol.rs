@@ -0,0 +1,111 @@
+use crate::parser::LispVal;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// An interned symbol name. Two symbols are the same symbol exactly when
+/// their ids are equal, so comparing and hashing them never looks at the
+/// text.
+///
+/// Each thread has a table of its own, so a symbol only means something on
+/// the thread that interned it. The marker keeps symbols from being sent to
+/// or shared with another thread, where the same id names something else:
+///
+/// ```compile_fail
+/// let lambda = scheme::Symbol::intern("lambda");
+/// std::thread::spawn(move || lambda.as_str());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+/// Every name interned so far on this thread. Names are never freed, which
+/// lets [`Symbol::as_str`] hand out `'static` references: a program that
+/// keeps making new names, say with `string->symbol`, holds on to every one
+/// of them for as long as the process runs. Names the expander makes up for local
+/// variables are reused from one top-level form to the next, so expanding
+/// code does not grow the table once it has seen that code.
+#[derive(Default)]
+struct SymbolTable {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, Symbol>,
+}
+
+thread_local! {
+    static SYMBOLS: RefCell<SymbolTable> = RefCell::new(SymbolTable::default());
+}
+
+impl Symbol {
+    /// Returns the symbol named `name`, adding it to the table the first time
+    /// it is seen.
+    pub fn intern(name: &str) -> Symbol {
+        SYMBOLS.with(|symbols| {
+            let mut symbols = symbols.borrow_mut();
+            if let Some(symbol) = symbols.ids.get(name) {
+                return *symbol;
+            }
+            let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+            let symbol = Symbol(symbols.names.len() as u32, PhantomData);
+            symbols.names.push(name);
+            symbols.ids.insert(name, symbol);
+            symbol
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        SYMBOLS.with(|symbols| symbols.borrow().names[self.0 as usize])
+    }
+
+    /// How many symbols have been interned on this thread.
+    #[cfg(test)]
+    pub(crate) fn count() -> usize {
+        SYMBOLS.with(|symbols| symbols.borrow().names.len())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl LispVal {
+    pub fn symbol(name: &str) -> LispVal {
+        LispVal::Atom(Symbol::intern(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::symbol::*;
+
+    #[test]
+    fn intern_test() {
+        let a = Symbol::intern("lambda");
+        let b = Symbol::intern(&String::from("lambda"));
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("Lambda"));
+        assert_eq!(a.as_str(), "lambda");
+        assert_eq!(a, "lambda");
+        assert_eq!(Symbol::intern("").to_string(), "");
+    }
+}
@@ -0,0 +1,806 @@
+use crate::analyze::{analyze, ControlOp};
+use crate::compile::{compile, Op, Template};
+use crate::error::LispError;
+use crate::eval::{
+    self, is_true, spread_args, trace_dynamic, trace_steps, travel, Env, Handler, Wind, WindStep,
+};
+use crate::expand::expand;
+use crate::gc::{self, Trace, Tracer};
+use crate::parser::LispVal;
+use crate::primitives::is_eqv;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// The variables of one procedure call, addressed by their position.
+pub struct Locals {
+    slots: RefCell<Vec<LispVal>>,
+    parent: Option<Rc<Locals>>,
+}
+
+/// A compiled procedure together with the frames and globals it closes over.
+pub struct Closure {
+    template: Rc<Template>,
+    locals: Option<Rc<Locals>>,
+    globals: Rc<Env>,
+}
+
+impl Trace for Locals {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self.slots.borrow().iter() {
+            tracer.value(value);
+        }
+        if let Some(parent) = &self.parent {
+            tracer.rc(parent);
+        }
+    }
+
+    fn clear(&self) {
+        let slots = self.slots.take();
+        drop(slots);
+    }
+}
+
+impl Trace for Closure {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(locals) = &self.locals {
+            tracer.rc(locals);
+        }
+        tracer.rc(&self.globals);
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Closure) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<closure {:p}>", self)
+    }
+}
+
+/// The work remaining once the value being computed is known. `Code` resumes
+/// a procedure after the call it was waiting on; the others play the same
+/// part as in the tree-walking evaluator.
+#[derive(Clone)]
+enum Frame {
+    Code(Rc<Closure>, usize, Option<Rc<Locals>>),
+    WindBefore(LispVal, LispVal, LispVal),
+    WindBody(Rc<Wind>),
+    WindAfter(LispVal),
+    Travel(
+        Vec<WindStep>,
+        Option<Rc<Wind>>,
+        Option<Rc<Handler>>,
+        LispVal,
+    ),
+    Handlers(Option<Rc<Handler>>),
+    Raised(LispVal),
+}
+
+impl Frame {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Frame::Code(closure, _, locals) => {
+                tracer.rc(closure);
+                if let Some(locals) = locals {
+                    tracer.rc(locals);
+                }
+            }
+            Frame::WindBefore(before, thunk, after) => {
+                tracer.value(before);
+                tracer.value(thunk);
+                tracer.value(after);
+            }
+            Frame::WindBody(wind) => tracer.rc(wind),
+            Frame::WindAfter(value) | Frame::Raised(value) => tracer.value(value),
+            Frame::Travel(steps, winders, handlers, result) => {
+                trace_steps(steps, tracer);
+                trace_dynamic(winders, handlers, tracer);
+                tracer.value(result);
+            }
+            Frame::Handlers(handlers) => trace_dynamic(&None, handlers, tracer),
+        }
+    }
+}
+
+/// A continuation captured by `call/cc`, with the operands its procedures
+/// had evaluated so far.
+pub struct Continuation {
+    frames: Vec<Frame>,
+    stack: Vec<LispVal>,
+    winders: Option<Rc<Wind>>,
+    handlers: Option<Rc<Handler>>,
+}
+
+impl Trace for Continuation {
+    fn trace(&self, tracer: &mut Tracer) {
+        for frame in &self.frames {
+            frame.trace(tracer);
+        }
+        for value in &self.stack {
+            tracer.value(value);
+        }
+        trace_dynamic(&self.winders, &self.handlers, tracer);
+    }
+}
+
+impl PartialEq for Continuation {
+    fn eq(&self, other: &Continuation) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Continuation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<continuation>")
+    }
+}
+
+enum State {
+    Run,
+    Apply(LispVal, Vec<LispVal>),
+    Return(LispVal),
+}
+
+type StateResult = Result<State, LispError>;
+
+/// Expands and compiles `expr`, then runs it with `env` as the global
+/// environment.
+pub fn eval(env: &Rc<Env>, expr: &LispVal) -> Result<LispVal, LispError> {
+    let node = analyze(&expand(env, expr)?)?;
+    let closure = gc::alloc(Closure {
+        template: compile(&node),
+        locals: None,
+        globals: env.clone(),
+    });
+    Machine::new(closure).run(State::Run)
+}
+
+/// Calls a compiled procedure from outside the VM, such as from the
+/// tree-walking evaluator. Continuations captured inside cannot escape it.
+pub(crate) fn apply(closure: &Rc<Closure>, args: Vec<LispVal>) -> Result<LispVal, LispError> {
+    Machine::new(closure.clone()).run(State::Apply(LispVal::Closure(closure.clone()), args))
+}
+
+/// Moves the arguments of a call into the frame for `template`, after its
+/// parameters checking their number and collecting any rest list.
+fn bind(template: &Template, mut slots: Vec<LispVal>) -> Result<Vec<LispVal>, LispError> {
+    let argc = slots.len();
+    if argc < template.params || (!template.vararg && argc > template.params) {
+        return Err(LispError::NumArgs(template.params, slots));
+    }
+    if template.vararg {
+        let rest = LispVal::list(slots.split_off(template.params));
+        slots.push(rest);
+    }
+    slots.resize(template.frame_size, LispVal::Unspecified);
+    Ok(slots)
+}
+
+struct Machine {
+    // The procedure running, the index of its next instruction and its
+    // variables.
+    closure: Rc<Closure>,
+    pc: usize,
+    locals: Option<Rc<Locals>>,
+    stack: Vec<LispVal>,
+    frames: Vec<Frame>,
+    winders: Option<Rc<Wind>>,
+    handlers: Option<Rc<Handler>>,
+}
+
+impl Machine {
+    fn new(closure: Rc<Closure>) -> Machine {
+        Machine {
+            locals: closure.locals.clone(),
+            closure,
+            pc: 0,
+            stack: vec![],
+            frames: vec![],
+            winders: None,
+            handlers: None,
+        }
+    }
+
+    fn run(&mut self, mut state: State) -> Result<LispVal, LispError> {
+        loop {
+            gc::safe_point();
+            let next = match state {
+                State::Run => self.execute(),
+                State::Apply(func, args) => self.apply(func, args),
+                State::Return(value) => match self.frames.pop() {
+                    Some(frame) => self.resume(frame, value),
+                    None => return Ok(value),
+                },
+            };
+            // Errors become error objects raised to the current handler, if
+            // there is one.
+            state = match next {
+                Ok(state) => state,
+                Err(err) if self.handlers.is_some() => self.raise(LispVal::error(err), false)?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn push(&mut self, value: LispVal) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> LispVal {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn frame(&self, depth: u32) -> &Rc<Locals> {
+        let mut locals = self.locals.as_ref().expect("local variables have frames");
+        for _ in 0..depth {
+            locals = locals.parent.as_ref().expect("local variables have frames");
+        }
+        locals
+    }
+
+    fn save(&mut self) {
+        self.frames.push(Frame::Code(
+            self.closure.clone(),
+            self.pc,
+            self.locals.take(),
+        ));
+    }
+
+    fn enter(&mut self, closure: Rc<Closure>, args: Vec<LispVal>) -> Result<(), LispError> {
+        // A loop of tail calls never leaves `execute`, so it has to give the
+        // collector its chance here.
+        gc::safe_point();
+        let slots = bind(&closure.template, args)?;
+        self.locals = Some(gc::alloc(Locals {
+            slots: RefCell::new(slots),
+            parent: closure.locals.clone(),
+        }));
+        self.closure = closure;
+        self.pc = 0;
+        Ok(())
+    }
+
+    /// Runs instructions until the current procedure returns or calls
+    /// something other than a primitive or compiled procedure.
+    fn execute(&mut self) -> StateResult {
+        loop {
+            let op = self.closure.template.ops[self.pc];
+            self.pc += 1;
+            match op {
+                Op::Const(index) => {
+                    let value = self.closure.template.consts[index as usize].clone();
+                    self.push(value);
+                }
+                Op::Local(depth, index) => {
+                    let value = self.frame(depth).slots.borrow()[index as usize].clone();
+                    self.push(value);
+                }
+                Op::SetLocal(depth, index) => {
+                    let value = self.pop();
+                    self.frame(depth).slots.borrow_mut()[index as usize] = value;
+                    self.push(LispVal::Unspecified);
+                }
+                Op::Global(name) => {
+                    let value = self.closure.globals.get(name)?;
+                    self.push(value);
+                }
+                Op::SetGlobal(name) => {
+                    let value = self.pop();
+                    self.closure.globals.set(name, value)?;
+                    self.push(LispVal::Unspecified);
+                }
+                Op::DefineGlobal(name) => {
+                    let value = self.pop();
+                    self.closure.globals.define(name, value);
+                    self.push(LispVal::Unspecified);
+                }
+                Op::Closure(index) => {
+                    let closure = Closure {
+                        template: self.closure.template.templates[index as usize].clone(),
+                        locals: self.locals.clone(),
+                        globals: self.closure.globals.clone(),
+                    };
+                    self.push(LispVal::Closure(gc::alloc(closure)));
+                }
+                Op::NamedClosure(index) => {
+                    let locals = gc::alloc(Locals {
+                        slots: RefCell::new(vec![LispVal::Unspecified]),
+                        parent: self.locals.clone(),
+                    });
+                    let closure = LispVal::Closure(gc::alloc(Closure {
+                        template: self.closure.template.templates[index as usize].clone(),
+                        locals: Some(locals.clone()),
+                        globals: self.closure.globals.clone(),
+                    }));
+                    locals.slots.borrow_mut()[0] = closure.clone();
+                    self.push(closure);
+                }
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Jump(target) => self.pc = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !is_true(&self.pop()) {
+                        self.pc = target as usize;
+                    }
+                }
+                Op::JumpIfFalseOrPop(target) => {
+                    if is_true(self.stack.last().expect("operand stack underflow")) {
+                        self.pop();
+                    } else {
+                        self.pc = target as usize;
+                    }
+                }
+                Op::JumpIfTrueOrPop(target) => {
+                    if is_true(self.stack.last().expect("operand stack underflow")) {
+                        self.pc = target as usize;
+                    } else {
+                        self.pop();
+                    }
+                }
+                Op::Case(data, target) => {
+                    let key = self.stack.last().expect("operand stack underflow");
+                    let data = &self.closure.template.cases[data as usize];
+                    if data.iter().any(|datum| is_eqv(datum, key)) {
+                        self.pop();
+                    } else {
+                        self.pc = target as usize;
+                    }
+                }
+                Op::Call(argc) => {
+                    if let Some(state) = self.call(argc as usize, false)? {
+                        return Ok(state);
+                    }
+                }
+                Op::TailCall(argc) => {
+                    if let Some(state) = self.call(argc as usize, true)? {
+                        return Ok(state);
+                    }
+                }
+                Op::Control(op, argc) => {
+                    let args = self.stack.split_off(self.stack.len() - argc as usize);
+                    self.save();
+                    return self.control(op, args);
+                }
+                Op::TailControl(op, argc) => {
+                    let args = self.stack.split_off(self.stack.len() - argc as usize);
+                    return self.control(op, args);
+                }
+                Op::Return => return Ok(State::Return(self.pop())),
+            }
+        }
+    }
+
+    /// Calls the procedure below the top `argc` operands. Primitives and
+    /// compiled procedures run without leaving `execute`; anything else is
+    /// left to `apply`.
+    fn call(&mut self, argc: usize, tail: bool) -> Result<Option<State>, LispError> {
+        let start = self.stack.len() - argc;
+        if let LispVal::PrimitiveFunc(primitive) = self.stack[start - 1] {
+            let result = (primitive.func)(&self.stack[start..]);
+            self.stack.truncate(start - 1);
+            let value = result?;
+            if tail {
+                return Ok(Some(State::Return(value)));
+            }
+            self.push(value);
+            return Ok(None);
+        }
+        let args = self.stack.split_off(start);
+        let func = self.pop();
+        if !tail {
+            self.save();
+        }
+        match func {
+            LispVal::Closure(closure) => {
+                self.enter(closure, args)?;
+                Ok(None)
+            }
+            func => Ok(Some(State::Apply(func, args))),
+        }
+    }
+
+    fn resume(&mut self, frame: Frame, value: LispVal) -> StateResult {
+        match frame {
+            Frame::Code(closure, pc, locals) => {
+                self.closure = closure;
+                self.pc = pc;
+                self.locals = locals;
+                self.push(value);
+                Ok(State::Run)
+            }
+            Frame::WindBefore(before, thunk, after) => {
+                let wind = gc::alloc(Wind {
+                    before,
+                    after,
+                    depth: eval::wind_depth(&self.winders) + 1,
+                    parent: self.winders.take(),
+                });
+                self.winders = Some(wind.clone());
+                self.frames.push(Frame::WindBody(wind));
+                Ok(State::Apply(thunk, vec![]))
+            }
+            Frame::WindBody(wind) => {
+                self.winders = wind.parent.clone();
+                self.frames.push(Frame::WindAfter(value));
+                Ok(State::Apply(wind.after.clone(), vec![]))
+            }
+            Frame::WindAfter(result) => Ok(State::Return(result)),
+            Frame::Travel(mut steps, target, handlers, result) => match steps.pop() {
+                Some(step) => {
+                    self.winders = step.winders;
+                    self.frames
+                        .push(Frame::Travel(steps, target, handlers, result));
+                    Ok(State::Apply(step.thunk, vec![]))
+                }
+                None => {
+                    self.winders = target;
+                    self.handlers = handlers;
+                    Ok(State::Return(result))
+                }
+            },
+            Frame::Handlers(handlers) => {
+                self.handlers = handlers;
+                Ok(State::Return(value))
+            }
+            Frame::Raised(obj) => Err(LispError::Default(format!(
+                "Handler returned from non-continuable raise of {}",
+                obj
+            ))),
+        }
+    }
+
+    fn apply(&mut self, func: LispVal, args: Vec<LispVal>) -> StateResult {
+        match func {
+            LispVal::Closure(closure) => {
+                self.enter(closure, args)?;
+                Ok(State::Run)
+            }
+            LispVal::PrimitiveFunc(primitive) => Ok(State::Return((primitive.func)(&args)?)),
+            LispVal::NativeFunc(native) => Ok(State::Return(native.call(args)?)),
+            LispVal::VmContinuation(k) => match args.len() {
+                0 | 1 => {
+                    let result = args.into_iter().next().unwrap_or(LispVal::Unspecified);
+                    Ok(self.throw(&k, result))
+                }
+                _ => Err(LispError::NumArgs(1, args)),
+            },
+            LispVal::Func(_) => Ok(State::Return(eval::apply(func, args)?)),
+            _ => Err(LispError::NotFunction(func)),
+        }
+    }
+
+    fn control(&mut self, op: ControlOp, mut args: Vec<LispVal>) -> StateResult {
+        match (op, args.len()) {
+            (ControlOp::CallCC, 1) => {
+                let k = Continuation {
+                    frames: self.frames.clone(),
+                    stack: self.stack.clone(),
+                    winders: self.winders.clone(),
+                    handlers: self.handlers.clone(),
+                };
+                let func = args.remove(0);
+                Ok(State::Apply(
+                    func,
+                    vec![LispVal::VmContinuation(gc::alloc(k))],
+                ))
+            }
+            (ControlOp::DynamicWind, 3) => {
+                let after = args.pop().unwrap();
+                let thunk = args.pop().unwrap();
+                let before = args.pop().unwrap();
+                self.frames
+                    .push(Frame::WindBefore(before.clone(), thunk, after));
+                Ok(State::Apply(before, vec![]))
+            }
+            (ControlOp::Apply, 2) => {
+                let spread = args.pop().unwrap();
+                let func = args.pop().unwrap();
+                Ok(State::Apply(func, spread_args(spread)?))
+            }
+            (ControlOp::WithHandler, 2) => {
+                let thunk = args.pop().unwrap();
+                let handler = args.pop().unwrap();
+                self.frames.push(Frame::Handlers(self.handlers.clone()));
+                self.handlers = Some(gc::alloc(Handler {
+                    handler,
+                    parent: self.handlers.take(),
+                }));
+                Ok(State::Apply(thunk, vec![]))
+            }
+            (ControlOp::Raise, 1) => self.raise(args.remove(0), false),
+            (ControlOp::RaiseContinuable, 1) => self.raise(args.remove(0), true),
+            (ControlOp::CallCC, _) | (ControlOp::Raise, _) | (ControlOp::RaiseContinuable, _) => {
+                Err(LispError::NumArgs(1, args))
+            }
+            (ControlOp::DynamicWind, _) => Err(LispError::NumArgs(3, args)),
+            (ControlOp::Apply, _) | (ControlOp::WithHandler, _) => Err(LispError::NumArgs(2, args)),
+        }
+    }
+
+    /// Calls the current handler with `obj`, as `Machine::raise` does in the
+    /// tree-walking evaluator.
+    fn raise(&mut self, obj: LispVal, continuable: bool) -> StateResult {
+        let handler = match self.handlers.clone() {
+            Some(handler) => handler,
+            None => {
+                return Err(match obj {
+                    LispVal::Error(err) => (*err).clone(),
+                    obj => LispError::Raised(obj),
+                })
+            }
+        };
+        self.frames.push(Frame::Handlers(self.handlers.take()));
+        if !continuable {
+            self.frames.push(Frame::Raised(obj.clone()));
+        }
+        self.handlers = handler.parent.clone();
+        Ok(State::Apply(handler.handler.clone(), vec![obj]))
+    }
+
+    /// Passes `result` to the continuation `k`, running the `dynamic-wind`
+    /// thunks between here and there first.
+    fn throw(&mut self, k: &Continuation, result: LispVal) -> State {
+        let steps = travel(&self.winders, &k.winders);
+        self.frames = k.frames.clone();
+        self.stack = k.stack.clone();
+        self.frames.push(Frame::Travel(
+            steps,
+            k.winders.clone(),
+            k.handlers.clone(),
+            result,
+        ));
+        State::Return(LispVal::Unspecified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::interpreter::Engine;
+    use crate::parser::int;
+    use crate::parser::parse_program;
+    use crate::primitives::primitive_env;
+    use crate::vm::*;
+
+    /// Evaluates every expression in `input` with both engines, checking
+    /// that they agree, and returns the last value.
+    fn run(input: &str) -> Result<LispVal, LispError> {
+        let results: Vec<_> = [Engine::Bytecode, Engine::TreeWalking]
+            .iter()
+            .map(|engine| {
+                let env = primitive_env(*engine);
+                let mut result = Ok(LispVal::Unspecified);
+                for expr in parse_program(input).unwrap() {
+                    result = engine.eval(&env, &expr);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                result.map(|value| value.to_string())
+            })
+            .collect();
+        assert_eq!(results[0], results[1], "engines disagree on {}", input);
+        let env = primitive_env(Engine::Bytecode);
+        let mut result = LispVal::Unspecified;
+        for expr in parse_program(input).unwrap() {
+            result = eval(&env, &expr)?;
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn closure_test() {
+        assert_eq!(
+            run("(define (make-counter)
+                   (define n 0)
+                   (lambda () (set! n (+ n 1)) n))
+                 (define c (make-counter))
+                 (c) (c)
+                 (+ (c) ((make-counter)))")
+            .unwrap(),
+            int(4)
+        );
+        assert_eq!(
+            run("((lambda (a . rest) (list a rest)) 1 2 3)")
+                .unwrap()
+                .to_string(),
+            "(1 (2 3))"
+        );
+        assert_eq!(
+            run("((lambda (a b) a) 1)").unwrap_err(),
+            LispError::NumArgs(2, vec![int(1)])
+        );
+        assert_eq!(
+            run("(let loop ((i 0) (acc '())) (if (= i 3) acc (loop (+ i 1) (cons i acc))))")
+                .unwrap()
+                .to_string(),
+            "(2 1 0)"
+        );
+    }
+
+    #[test]
+    fn special_form_test() {
+        assert_eq!(run("(and 1 #f 2)").unwrap(), LispVal::Boolean(false));
+        assert_eq!(run("(and 1 2)").unwrap(), int(2));
+        assert_eq!(run("(or #f 2 3)").unwrap(), int(2));
+        assert_eq!(run("(or)").unwrap(), LispVal::Boolean(false));
+        assert_eq!(
+            run(
+                "(define (size x) (case x ((1 2) 'low) ((3) 'mid) (else 'high)))
+                 (list (size 1) (size 3) (size 9))"
+            )
+            .unwrap()
+            .to_string(),
+            "(low mid high)"
+        );
+        assert_eq!(run("(case 5 ((1) 'one))").unwrap(), LispVal::Unspecified);
+        assert_eq!(
+            run("(cond ((eq? 'a 'b) 1) ((car '(#f)) 2) (else 3))").unwrap(),
+            int(3)
+        );
+        assert_eq!(run("(define x 1) (set! x (+ x 1)) x").unwrap(), int(2));
+    }
+
+    #[test]
+    fn binding_form_test() {
+        // The examples from R7RS section 4.2.
+        assert_eq!(
+            run("(let ((x 2) (y 3)) (let* ((x 7) (z (+ x y))) (* z x)))").unwrap(),
+            int(70)
+        );
+        assert_eq!(
+            run(
+                "(letrec ((even? (lambda (n) (if (zero? n) #t (odd? (- n 1)))))
+                          (odd? (lambda (n) (if (zero? n) #f (even? (- n 1))))))
+                   (even? 88))"
+            )
+            .unwrap(),
+            LispVal::Boolean(true)
+        );
+        assert_eq!(
+            run("(letrec* ((p (lambda (x) (+ 1 (q (- x 1)))))
+                           (q (lambda (y) (if (zero? y) 0 (+ 1 (p (- y 1))))))
+                           (x (p 5))
+                           (y x))
+                   y)")
+            .unwrap(),
+            int(5)
+        );
+        assert_eq!(
+            run("(let-values (((root rem) (exact-integer-sqrt 32))) (* root rem))").unwrap(),
+            int(35)
+        );
+        // `let-values` inits see the outer bindings, `let*-values` ones the
+        // bindings before them.
+        assert_eq!(
+            run("(let ((a 'a) (b 'b) (x 'x) (y 'y))
+                   (let-values (((a b) (values x y)) ((x y) (values a b)))
+                     (list a b x y)))")
+            .unwrap()
+            .to_string(),
+            "(x y a b)"
+        );
+        assert_eq!(
+            run("(let ((a 'a) (b 'b) (x 'x) (y 'y))
+                   (let*-values (((a b) (values x y)) ((x y) (values a b)))
+                     (list a b x y)))")
+            .unwrap()
+            .to_string(),
+            "(x y x y)"
+        );
+        assert_eq!(
+            run("(let-values (((a . b) (values 1 2 3)) (c (values 4 5))) (list a b c))")
+                .unwrap()
+                .to_string(),
+            "(1 (2 3) (4 5))"
+        );
+        assert_eq!(
+            run("(do ((vec (make-vector 5)) (i 0 (+ i 1))) ((= i 5) vec) (vector-set! vec i i))")
+                .unwrap()
+                .to_string(),
+            "#(0 1 2 3 4)"
+        );
+        assert_eq!(
+            run("(let ((x '(1 3 5 7 9)))
+                   (do ((x x (cdr x)) (sum 0 (+ sum (car x)))) ((null? x) sum)))")
+            .unwrap(),
+            int(25)
+        );
+        assert_eq!(
+            run(
+                "(define-values (q r) (floor/ 17 5)) (define-values (h . t) (values 1 2 3))
+                 (list q r h t)"
+            )
+            .unwrap()
+            .to_string(),
+            "(3 2 1 (2 3))"
+        );
+        assert_eq!(
+            run("(define (f) (define-values (x y) (values 1 2)) (+ x y)) (f)").unwrap(),
+            int(3)
+        );
+        // A local binding of `=>` makes it an ordinary variable.
+        assert_eq!(
+            run("(let ((=> #f)) (cond (#t => 'ok)))").unwrap(),
+            LispVal::symbol("ok")
+        );
+    }
+
+    #[test]
+    fn tail_call_test() {
+        assert_eq!(
+            run("(define (count n) (if (= n 0) 'done (count (- n 1)))) (count 1000000)")
+                .unwrap()
+                .to_string(),
+            "done"
+        );
+        assert_eq!(
+            run("(define (sum n) (if (= n 0) 0 (+ n (sum (- n 1))))) (sum 100000)").unwrap(),
+            int(5000050000)
+        );
+    }
+
+    #[test]
+    fn control_test() {
+        assert_eq!(
+            run("(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))").unwrap(),
+            int(3)
+        );
+        assert_eq!(
+            run("(define (collect)
+                   (define r '())
+                   (define k #f)
+                   (define n 0)
+                   (set! r (cons (call/cc (lambda (c) (set! k c) 0)) r))
+                   (set! n (+ n 1))
+                   (if (< n 3) (k n))
+                   r)
+                 (collect)")
+            .unwrap()
+            .to_string(),
+            "(2 1 0)"
+        );
+        assert_eq!(
+            run("(define trace '())
+                 (define (note x) (set! trace (cons x trace)))
+                 (call/cc (lambda (k)
+                   (dynamic-wind (lambda () (note 'in))
+                                 (lambda () (k 'out))
+                                 (lambda () (note 'after)))))
+                 trace")
+            .unwrap()
+            .to_string(),
+            "(after in)"
+        );
+        assert_eq!(
+            run("(guard (e ((eq? e 'oops) 'caught)) (+ 1 (raise 'oops)))")
+                .unwrap()
+                .to_string(),
+            "caught"
+        );
+        assert_eq!(
+            run(
+                "(with-exception-handler (lambda (e) 10) (lambda () (+ 1 (raise-continuable 'c))))"
+            )
+            .unwrap(),
+            int(11)
+        );
+        assert_eq!(
+            run("(guard (e ((error-object? e) 'error)) (car '()))")
+                .unwrap()
+                .to_string(),
+            "error"
+        );
+        assert_eq!(run("(apply + 1 2 '(3 4))").unwrap(), int(10));
+        assert_eq!(
+            run("(undefined-thing)").unwrap_err().to_string(),
+            "Unbound variable: undefined-thing"
+        );
+    }
+}